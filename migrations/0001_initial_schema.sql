@@ -0,0 +1,67 @@
+-- migrations/0001_initial_schema.sql
+--
+-- Schéma initial : toutes les tables utilisées par le module db
+-- Les instructions sont idempotentes pour pouvoir s'appliquer
+-- à une base créée à la main avant les migrations.
+
+CREATE TABLE IF NOT EXISTS persons (
+    id SERIAL PRIMARY KEY,
+    full_name TEXT NOT NULL CHECK (full_name <> '')
+);
+
+CREATE TABLE IF NOT EXISTS genres (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL CHECK (name <> '')
+);
+
+CREATE TABLE IF NOT EXISTS partitions (
+    id SERIAL PRIMARY KEY,
+    title TEXT NOT NULL CHECK (title <> ''),
+    person_id INTEGER,
+    genre_id INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS users (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL CHECK (name <> ''),
+    password_hash TEXT NOT NULL,
+    role TEXT
+);
+
+-- table utilisée par AxumPgSessionStore (axum_database_sessions)
+CREATE TABLE IF NOT EXISTS async_sessions (
+    id VARCHAR(128) NOT NULL PRIMARY KEY,
+    expires INTEGER NULL,
+    session TEXT NOT NULL
+);
+
+-- contraintes d'unicité
+CREATE UNIQUE INDEX IF NOT EXISTS persons_full_name_key ON persons (full_name);
+CREATE UNIQUE INDEX IF NOT EXISTS genres_name_key ON genres (name);
+CREATE UNIQUE INDEX IF NOT EXISTS users_name_key ON users (name);
+
+-- clés étrangères des partitions (ajoutées seulement si elles manquent)
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'partitions_person_id_fkey') THEN
+        ALTER TABLE partitions
+            ADD CONSTRAINT partitions_person_id_fkey
+            FOREIGN KEY (person_id) REFERENCES persons (id) ON DELETE RESTRICT;
+    END IF;
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'partitions_genre_id_fkey') THEN
+        ALTER TABLE partitions
+            ADD CONSTRAINT partitions_genre_id_fkey
+            FOREIGN KEY (genre_id) REFERENCES genres (id) ON DELETE RESTRICT;
+    END IF;
+END
+$$;
+
+CREATE INDEX IF NOT EXISTS partitions_person_id_idx ON partitions (person_id);
+CREATE INDEX IF NOT EXISTS partitions_genre_id_idx ON partitions (genre_id);
+
+-- index pour les recherches "LIKE 'x%'" de find_person_by_name,
+-- find_genre_by_name et find_partition_by_title
+CREATE INDEX IF NOT EXISTS persons_full_name_pattern_idx ON persons (full_name text_pattern_ops);
+CREATE INDEX IF NOT EXISTS genres_name_pattern_idx ON genres (name text_pattern_ops);
+CREATE INDEX IF NOT EXISTS partitions_title_pattern_idx ON partitions (title text_pattern_ops);
+CREATE INDEX IF NOT EXISTS partitions_title_idx ON partitions (title);
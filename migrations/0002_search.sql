@@ -0,0 +1,38 @@
+-- migrations/0002_search.sql
+--
+-- Recherche plein texte insensible aux accents et à la casse
+-- sur les titres des partitions, les noms des musiciens et des genres.
+-- unaccent : "Fauré" = "faure" ; pg_trgm : recherche de sous-chaînes indexée
+
+CREATE EXTENSION IF NOT EXISTS unaccent;
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- unaccent() n'est pas IMMUTABLE et ne peut pas servir dans un index :
+-- on l'enveloppe en précisant le dictionnaire
+CREATE OR REPLACE FUNCTION f_unaccent(TEXT)
+    RETURNS TEXT
+    LANGUAGE sql IMMUTABLE PARALLEL SAFE STRICT
+AS $$
+    SELECT public.unaccent('public.unaccent'::regdictionary, $1)
+$$;
+
+-- configuration plein texte sans racinisation (titres et noms en plusieurs langues)
+-- qui retire les accents des mots du document et de la requête
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_ts_config WHERE cfgname = 'partitions_search') THEN
+        CREATE TEXT SEARCH CONFIGURATION partitions_search (COPY = simple);
+        ALTER TEXT SEARCH CONFIGURATION partitions_search
+            ALTER MAPPING FOR hword, hword_part, word WITH unaccent, simple;
+    END IF;
+END
+$$;
+
+-- index trigrammes pour les recherches "LIKE '%x%'" sans accents ni majuscules
+-- (le document plein texte réunit trois tables et n'est pas indexé)
+CREATE INDEX IF NOT EXISTS partitions_title_trgm_idx
+    ON partitions USING GIN (f_unaccent(lower(title)) gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS persons_full_name_trgm_idx
+    ON persons USING GIN (f_unaccent(lower(full_name)) gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS genres_name_trgm_idx
+    ON genres USING GIN (f_unaccent(lower(name)) gin_trgm_ops);
@@ -0,0 +1,22 @@
+-- migrations/0003_attachments.sql
+--
+-- Fichiers des partitions (PDF, MusicXML, images)
+-- Le contenu est rangé par le Storage (src/storage) sous sa clé,
+-- qui est l'empreinte SHA3-256 du fichier : un même fichier n'est stocké qu'une fois.
+
+CREATE TABLE IF NOT EXISTS attachments (
+    id SERIAL PRIMARY KEY,
+    partition_id INTEGER NOT NULL REFERENCES partitions (id) ON DELETE CASCADE,
+    file_name TEXT NOT NULL CHECK (file_name <> ''),
+    content_type TEXT NOT NULL,
+    size_bytes BIGINT NOT NULL CHECK (size_bytes >= 0),
+    sha3_256 TEXT NOT NULL,
+    uploaded_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS attachments_partition_id_idx ON attachments (partition_id);
+CREATE INDEX IF NOT EXISTS attachments_sha3_256_idx ON attachments (sha3_256);
+
+-- le même fichier n'est attaché qu'une fois à une partition
+CREATE UNIQUE INDEX IF NOT EXISTS attachments_partition_hash_key
+    ON attachments (partition_id, sha3_256);
@@ -0,0 +1,23 @@
+-- migrations/0004_partition_metadata.sql
+--
+-- Renseignements facultatifs des partitions (models::partition::PartitionMetadata)
+
+ALTER TABLE partitions
+    ADD COLUMN IF NOT EXISTS instrumentation TEXT,
+    ADD COLUMN IF NOT EXISTS music_key TEXT,
+    ADD COLUMN IF NOT EXISTS tempo TEXT,
+    ADD COLUMN IF NOT EXISTS difficulty SMALLINT CHECK (difficulty BETWEEN 1 AND 5),
+    ADD COLUMN IF NOT EXISTS duration_seconds INTEGER CHECK (duration_seconds >= 0),
+    ADD COLUMN IF NOT EXISTS opus TEXT,
+    ADD COLUMN IF NOT EXISTS publisher TEXT,
+    ADD COLUMN IF NOT EXISTS edition_year INTEGER CHECK (edition_year BETWEEN 1000 AND 9999),
+    ADD COLUMN IF NOT EXISTS notes TEXT;
+
+CREATE INDEX IF NOT EXISTS partitions_difficulty_idx ON partitions (difficulty);
+CREATE INDEX IF NOT EXISTS partitions_edition_year_idx ON partitions (edition_year);
+
+-- recherches "contient" du filtre, sans accents ni majuscules (voir 0002_search.sql)
+CREATE INDEX IF NOT EXISTS partitions_instrumentation_trgm_idx
+    ON partitions USING GIN (f_unaccent(lower(instrumentation)) gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS partitions_publisher_trgm_idx
+    ON partitions USING GIN (f_unaccent(lower(publisher)) gin_trgm_ops);
@@ -0,0 +1,20 @@
+-- migrations/0005_partition_contributors.sql
+--
+-- Plusieurs musiciens par partition, chacun avec son rôle
+-- (models::contributor::ContributorRole) : compositeur, arrangeur, parolier.
+-- partitions.person_id reste le musicien principal affiché dans les listes ;
+-- il est repris ici comme compositeur.
+
+CREATE TABLE IF NOT EXISTS partition_contributors (
+    partition_id INTEGER NOT NULL REFERENCES partitions (id) ON DELETE CASCADE,
+    person_id INTEGER NOT NULL REFERENCES persons (id) ON DELETE RESTRICT,
+    role TEXT NOT NULL CHECK (role IN ('composer', 'arranger', 'lyricist')),
+    PRIMARY KEY (partition_id, person_id, role)
+);
+
+CREATE INDEX IF NOT EXISTS partition_contributors_person_id_idx
+    ON partition_contributors (person_id);
+
+INSERT INTO partition_contributors (partition_id, person_id, role)
+    SELECT id, person_id, 'composer' FROM partitions WHERE person_id IS NOT NULL
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,34 @@
+-- migrations/0006_genres_and_tags.sql
+--
+-- Plusieurs genres par partition et des étiquettes libres (tags).
+-- partitions.genre_id reste le genre principal affiché dans les listes ;
+-- il est repris dans partition_genres.
+
+CREATE TABLE IF NOT EXISTS partition_genres (
+    partition_id INTEGER NOT NULL REFERENCES partitions (id) ON DELETE CASCADE,
+    genre_id INTEGER NOT NULL REFERENCES genres (id) ON DELETE RESTRICT,
+    PRIMARY KEY (partition_id, genre_id)
+);
+
+CREATE INDEX IF NOT EXISTS partition_genres_genre_id_idx ON partition_genres (genre_id);
+
+INSERT INTO partition_genres (partition_id, genre_id)
+    SELECT id, genre_id FROM partitions WHERE genre_id IS NOT NULL
+ON CONFLICT DO NOTHING;
+
+CREATE TABLE IF NOT EXISTS tags (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL CHECK (name <> '')
+);
+
+-- "Noël" et "noël" sont la même étiquette
+CREATE UNIQUE INDEX IF NOT EXISTS tags_name_key ON tags (lower(name));
+
+-- une étiquette effacée disparaît de toutes les partitions
+CREATE TABLE IF NOT EXISTS partition_tags (
+    partition_id INTEGER NOT NULL REFERENCES partitions (id) ON DELETE CASCADE,
+    tag_id INTEGER NOT NULL REFERENCES tags (id) ON DELETE CASCADE,
+    PRIMARY KEY (partition_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS partition_tags_tag_id_idx ON partition_tags (tag_id);
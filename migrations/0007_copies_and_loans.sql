@@ -0,0 +1,34 @@
+-- migrations/0007_copies_and_loans.sql
+--
+-- Bibliothèque : exemplaires imprimés des partitions et prêts aux membres.
+-- Une ligne de copies est un lot d'exemplaires identiques rangés au même endroit
+-- (ex. : 20 exemplaires pour le chœur dans la boîte 3).
+-- Un prêt emprunte un ou plusieurs exemplaires d'un lot ;
+-- il est en cours tant que returned_at est NULL.
+
+CREATE TABLE IF NOT EXISTS copies (
+    id SERIAL PRIMARY KEY,
+    partition_id INTEGER NOT NULL REFERENCES partitions (id) ON DELETE CASCADE,
+    quantity INTEGER NOT NULL DEFAULT 1 CHECK (quantity >= 1),
+    location TEXT,
+    condition TEXT NOT NULL DEFAULT 'good'
+        CHECK (condition IN ('new', 'good', 'worn', 'damaged')),
+    added_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS copies_partition_id_idx ON copies (partition_id);
+
+CREATE TABLE IF NOT EXISTS loans (
+    id SERIAL PRIMARY KEY,
+    copy_id INTEGER NOT NULL REFERENCES copies (id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users (id) ON DELETE RESTRICT,
+    quantity INTEGER NOT NULL DEFAULT 1 CHECK (quantity >= 1),
+    loaned_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    due_date DATE NOT NULL,
+    returned_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS loans_copy_id_idx ON loans (copy_id);
+CREATE INDEX IF NOT EXISTS loans_user_id_idx ON loans (user_id);
+-- prêts en cours, pour les disponibilités et la liste des retards
+CREATE INDEX IF NOT EXISTS loans_active_due_date_idx ON loans (due_date) WHERE returned_at IS NULL;
@@ -0,0 +1,27 @@
+-- migrations/0008_setlists.sql
+--
+-- Programmes de concert (setlists) composés à partir du catalogue.
+-- Les pièces d'un programme sont numérotées à partir de 1 (position) ;
+-- la contrainte d'unicité est vérifiée en fin de transaction
+-- pour pouvoir échanger deux positions.
+
+CREATE TABLE IF NOT EXISTS setlists (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL CHECK (name <> ''),
+    concert_date DATE,
+    venue TEXT,
+    notes TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS setlist_items (
+    id SERIAL PRIMARY KEY,
+    setlist_id INTEGER NOT NULL REFERENCES setlists (id) ON DELETE CASCADE,
+    partition_id INTEGER NOT NULL REFERENCES partitions (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL CHECK (position >= 1),
+    notes TEXT,
+    CONSTRAINT setlist_items_position_key UNIQUE (setlist_id, position) DEFERRABLE INITIALLY DEFERRED
+);
+
+CREATE INDEX IF NOT EXISTS setlist_items_partition_id_idx ON setlist_items (partition_id);
+CREATE INDEX IF NOT EXISTS setlists_concert_date_idx ON setlists (concert_date);
@@ -0,0 +1,26 @@
+-- migrations/0009_audit_log.sql
+--
+-- Journal des modifications du catalogue et des utilisateurs.
+-- Une ligne par ajout, modification ou effacement, écrite dans la même
+-- transaction que la modification : si elle échoue, rien n'est enregistré.
+-- before / after : la ligne avant et après la modification (to_jsonb),
+-- NULL pour before d'un ajout et pour after d'un effacement
+-- (sauf si les partitions ont été réaffectées : { "reassigned_to": id, "partitions": n }).
+-- Les mots de passe (hash) ne sont jamais copiés dans le journal.
+-- actor_name est gardé même si l'utilisateur est effacé par la suite.
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    actor_id UUID REFERENCES users (id) ON DELETE SET NULL,
+    actor_name TEXT NOT NULL,
+    action TEXT NOT NULL CHECK (action IN ('create', 'update', 'delete')),
+    entity_type TEXT NOT NULL CHECK (entity_type IN ('person', 'genre', 'partition', 'user')),
+    entity_id TEXT NOT NULL,
+    before JSONB,
+    after JSONB
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_at_idx ON audit_log (at DESC);
+CREATE INDEX IF NOT EXISTS audit_log_entity_idx ON audit_log (entity_type, entity_id, at DESC);
+CREATE INDEX IF NOT EXISTS audit_log_actor_name_idx ON audit_log (actor_name);
@@ -0,0 +1,29 @@
+-- migrations/0010_soft_delete.sql
+--
+-- Corbeille des partitions, des musiciens et des genres.
+-- Effacer remplit deleted_at : la ligne disparaît des listes et des recherches
+-- mais peut être restaurée (deleted_at remis à NULL) jusqu'à sa purge,
+-- à la main ou après TRASH_RETENTION_DAYS jours (tâche de fond, src/trash.rs).
+-- Dans le journal (audit_log), la mise à la corbeille est un effacement
+-- dont after est la ligne avec son deleted_at ; la purge, un effacement sans after.
+
+ALTER TABLE partitions ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+ALTER TABLE persons ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+ALTER TABLE genres ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+
+-- un nom à la corbeille peut être réutilisé : l'unicité ne porte que sur les lignes actives
+-- (une restauration est refusée si le nom a été repris entre-temps)
+DROP INDEX IF EXISTS persons_full_name_key;
+CREATE UNIQUE INDEX IF NOT EXISTS persons_full_name_key
+    ON persons (full_name) WHERE deleted_at IS NULL;
+DROP INDEX IF EXISTS genres_name_key;
+CREATE UNIQUE INDEX IF NOT EXISTS genres_name_key
+    ON genres (name) WHERE deleted_at IS NULL;
+
+-- pages de la corbeille et purge des lignes trop anciennes
+CREATE INDEX IF NOT EXISTS partitions_deleted_at_idx
+    ON partitions (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS persons_deleted_at_idx
+    ON persons (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS genres_deleted_at_idx
+    ON genres (deleted_at) WHERE deleted_at IS NOT NULL;
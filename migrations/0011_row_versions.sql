@@ -0,0 +1,10 @@
+-- migrations/0011_row_versions.sql
+--
+-- Verrouillage optimiste des musiciens, des genres et des partitions.
+-- Chaque modification incrémente version ; les formulaires renvoient la version
+-- qu'ils ont affichée et la modification est refusée (page de conflit)
+-- si la ligne a été modifiée entre-temps par quelqu'un d'autre.
+
+ALTER TABLE persons ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE genres ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE partitions ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,72 @@
+-- migrations/0012_normalized_names.sql
+--
+-- "Jazz", "jazz " et "Jàzz" sont le même genre, "Big  band" et "big band" aussi ;
+-- "Fauré" et "faure" le même musicien.
+-- L'unicité des noms porte sur leur forme normalisée (sans espaces autour, les
+-- espaces intérieurs réduits à un seul, sans majuscules ni accents), toujours
+-- sur les seules lignes actives (migration 0010).
+-- Les doublons déjà présents sont d'abord fusionnés dans le plus ancien,
+-- comme le fait la fusion des doublons (/persons/duplicates, /genres/duplicates).
+
+-- la forme normalisée d'un nom ou d'un titre, utilisable dans un index
+CREATE OR REPLACE FUNCTION f_normalize(TEXT)
+    RETURNS TEXT
+    LANGUAGE sql IMMUTABLE PARALLEL SAFE STRICT
+AS $$
+    SELECT f_unaccent(lower(btrim(regexp_replace($1, '\s+', ' ', 'g'))))
+$$;
+
+-- chaque doublon actif et le musicien qui le remplace
+CREATE TEMPORARY TABLE person_duplicates ON COMMIT DROP AS
+    SELECT id, first_value(id) OVER w AS survivor
+    FROM persons
+    WHERE deleted_at IS NULL
+    WINDOW w AS (PARTITION BY f_normalize(full_name) ORDER BY id);
+DELETE FROM person_duplicates WHERE id = survivor;
+
+UPDATE partitions SET person_id = d.survivor, version = version + 1
+    FROM person_duplicates d WHERE partitions.person_id = d.id;
+INSERT INTO partition_contributors (partition_id, person_id, role)
+    SELECT c.partition_id, d.survivor, c.role
+    FROM partition_contributors c INNER JOIN person_duplicates d ON d.id = c.person_id
+ON CONFLICT DO NOTHING;
+DELETE FROM partition_contributors WHERE person_id IN (SELECT id FROM person_duplicates);
+INSERT INTO audit_log (actor_name, action, entity_type, entity_id, before, after)
+    SELECT 'migration', 'delete', 'person', p.id::TEXT, to_jsonb(p),
+        jsonb_build_object('reassigned_to', d.survivor)
+    FROM persons p INNER JOIN person_duplicates d ON d.id = p.id;
+DELETE FROM persons WHERE id IN (SELECT id FROM person_duplicates);
+
+-- chaque doublon actif et le genre qui le remplace
+CREATE TEMPORARY TABLE genre_duplicates ON COMMIT DROP AS
+    SELECT id, first_value(id) OVER w AS survivor
+    FROM genres
+    WHERE deleted_at IS NULL
+    WINDOW w AS (PARTITION BY f_normalize(name) ORDER BY id);
+DELETE FROM genre_duplicates WHERE id = survivor;
+
+UPDATE partitions SET genre_id = d.survivor, version = version + 1
+    FROM genre_duplicates d WHERE partitions.genre_id = d.id;
+INSERT INTO partition_genres (partition_id, genre_id)
+    SELECT g.partition_id, d.survivor
+    FROM partition_genres g INNER JOIN genre_duplicates d ON d.id = g.genre_id
+ON CONFLICT DO NOTHING;
+DELETE FROM partition_genres WHERE genre_id IN (SELECT id FROM genre_duplicates);
+INSERT INTO audit_log (actor_name, action, entity_type, entity_id, before, after)
+    SELECT 'migration', 'delete', 'genre', g.id::TEXT, to_jsonb(g),
+        jsonb_build_object('reassigned_to', d.survivor)
+    FROM genres g INNER JOIN genre_duplicates d ON d.id = g.id;
+DELETE FROM genres WHERE id IN (SELECT id FROM genre_duplicates);
+
+-- les index uniques remplacent ceux de la migration 0010 (même nom) ;
+-- les ON CONFLICT de l'import CSV (db::partitions) reprennent leur expression
+DROP INDEX IF EXISTS persons_full_name_key;
+CREATE UNIQUE INDEX IF NOT EXISTS persons_full_name_key
+    ON persons (f_normalize(full_name)) WHERE deleted_at IS NULL;
+DROP INDEX IF EXISTS genres_name_key;
+CREATE UNIQUE INDEX IF NOT EXISTS genres_name_key
+    ON genres (f_normalize(name)) WHERE deleted_at IS NULL;
+
+-- recherche des doublons d'une partition : même titre normalisé, même compositeur
+CREATE INDEX IF NOT EXISTS partitions_normalized_title_idx
+    ON partitions (person_id, f_normalize(title)) WHERE deleted_at IS NULL;
@@ -0,0 +1,10 @@
+-- migrations/0013_audit_tags.sql
+--
+-- Les étiquettes entrent au journal des modifications (audit_log) :
+-- ajout, renommage et effacement d'une étiquette (entity_type 'tag').
+-- Les musiciens (rôles) et les étiquettes d'une partition sont repris
+-- dans les copies de la partition (before / after, voir db::audit::snapshot).
+
+ALTER TABLE audit_log DROP CONSTRAINT IF EXISTS audit_log_entity_type_check;
+ALTER TABLE audit_log ADD CONSTRAINT audit_log_entity_type_check
+    CHECK (entity_type IN ('person', 'genre', 'partition', 'user', 'tag'));
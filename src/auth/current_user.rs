@@ -0,0 +1,145 @@
+//! src/auth/current_user.rs
+//!
+//! Extracteurs qui donnent l'utilisateur connecté à n'importe quel handler
+//!
+//! L'utilisateur est retrouvé grâce au token JWT de l'en-tête Authorization: Bearer
+//! s'il est présent (clients de l'API), sinon grâce à la clé SESSION_USER_ID
+//! de la session (posée par login_session et signup_session).
+//! La session est détruite si elle dépasse la durée de vie absolue
+//! (SESSION_MAX_HOURS dans le fichier .env)
+
+use axum::{
+    async_trait,
+    extract::{Extension, FromRequest, RequestParts},
+    http::header::AUTHORIZATION,
+};
+use axum_database_sessions::{AxumPgPool, AxumSession};
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::auth::jwt::Claims;
+use crate::auth::session::{session_max_lifetime, SESSION_LOGIN_AT, SESSION_USER_ID};
+use crate::db::users::find_user_by_id;
+use crate::error::AppError;
+use crate::models::audit::Actor;
+use crate::models::user::User;
+
+///
+/// Utilisateur connecté, chargé depuis la DB à partir de la session
+///
+/// Renvoie AppError::Unauthorized si personne n'est connecté
+/// ou si la session a expiré
+///
+#[derive(Debug, Clone)]
+pub struct CurrentUser(pub User);
+
+impl CurrentUser {
+    pub fn has_role(&self, allowed: &[String]) -> bool {
+        let role = self.0.role.trim().to_lowercase();
+        allowed.iter().any(|r| *r == role)
+    }
+}
+
+#[async_trait]
+impl<B> FromRequest<B> for CurrentUser
+where
+    B: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let Extension(pool) = Extension::<PgPool>::from_request(req)
+            .await
+            .map_err(|e| AppError::Anyhow(anyhow::anyhow!("{}", e)))?;
+
+        // client de l'API : le token remplace le cookie de session
+        if req.headers().contains_key(AUTHORIZATION) {
+            let claims = Claims::from_request(req)
+                .await
+                .map_err(|_| AppError::Unauthorized)?;
+            return load_user(claims.sub, &pool).await;
+        }
+
+        let session = AxumSession::<AxumPgPool>::from_request(req)
+            .await
+            .map_err(|_| AppError::Unauthorized)?;
+
+        let user_id: Uuid = session
+            .get(SESSION_USER_ID)
+            .await
+            .ok_or(AppError::Unauthorized)?;
+
+        // durée de vie absolue : on se reconnecte après SESSION_MAX_HOURS,
+        // même si la session a été utilisée entre-temps
+        let login_at: i64 = session.get(SESSION_LOGIN_AT).await.unwrap_or(0);
+        if Utc::now().timestamp() - login_at > session_max_lifetime().num_seconds() {
+            tracing::info!("session expirée pour l'utilisateur {}", user_id);
+            session.destroy().await;
+            return Err(AppError::Unauthorized);
+        }
+
+        load_user(user_id, &pool).await
+    }
+}
+
+async fn load_user(user_id: Uuid, pool: &PgPool) -> Result<CurrentUser, AppError> {
+    match find_user_by_id(user_id, pool).await {
+        Ok(user) => Ok(CurrentUser(user)),
+        Err(sqlx::Error::RowNotFound) => Err(AppError::Unauthorized),
+        Err(e) => Err(AppError::Sqlx(e)),
+    }
+}
+
+///
+/// Utilisateur connecté s'il y en a un
+///
+/// Ne rejette jamais la requête : sert aux pages visibles par tous
+/// pour afficher le nom de l'utilisateur dans base.html
+///
+#[derive(Debug, Clone, Default)]
+pub struct MaybeUser(pub Option<User>);
+
+impl MaybeUser {
+    ///
+    /// nom de l'utilisateur à insérer dans le contexte Tera sous "current_user"
+    ///
+    pub fn name(&self) -> Option<&str> {
+        self.0.as_ref().map(|user| user.name.as_str())
+    }
+}
+
+#[async_trait]
+impl<B> FromRequest<B> for MaybeUser
+where
+    B: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        match CurrentUser::from_request(req).await {
+            Ok(CurrentUser(user)) => Ok(MaybeUser(Some(user))),
+            Err(AppError::Unauthorized) => Ok(MaybeUser(None)),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+///
+/// Auteur des modifications écrites dans le journal (audit_log)
+///
+/// Même règle que CurrentUser : les routes qui modifient la DB
+/// sont réservées aux utilisateurs connectés (require_editor)
+///
+#[async_trait]
+impl<B> FromRequest<B> for Actor
+where
+    B: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let CurrentUser(user) = CurrentUser::from_request(req).await?;
+        Ok(Actor::from(&user))
+    }
+}
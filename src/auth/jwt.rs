@@ -4,10 +4,7 @@ use std::fmt::Display;
 
 use chrono::{Duration, Utc};
 
-use anyhow::{anyhow, Context};
-
-use argon2::password_hash::SaltString;
-use argon2::{Argon2, PasswordHash};
+use anyhow::Context;
 
 use axum::{
     async_trait,
@@ -33,6 +30,27 @@ static KEYS: Lazy<Keys> = Lazy::new(|| {
     Keys::new(secret.as_bytes())
 });
 
+///
+/// Durées de validité des tokens
+/// JWT_ACCESS_MINUTES (défaut 60) et JWT_REFRESH_DAYS (défaut 30) dans le fichier .env
+///
+static TOKEN_LIFETIMES: Lazy<TokenLifetimes> = Lazy::new(|| TokenLifetimes {
+    access: Duration::minutes(number_from_env("JWT_ACCESS_MINUTES", 60)),
+    refresh: Duration::days(number_from_env("JWT_REFRESH_DAYS", 30)),
+});
+
+struct TokenLifetimes {
+    access: Duration,
+    refresh: Duration,
+}
+
+fn number_from_env(var: &str, default: i64) -> i64 {
+    std::env::var(var)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
 pub struct Keys {
     pub encoding: EncodingKey,
     pub decoding: DecodingKey,
@@ -51,18 +69,26 @@ impl Keys {
 pub struct AuthBody {
     pub access_token: String,
     pub token_type: String,
+    pub expires_in: i64,
+    pub refresh_token: String,
 }
 
 impl AuthBody {
-    #[allow(dead_code)]
-    pub fn new(access_token: String) -> Self {
+    pub fn new(access_token: String, refresh_token: String) -> Self {
         Self {
             access_token,
             token_type: "Bearer".to_string(),
+            expires_in: TOKEN_LIFETIMES.access.num_seconds(),
+            refresh_token,
         }
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct RefreshPayload {
+    pub refresh_token: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SignInPayload {
     pub username: String,
@@ -76,24 +102,38 @@ pub struct LoginPayload {
     pub password: String,
 }
 
+///
+/// Un token d'accès sert à appeler l'API,
+/// un token de rafraîchissement sert seulement à obtenir de nouveaux tokens
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenKind {
+    Access,
+    Refresh,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: Uuid,
     pub username: String,
     pub exp: i64,
+    pub kind: TokenKind,
 }
 
 impl Claims {
-    #[allow(dead_code)]
-    pub fn new(id: Uuid) -> Self {
-        let username = "".to_string();
+    pub fn new(id: Uuid, username: String, kind: TokenKind) -> Self {
         let iat = Utc::now();
-        let exp = iat + Duration::hours(24);
+        let exp = match kind {
+            TokenKind::Access => iat + TOKEN_LIFETIMES.access,
+            TokenKind::Refresh => iat + TOKEN_LIFETIMES.refresh,
+        };
 
         Self {
             sub: id,
             username,
             exp: exp.timestamp(),
+            kind,
         }
     }
 }
@@ -126,62 +166,15 @@ where
         let token_data = decode::<Claims>(bearer.token(), &KEYS.decoding, &Validation::default())
             .map_err(|_| AppError::InvalidJWTToken)?;
 
+        // un token de rafraîchissement ne donne pas accès à l'API
+        if token_data.claims.kind != TokenKind::Access {
+            return Err(AppError::InvalidJWTToken);
+        }
+
         Ok(token_data.claims)
     }
 }
 
-///
-/// Utility function to hash passwords
-/// Returns a String with hashed password or anyhow::Error
-///
-#[allow(dead_code)]
-pub async fn hash_password(password: String) -> Result<String, anyhow::Error> {
-    // Argon2 hashing is designed to be computationally intensive,
-    // so we need to do this on a blocking thread.
-    Ok(
-        tokio::task::spawn_blocking(move || -> Result<String, anyhow::Error> {
-            let salt = SaltString::generate(rand::thread_rng());
-            Ok(
-                PasswordHash::generate(Argon2::default(), password, salt.as_str())
-                    .map_err(|e| anyhow::anyhow!("failed to generate password hash: {}", e))?
-                    .to_string(),
-            )
-        })
-        .await
-        .context("panic in generating password hash")??,
-    )
-}
-
-///
-/// Function verify_password
-/// 1) password to verify
-/// 2) stored password
-///
-/// uses argon2
-///
-#[allow(dead_code)]
-pub async fn verify_password(
-    entered_password: String,
-    stored_password_hash: String,
-) -> Result<(), anyhow::Error> {
-    Ok(
-        tokio::task::spawn_blocking(move || -> Result<(), anyhow::Error> {
-            let hash = PasswordHash::new(&stored_password_hash)
-                .map_err(|e| anyhow::anyhow!("invalid password hash: {}", e))?;
-
-            hash.verify_password(&[&Argon2::default()], entered_password)
-                .map_err(|e| match e {
-                    argon2::password_hash::Error::Password => {
-                        anyhow!("Mot de passe inexact").into()
-                    }
-                    _ => anyhow::anyhow!("failed to verify password hash: {}", e).into(),
-                })
-        })
-        .await
-        .context("panic in verifying password hash")??,
-    )
-}
-
 #[allow(dead_code)]
 pub fn generate_jwt(claims: &Claims) -> anyhow::Result<String> {
     encode(
@@ -192,17 +185,24 @@ pub fn generate_jwt(claims: &Claims) -> anyhow::Result<String> {
     )
     .map_err(|e| anyhow::anyhow!(e))
 }
-#[allow(dead_code)]
-pub fn sign_jwt(id: Uuid) -> anyhow::Result<String> {
+pub fn sign_jwt(id: Uuid, username: String, kind: TokenKind) -> anyhow::Result<String> {
     Ok(jsonwebtoken::encode(
         &Header::default(),
-        &Claims::new(id),
+        &Claims::new(id, username, kind),
         //&EncodingKey::from_secret(JWT_SECRET.as_bytes()),
         &KEYS.encoding,
     )?)
 }
 
-#[allow(dead_code)]
+///
+/// Returns an AuthBody with a new access token and a new refresh token
+///
+pub fn issue_tokens(id: Uuid, username: &str) -> anyhow::Result<AuthBody> {
+    let access_token = sign_jwt(id, username.to_string(), TokenKind::Access)?;
+    let refresh_token = sign_jwt(id, username.to_string(), TokenKind::Refresh)?;
+    Ok(AuthBody::new(access_token, refresh_token))
+}
+
 pub fn verify_jwt(token: &str) -> anyhow::Result<Claims> {
     jsonwebtoken::decode(
         token,
@@ -218,7 +218,6 @@ pub fn verify_jwt(token: &str) -> anyhow::Result<Claims> {
 /// Returns AuthPayload with username and password
 /// under the form 'client_name' and 'client_secret'
 ///
-#[allow(dead_code)]
 pub async fn basic_authentication(headers: &HeaderMap) -> Result<LoginPayload, anyhow::Error> {
     // The header value, if present, must be a valid UTF8 string
     let header_value = headers
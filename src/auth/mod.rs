@@ -1,4 +1,7 @@
 //! src/auth/mod.rs
 
+pub(crate) mod current_user;
 pub(crate) mod jwt;
+pub(crate) mod password;
+pub(crate) mod roles;
 pub(crate) mod session;
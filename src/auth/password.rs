@@ -0,0 +1,137 @@
+//! src/auth/password.rs
+//!
+//! Service unique de hachage et de vérification des mots de passe
+//!
+//! Les nouveaux mots de passe sont hachés avec Argon2id.
+//! La vérification reconnaît l'algorithme du hash stocké (chaîne PHC) :
+//! argon2id, argon2i, argon2d, pbkdf2-sha256, pbkdf2-sha512,
+//! ainsi que l'ancien format SHA3-256 hexadécimal sans sel.
+//! Un hash qui n'est pas en Argon2id avec les paramètres courants est signalé
+//! par `needs_rehash` pour être recalculé après une connexion réussie.
+//!
+//! Paramètres Argon2 dans le fichier .env :
+//! ARGON2_MEMORY_KIB (défaut 19456), ARGON2_ITERATIONS (défaut 2), ARGON2_PARALLELISM (défaut 1)
+
+use anyhow::Context;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use once_cell::sync::Lazy;
+use pbkdf2::Pbkdf2;
+use rand_core::OsRng;
+use sha3::Digest;
+
+///
+/// Paramètres Argon2id, lus une seule fois dans l'environnement
+///
+static ARGON2_PARAMS: Lazy<Params> = Lazy::new(|| {
+    let memory = number_from_env("ARGON2_MEMORY_KIB", 19_456);
+    let iterations = number_from_env("ARGON2_ITERATIONS", 2);
+    let parallelism = number_from_env("ARGON2_PARALLELISM", 1);
+    Params::new(memory, iterations, parallelism, None)
+        .expect("paramètres Argon2 invalides dans le fichier .env")
+});
+
+fn number_from_env(var: &str, default: u32) -> u32 {
+    std::env::var(var)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+fn argon2id() -> Argon2<'static> {
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, ARGON2_PARAMS.clone())
+}
+
+///
+/// Résultat de la vérification d'un mot de passe
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    /// le mot de passe est correct ; needs_rehash si le hash stocké doit être mis à jour
+    Valid { needs_rehash: bool },
+    Invalid,
+}
+
+///
+/// Hashes a password with Argon2id and the configured parameters
+/// Returns a PHC string ($argon2id$...) or anyhow::Error
+///
+pub async fn hash_password(password: String) -> anyhow::Result<String> {
+    // Argon2 hashing is designed to be computationally intensive,
+    // so we need to do this on a blocking thread.
+    tokio::task::spawn_blocking(move || -> anyhow::Result<String> {
+        let salt = SaltString::generate(&mut OsRng);
+        Ok(argon2id()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| anyhow::anyhow!("failed to generate password hash: {}", e))?
+            .to_string())
+    })
+    .await
+    .context("panic in generating password hash")?
+}
+
+///
+/// Verifies a password against a stored hash of any supported scheme
+///
+pub async fn verify_password(
+    password: String,
+    stored_hash: String,
+) -> anyhow::Result<Verification> {
+    tokio::task::spawn_blocking(move || verify_blocking(&password, &stored_hash))
+        .await
+        .context("panic in verifying password hash")?
+}
+
+fn verify_blocking(password: &str, stored_hash: &str) -> anyhow::Result<Verification> {
+    // ancien format : SHA3-256 en hexadécimal, sans sel ni préfixe PHC
+    if !stored_hash.starts_with('$') {
+        let digest = format!("{:x}", sha3::Sha3_256::digest(password.as_bytes()));
+        return Ok(if constant_time_eq(digest.as_bytes(), stored_hash.as_bytes()) {
+            Verification::Valid { needs_rehash: true }
+        } else {
+            Verification::Invalid
+        });
+    }
+
+    let hash = PasswordHash::new(stored_hash)
+        .map_err(|e| anyhow::anyhow!("invalid password hash: {}", e))?;
+
+    let result = match hash.algorithm.as_str() {
+        "argon2id" | "argon2i" | "argon2d" => {
+            Argon2::default().verify_password(password.as_bytes(), &hash)
+        }
+        "pbkdf2" | "pbkdf2-sha256" | "pbkdf2-sha512" => {
+            Pbkdf2.verify_password(password.as_bytes(), &hash)
+        }
+        other => anyhow::bail!("unsupported password hash algorithm: {}", other),
+    };
+
+    match result {
+        Ok(()) => Ok(Verification::Valid {
+            needs_rehash: needs_rehash(&hash),
+        }),
+        Err(argon2::password_hash::Error::Password) => Ok(Verification::Invalid),
+        Err(e) => Err(anyhow::anyhow!("failed to verify password hash: {}", e)),
+    }
+}
+
+///
+/// true si le hash n'est pas en Argon2id avec les paramètres configurés
+///
+fn needs_rehash(hash: &PasswordHash) -> bool {
+    if hash.algorithm.as_str() != "argon2id" {
+        return true;
+    }
+    match Params::try_from(hash) {
+        Ok(params) => {
+            params.m_cost() != ARGON2_PARAMS.m_cost()
+                || params.t_cost() != ARGON2_PARAMS.t_cost()
+                || params.p_cost() != ARGON2_PARAMS.p_cost()
+        }
+        Err(_) => true,
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
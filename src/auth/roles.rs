@@ -0,0 +1,112 @@
+//! src/auth/roles.rs
+//!
+//! Autorisations basées sur la colonne users.role
+//!
+//! L'utilisateur connecté est donné par l'extracteur CurrentUser
+//! (voir auth::current_user).
+//! Les rôles autorisés sont configurables dans le fichier .env :
+//! EDITOR_ROLES : rôles qui peuvent ajouter, modifier, effacer (défaut : "admin,editor")
+//! ADMIN_ROLES : rôles qui peuvent voir la liste des utilisateurs (défaut : "admin")
+//!
+//! L'inscription (/auth/signup) donne toujours le rôle SIGNUP_ROLE ;
+//! seul un administrateur change ensuite le rôle d'un utilisateur (/auth/users).
+//! Le premier administrateur se nomme dans la DB :
+//! UPDATE users SET role = 'admin' WHERE name = '...';
+
+use axum::{
+    body::Body,
+    extract::{FromRequest, RequestParts},
+    http::Request,
+    middleware::Next,
+    response::Response,
+};
+use once_cell::sync::Lazy;
+
+use crate::auth::current_user::CurrentUser;
+use crate::error::AppError;
+
+///
+/// le rôle donné à l'inscription : lecture seule
+///
+pub const SIGNUP_ROLE: &str = "viewer";
+
+///
+/// les rôles proposés par la page des utilisateurs
+///
+pub const USER_ROLES: [&str; 3] = ["admin", "editor", "viewer"];
+
+///
+/// Rôles autorisés, lus une seule fois dans l'environnement
+///
+static ROLES: Lazy<RolesConfig> = Lazy::new(RolesConfig::from_env);
+
+#[derive(Debug, Clone)]
+pub struct RolesConfig {
+    pub editors: Vec<String>,
+    pub admins: Vec<String>,
+}
+
+impl RolesConfig {
+    fn from_env() -> Self {
+        let config = Self {
+            editors: roles_from_env("EDITOR_ROLES", "admin,editor"),
+            admins: roles_from_env("ADMIN_ROLES", "admin"),
+        };
+        tracing::info!("rôles configurés : {:?}", config);
+        config
+    }
+}
+
+///
+/// lit une liste de rôles séparés par des virgules
+///
+fn roles_from_env(var: &str, default: &str) -> Vec<String> {
+    std::env::var(var)
+        .unwrap_or_else(|_| default.to_string())
+        .split(',')
+        .map(|role| role.trim().to_lowercase())
+        .filter(|role| !role.is_empty())
+        .collect()
+}
+
+//***********************************************************************************
+// Middlewares à utiliser avec axum::middleware::from_fn dans router.rs
+//
+
+///
+/// réservé aux rôles de EDITOR_ROLES : routes add, update, delete
+///
+pub async fn require_editor(req: Request<Body>, next: Next<Body>) -> Result<Response, AppError> {
+    require_role(req, next, &ROLES.editors).await
+}
+
+///
+/// réservé aux rôles de ADMIN_ROLES : liste des utilisateurs
+///
+pub async fn require_admin(req: Request<Body>, next: Next<Body>) -> Result<Response, AppError> {
+    require_role(req, next, &ROLES.admins).await
+}
+
+async fn require_role(
+    req: Request<Body>,
+    next: Next<Body>,
+    allowed: &[String],
+) -> Result<Response, AppError> {
+    let mut parts = RequestParts::new(req);
+    let user = CurrentUser::from_request(&mut parts).await?;
+
+    if !user.has_role(allowed) {
+        tracing::info!(
+            "accès refusé à {} (rôle : {}) pour {}",
+            user.0.name,
+            user.0.role,
+            parts.uri()
+        );
+        return Err(AppError::Forbidden);
+    }
+
+    let req = parts
+        .try_into_request()
+        .map_err(|e| AppError::Anyhow(anyhow::anyhow!(e)))?;
+    Ok(next.run(req).await)
+}
@@ -4,10 +4,9 @@ use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex;
 
-use pbkdf2::{
-    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
-    Pbkdf2,
-};
+use chrono::{Duration, Utc};
+use once_cell::sync::Lazy;
+
 use rand_chacha::ChaCha8Rng;
 use rand_core::{OsRng, RngCore};
 
@@ -32,13 +31,61 @@ use axum_sessions::{
 use sqlx::PgPool;
 use uuid::Uuid;
 
+use crate::auth::password::{hash_password, verify_password, Verification};
+use crate::auth::roles::SIGNUP_ROLE;
 use crate::db;
 use tower_cookies::Cookie;
 
 use crate::error::{LoginError, SignupError};
 use crate::flash::signup_response;
+use crate::models::audit::Actor;
 use crate::models::user::{NewUser, NewUserName};
-use crate::utils::auth_utils::{hash_password_argon2, hash_password_pbkdf2, parse};
+use crate::utils::auth_utils::parse;
+
+///
+/// clés utilisées dans la session
+/// SESSION_USER_ID : l'Uuid de l'utilisateur connecté
+/// SESSION_LOGIN_AT : le timestamp de la connexion (durée de vie absolue)
+///
+pub(crate) const SESSION_USER_ID: &str = "id";
+pub(crate) const SESSION_LOGIN_AT: &str = "login-at";
+
+///
+/// durée d'inactivité après laquelle la session expire
+/// SESSION_IDLE_MINUTES dans le fichier .env, 60 minutes par défaut
+///
+static SESSION_IDLE_LIFETIME: Lazy<Duration> =
+    Lazy::new(|| Duration::minutes(duration_from_env("SESSION_IDLE_MINUTES", 60)));
+
+///
+/// durée de vie maximale d'une session, même utilisée
+/// SESSION_MAX_HOURS dans le fichier .env, 12 heures par défaut
+///
+static SESSION_MAX_LIFETIME: Lazy<Duration> =
+    Lazy::new(|| Duration::hours(duration_from_env("SESSION_MAX_HOURS", 12)));
+
+pub(crate) fn session_idle_lifetime() -> Duration {
+    *SESSION_IDLE_LIFETIME
+}
+
+pub(crate) fn session_max_lifetime() -> Duration {
+    *SESSION_MAX_LIFETIME
+}
+
+fn duration_from_env(var: &str, default: i64) -> i64 {
+    std::env::var(var)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+///
+/// ouvre la session de l'utilisateur : id et heure de connexion
+///
+async fn open_user_session(session: &AxumSession<AxumPgPool>, user_id: Uuid) {
+    session.set(SESSION_USER_ID, user_id).await;
+    session.set(SESSION_LOGIN_AT, Utc::now().timestamp()).await;
+}
 
 
 pub(crate) async fn login_session(
@@ -47,10 +94,27 @@ pub(crate) async fn login_session(
     username: String,
     password: String,
 ) -> Result<AxumSession<AxumPgPool>, LoginError> {
+    let user_id = check_credentials(database, username, password).await?;
+
+    session.clear_all().await;
+    open_user_session(&session, user_id).await;
+    Ok(session)
+}
+
+///
+/// Verifies a user name and a password against the stored hash
+/// used by the session login and by the token endpoint
+/// returns the Uuid of the user or LoginError
+///
+pub(crate) async fn check_credentials(
+    database: &PgPool,
+    username: String,
+    password: String,
+) -> Result<Uuid, LoginError> {
     const LOGIN_QUERY: &str = "SELECT id, password_hash FROM users WHERE name = $1;";
 
     let row: Option<(Uuid, String)> = sqlx::query_as(LOGIN_QUERY)
-        .bind(username)
+        .bind(&username)
         .fetch_optional(database)
         .await
         .unwrap();
@@ -61,20 +125,60 @@ pub(crate) async fn login_session(
         return Err(LoginError::UserDoesNotExist);
     };
 
-    // Verify password against PHC string
-    let parsed_hash = PasswordHash::new(&hashed_password).unwrap();
-    if let Err(_err) = Pbkdf2.verify_password(password.as_bytes(), &parsed_hash) {
-        return Err(LoginError::WrongPassword);
+    // le service de mots de passe reconnaît l'algorithme du hash stocké
+    match verify_password(password.clone(), hashed_password).await {
+        Ok(Verification::Valid { needs_rehash }) => {
+            if needs_rehash {
+                rehash_password(database, user_id, username, password).await;
+            }
+        }
+        Ok(Verification::Invalid) => return Err(LoginError::WrongPassword),
+        Err(err) => {
+            tracing::error!("vérification du mot de passe impossible : {:?}", err);
+            return Err(LoginError::WrongPassword);
+        }
     }
-    //session.clear_all().await;
-    session.set("id", user_id).await;
-    Ok(session)
+    Ok(user_id)
+}
+
+///
+/// Upgrades the stored hash to Argon2id after a successful login
+/// a failure is logged but does not prevent the login
+///
+async fn rehash_password(database: &PgPool, user_id: Uuid, username: String, password: String) {
+    // l'utilisateur qui vient de se connecter est l'auteur de la modification
+    let actor = Actor {
+        id: Some(user_id),
+        name: username,
+    };
+    let result = match hash_password(password).await {
+        Ok(new_hash) => db::users::update_password_hash(user_id, new_hash, &actor, database)
+            .await
+            .map_err(anyhow::Error::from),
+        Err(err) => Err(err),
+    };
+    match result {
+        Ok(()) => tracing::info!("hash du mot de passe mis à jour pour {}", user_id),
+        Err(err) => tracing::error!("mise à jour du hash impossible pour {} : {:?}", user_id, err),
+    }
+}
+
+///
+/// Logs the user out
+/// the session is destroyed in the AxumPgSessionStore table, not only in the cookie
+///
+pub(crate) async fn logout_session(session: AxumSession<AxumPgPool>) {
+    if let Some(user_id) = session.get::<Uuid>(SESSION_USER_ID).await {
+        tracing::info!("déconnexion de l'utilisateur {}", user_id);
+    }
+    session.destroy().await;
 }
 
 ///
 /// Function to treat data from a new user
 /// generates a hashed password
-/// adds the user to the DB
+/// adds the user to the DB with the role SIGNUP_ROLE
+/// (an admin grants the other roles)
 /// opens a session
 /// returns the Uuid of the new user or SignupError
 ///
@@ -83,7 +187,6 @@ pub(crate) async fn signup_session(
     session: AxumSession<AxumPgPool>,
     username: &str,
     password: &str,
-    role: &str,
 ) -> Result<Uuid, SignupError> {
     let string_username: &String = &username.to_string();
     tracing::info!("username = {}", string_username);
@@ -99,12 +202,14 @@ pub(crate) async fn signup_session(
         } else {
             let name = NewUserName::parse(username.to_string()).unwrap();
             // fonction de hash ici
-            let password = hash_password_pbkdf2(password.to_string()).await.unwrap();
+            let password = hash_password(password.to_string())
+                .await
+                .map_err(|_| SignupError::InvalidPassword)?;
             // *********************
             let new_user = NewUser {
                 name,
                 password,
-                role: role.to_string(),
+                role: SIGNUP_ROLE.to_string(),
             };
             let added_user = db::users::add_user(&new_user, &pool).await.unwrap();
 
@@ -112,8 +217,8 @@ pub(crate) async fn signup_session(
 
             let user_id = added_user.id;
             session.clear_all().await;
-            session.set("user-id", user_id).await;
-            return Ok(session.get("user-id").await.unwrap());
+            open_user_session(&session, user_id).await;
+            return Ok(user_id);
         }
     } else {
         tracing::info!("Erreur de validation du nom d'utilisateur");
@@ -134,6 +239,7 @@ pub(crate) async fn new_ascd_creation_sqlx_session(
     // axum_sqlx_session
     let session_config = AxumSessionConfig::default()
         //.with_table_name("persons.sessions")
+        .with_lifetime(session_idle_lifetime())
         .with_key(key.clone());
     tracing::debug!("AxumSessionConfig : {:?}", session_config);
 
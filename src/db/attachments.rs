@@ -0,0 +1,106 @@
+//! src/db/attachments.rs
+//!
+//! Fichiers attachés aux partitions
+//! seules les métadonnées sont dans la DB, le contenu est dans le Storage
+
+use sqlx::PgPool;
+
+use crate::models::attachment::Attachment;
+
+//*******************************************************************************************
+// CRUD Operations on attachments
+//
+
+///
+/// Adds an attachment to a partition
+/// the same file (same hash) uploaded again for the same partition
+/// only updates its name and returns the existing attachment
+///
+pub async fn add_attachment(
+    partition_id: i32,
+    file_name: &str,
+    content_type: &str,
+    size_bytes: i64,
+    sha3_256: &str,
+    pool: &PgPool,
+) -> sqlx::Result<Attachment> {
+    let attachment = sqlx::query_as::<_, Attachment>(
+        "INSERT INTO attachments (partition_id, file_name, content_type, size_bytes, sha3_256)
+                VALUES ( $1, $2, $3, $4, $5 )
+                ON CONFLICT (partition_id, sha3_256)
+                DO UPDATE SET file_name = EXCLUDED.file_name
+                RETURNING id, partition_id, file_name, content_type, size_bytes, sha3_256, uploaded_at;",
+    )
+    .bind(partition_id)
+    .bind(file_name)
+    .bind(content_type)
+    .bind(size_bytes)
+    .bind(sha3_256)
+    .fetch_one(pool)
+    .await?;
+
+    tracing::info!("db : attachment added : {:?}", &attachment);
+    Ok(attachment)
+}
+
+///
+/// Deletes an attachment
+/// returns the deleted attachment and the number of attachments
+/// still using the same content (the content can be removed from the Storage when 0)
+///
+pub async fn delete_attachment(id: i32, pool: &PgPool) -> sqlx::Result<(Attachment, i64)> {
+    let mut tx = pool.begin().await?;
+    let attachment = sqlx::query_as::<_, Attachment>(
+        "DELETE FROM attachments WHERE id = $1
+                RETURNING id, partition_id, file_name, content_type, size_bytes, sha3_256, uploaded_at;",
+    )
+    .bind(id)
+    .fetch_one(&mut tx)
+    .await?;
+    let remaining: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM attachments WHERE sha3_256 = $1;")
+            .bind(&attachment.sha3_256)
+            .fetch_one(&mut tx)
+            .await?;
+    tx.commit().await?;
+
+    tracing::info!("db : attachment deleted : {}", &attachment.file_name);
+    Ok((attachment, remaining))
+}
+
+//**********************************************************************************
+// Recherche des fichiers
+//
+
+pub async fn find_attachment_by_id(id: i32, pool: &PgPool) -> sqlx::Result<Attachment> {
+    sqlx::query_as::<_, Attachment>("SELECT * FROM attachments WHERE id = $1;")
+        .bind(id)
+        .fetch_one(pool)
+        .await
+}
+
+///
+/// Returns the attachments of a partition, in upload order
+///
+pub async fn list_attachments(partition_id: i32, pool: &PgPool) -> sqlx::Result<Vec<Attachment>> {
+    sqlx::query_as::<_, Attachment>(
+        "SELECT * FROM attachments WHERE partition_id = $1 ORDER BY uploaded_at, id;",
+    )
+    .bind(partition_id)
+    .fetch_all(pool)
+    .await
+}
+
+///
+/// Returns the hashes among `hashes` that no attachment uses any more
+/// (after the deletion of a partition, whose attachments are deleted in cascade)
+///
+pub async fn unused_hashes(hashes: &[String], pool: &PgPool) -> sqlx::Result<Vec<String>> {
+    sqlx::query_scalar(
+        "SELECT hash FROM UNNEST($1::TEXT[]) AS hash
+         WHERE NOT EXISTS (SELECT 1 FROM attachments WHERE sha3_256 = hash);",
+    )
+    .bind(hashes)
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,255 @@
+//! src/db/audit.rs
+//!
+//! Journal des modifications (audit_log)
+//! les lignes sont écrites par les fonctions de musicians, genres, partitions,
+//! contributors, tags et users
+//! dans la transaction de la modification : pas de modification sans sa ligne au journal
+
+use serde_json::Value;
+use sqlx::postgres::PgRow;
+use sqlx::{Encode, PgPool, Postgres, QueryBuilder, Row, Transaction, Type};
+
+use crate::db::search::escape_like;
+use crate::models::audit::{
+    field_changes, Actor, AuditAction, AuditEntity, AuditEntry, AuditFilter,
+};
+use crate::models::pagination::{ListParams, Page};
+
+const ENTRY_COLUMNS: &str =
+    "id, at, actor_id, actor_name, action, entity_type, entity_id, before, after";
+
+//*******************************************************************************************
+// Ecriture du journal
+//
+
+///
+/// Returns the row of an entity as JSON, or None when it does not exist,
+/// read inside the transaction of the change (before and after it) ;
+/// the row stays locked until the end of the transaction
+/// a partition comes with the ids of all its genres, its musicians with their roles
+/// and the ids of its tags ; a user without its password hash
+///
+pub(crate) async fn snapshot<T>(
+    tx: &mut Transaction<'_, Postgres>,
+    entity: AuditEntity,
+    id: T,
+) -> sqlx::Result<Option<Value>>
+where
+    T: 'static + Send + for<'q> Encode<'q, Postgres> + Type<Postgres>,
+{
+    let query = match entity {
+        AuditEntity::Person => "SELECT to_jsonb(persons) FROM persons WHERE id = $1 FOR UPDATE;",
+        AuditEntity::Genre => "SELECT to_jsonb(genres) FROM genres WHERE id = $1 FOR UPDATE;",
+        AuditEntity::Partition => {
+            "SELECT to_jsonb(partitions) || jsonb_build_object(
+                    'genre_ids', ARRAY(
+                        SELECT genre_id FROM partition_genres
+                        WHERE partition_id = partitions.id ORDER BY genre_id),
+                    'contributors', ARRAY(
+                        SELECT person_id || ' ' || role FROM partition_contributors
+                        WHERE partition_id = partitions.id ORDER BY person_id, role),
+                    'tag_ids', ARRAY(
+                        SELECT tag_id FROM partition_tags
+                        WHERE partition_id = partitions.id ORDER BY tag_id))
+                FROM partitions WHERE id = $1 FOR UPDATE;"
+        }
+        AuditEntity::Tag => "SELECT to_jsonb(tags) FROM tags WHERE id = $1 FOR UPDATE;",
+        AuditEntity::User => {
+            "SELECT to_jsonb(users) - 'password_hash' FROM users WHERE id = $1 FOR UPDATE;"
+        }
+    };
+    sqlx::query_scalar(query)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await
+}
+
+///
+/// Adds a line to the journal inside the transaction of the change
+///
+pub(crate) async fn record(
+    tx: &mut Transaction<'_, Postgres>,
+    actor: &Actor,
+    action: AuditAction,
+    entity: AuditEntity,
+    entity_id: impl ToString,
+    before: Option<Value>,
+    after: Option<Value>,
+) -> sqlx::Result<()> {
+    sqlx::query(
+        "INSERT INTO audit_log (actor_id, actor_name, action, entity_type, entity_id, before, after)
+                VALUES ( $1, $2, $3, $4, $5, $6, $7 );",
+    )
+    .bind(actor.id)
+    .bind(&actor.name)
+    .bind(action.as_str())
+    .bind(entity.as_str())
+    .bind(entity_id.to_string())
+    .bind(before)
+    .bind(after)
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
+///
+/// ajoute au journal la modification d'une partition (étiquettes, musiciens, genres)
+/// before : la copie de la partition prise avant la modification
+///
+pub(crate) async fn record_partition_change(
+    tx: &mut Transaction<'_, Postgres>,
+    actor: &Actor,
+    partition_id: i32,
+    before: Option<Value>,
+) -> sqlx::Result<()> {
+    let after = snapshot(&mut *tx, AuditEntity::Partition, partition_id).await?;
+    record(
+        &mut *tx,
+        actor,
+        AuditAction::Update,
+        AuditEntity::Partition,
+        partition_id,
+        before,
+        after,
+    )
+    .await
+}
+
+//*******************************************************************************************
+// Lecture du journal
+//
+
+///
+/// Returns one page of the journal matching all the criteria of the filter
+/// sorted on "date" (the only sort column)
+///
+pub async fn list_audit_page(
+    filter: &AuditFilter,
+    params: &ListParams,
+    pool: &PgPool,
+) -> sqlx::Result<Page<AuditEntry>> {
+    let mut query = QueryBuilder::new(format!("SELECT {} FROM audit_log", ENTRY_COLUMNS));
+    push_filter(&mut query, filter);
+    query
+        .push(format!(
+            " ORDER BY at {}, id {}",
+            params.dir.as_sql(),
+            params.dir.as_sql()
+        ))
+        .push(" LIMIT ")
+        .push_bind(params.limit())
+        .push(" OFFSET ")
+        .push_bind(params.offset());
+    let entries = query
+        .build()
+        .try_map(entry_from_row)
+        .fetch_all(pool)
+        .await?;
+
+    let mut count = QueryBuilder::new("SELECT COUNT(*) FROM audit_log");
+    push_filter(&mut count, filter);
+    let total: i64 = count.build().fetch_one(pool).await?.get(0);
+
+    Ok(Page::new(entries, total, params, "date"))
+}
+
+///
+/// Returns the whole history of one musician, genre, partition or user,
+/// the most recent change first
+///
+pub async fn entity_history(
+    entity: AuditEntity,
+    entity_id: &str,
+    pool: &PgPool,
+) -> sqlx::Result<Vec<AuditEntry>> {
+    let query = format!(
+        "SELECT {} FROM audit_log
+        WHERE entity_type = $1 AND entity_id = $2
+        ORDER BY at DESC, id DESC",
+        ENTRY_COLUMNS
+    );
+    sqlx::query(&query)
+        .bind(entity.as_str())
+        .bind(entity_id)
+        .try_map(entry_from_row)
+        .fetch_all(pool)
+        .await
+}
+
+///
+/// ajoute les critères du filtre à la requête, combinés par AND
+/// l'auteur : contient, sans tenir compte des majuscules ; les dates : jours compris
+///
+fn push_filter(query: &mut QueryBuilder<Postgres>, filter: &AuditFilter) {
+    let mut first = true;
+    if let Some(actor) = &filter.actor {
+        next_condition(query, &mut first);
+        query
+            .push("actor_name ILIKE ")
+            .push_bind(format!("%{}%", escape_like(actor)));
+    }
+    if let Some(action) = filter.action {
+        next_condition(query, &mut first);
+        query.push("action = ").push_bind(action.as_str());
+    }
+    if let Some(entity) = filter.entity_type {
+        next_condition(query, &mut first);
+        query.push("entity_type = ").push_bind(entity.as_str());
+    }
+    if let Some(entity_id) = &filter.entity_id {
+        next_condition(query, &mut first);
+        query.push("entity_id = ").push_bind(entity_id.clone());
+    }
+    if let Some(from) = filter.from {
+        next_condition(query, &mut first);
+        query.push("at >= ").push_bind(from);
+    }
+    if let Some(to) = filter.to {
+        next_condition(query, &mut first);
+        query
+            .push("at < ")
+            .push_bind(to)
+            .push(" + INTERVAL '1 day'");
+    }
+}
+
+fn next_condition(query: &mut QueryBuilder<Postgres>, first: &mut bool) {
+    query.push(if *first { " WHERE " } else { " AND " });
+    *first = false;
+}
+
+//******************************************************************************************
+// Construction des structs à partir des lignes de la DB
+//
+
+fn entry_from_row(row: PgRow) -> sqlx::Result<AuditEntry> {
+    let action: AuditAction = parse_column(&row, "action")?;
+    let entity_type: AuditEntity = parse_column(&row, "entity_type")?;
+    let before: Option<Value> = row.try_get("before")?;
+    let after: Option<Value> = row.try_get("after")?;
+    Ok(AuditEntry {
+        id: row.try_get("id")?,
+        at: row.try_get("at")?,
+        actor_id: row.try_get("actor_id")?,
+        actor_name: row.try_get("actor_name")?,
+        action,
+        action_label: action.label().to_string(),
+        entity_type,
+        entity_label: entity_type.label().to_string(),
+        entity_id: row.try_get("entity_id")?,
+        changes: field_changes(before.as_ref(), after.as_ref()),
+        before,
+        after,
+    })
+}
+
+fn parse_column<T>(row: &PgRow, column: &str) -> sqlx::Result<T>
+where
+    T: std::str::FromStr<Err = String>,
+{
+    let text: String = row.try_get(column)?;
+    text.parse().map_err(|e: String| sqlx::Error::ColumnDecode {
+        index: column.to_string(),
+        source: e.into(),
+    })
+}
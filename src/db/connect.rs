@@ -9,3 +9,13 @@ pub async fn create_pg_pool(db_url: &str) -> sqlx::Result<PgPool> {
     let pool = PgPool::connect(db_url).await?;
     Ok(pool)
 }
+
+///
+/// Applies the versioned migrations of the "migrations" directory
+/// (embedded in the binary at compile time)
+///
+pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::migrate::MigrateError> {
+    sqlx::migrate!("./migrations").run(pool).await?;
+    tracing::info!("migrations de la base de données appliquées");
+    Ok(())
+}
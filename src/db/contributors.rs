@@ -0,0 +1,158 @@
+//! src/db/contributors.rs
+//!
+//! Les musiciens d'une partition et leur rôle (table partition_contributors)
+//! le musicien principal (partitions.person_id) y est toujours compositeur
+
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+
+use crate::db::audit::{record, snapshot};
+use crate::models::audit::{Actor, AuditAction, AuditEntity};
+use crate::models::contributor::{Contributor, ContributorRole};
+
+//*******************************************************************************************
+// CRUD Operations on contributors
+//
+
+///
+/// Adds a musician with a role to a partition
+/// returns false when the musician already has this role
+/// the change is recorded in the journal of the partition
+///
+pub async fn add_contributor(
+    partition_id: i32,
+    person_id: i32,
+    role: ContributorRole,
+    actor: &Actor,
+    pool: &PgPool,
+) -> sqlx::Result<bool> {
+    let mut tx = pool.begin().await?;
+    let before = snapshot(&mut tx, AuditEntity::Partition, partition_id).await?;
+    let result = sqlx::query(
+        "INSERT INTO partition_contributors (partition_id, person_id, role)
+                VALUES ( $1, $2, $3 )
+                ON CONFLICT DO NOTHING;",
+    )
+    .bind(partition_id)
+    .bind(person_id)
+    .bind(role.as_str())
+    .execute(&mut tx)
+    .await?;
+    let added = result.rows_affected() > 0;
+    if added {
+        let after = snapshot(&mut tx, AuditEntity::Partition, partition_id).await?;
+        record(
+            &mut tx,
+            actor,
+            AuditAction::Update,
+            AuditEntity::Partition,
+            partition_id,
+            before,
+            after,
+        )
+        .await?;
+    }
+    tx.commit().await?;
+
+    tracing::info!(
+        "db : contributor added : partition {} person {} {}",
+        partition_id,
+        person_id,
+        role
+    );
+    Ok(added)
+}
+
+///
+/// Removes a role of a musician from a partition
+/// returns false when there was nothing to remove
+/// the change is recorded in the journal of the partition
+///
+pub async fn delete_contributor(
+    partition_id: i32,
+    person_id: i32,
+    role: ContributorRole,
+    actor: &Actor,
+    pool: &PgPool,
+) -> sqlx::Result<bool> {
+    let mut tx = pool.begin().await?;
+    let before = snapshot(&mut tx, AuditEntity::Partition, partition_id).await?;
+    let result = sqlx::query(
+        "DELETE FROM partition_contributors
+                WHERE partition_id = $1 AND person_id = $2 AND role = $3;",
+    )
+    .bind(partition_id)
+    .bind(person_id)
+    .bind(role.as_str())
+    .execute(&mut tx)
+    .await?;
+    let deleted = result.rows_affected() > 0;
+    if deleted {
+        let after = snapshot(&mut tx, AuditEntity::Partition, partition_id).await?;
+        record(
+            &mut tx,
+            actor,
+            AuditAction::Update,
+            AuditEntity::Partition,
+            partition_id,
+            before,
+            after,
+        )
+        .await?;
+    }
+    tx.commit().await?;
+
+    tracing::info!(
+        "db : contributor deleted : partition {} person {} {}",
+        partition_id,
+        person_id,
+        role
+    );
+    Ok(deleted)
+}
+
+//**********************************************************************************
+// Recherche des musiciens des partitions
+//
+
+///
+/// Returns the musicians of several partitions at once
+/// ordered by partition, role (composer, arranger, lyricist) and name
+///
+pub async fn list_contributors(
+    partition_ids: &[i32],
+    pool: &PgPool,
+) -> sqlx::Result<Vec<Contributor>> {
+    sqlx::query(
+        "
+    SELECT partition_contributors.partition_id, partition_contributors.person_id,
+        persons.full_name, partition_contributors.role
+    FROM partition_contributors
+    INNER JOIN persons
+    ON partition_contributors.person_id = persons.id
+    WHERE partition_contributors.partition_id = ANY($1)
+    ORDER BY partition_contributors.partition_id,
+        array_position(ARRAY['composer', 'arranger', 'lyricist'], partition_contributors.role),
+        persons.full_name
+        ",
+    )
+    .bind(partition_ids)
+    .try_map(|row: PgRow| {
+        let role: String = row.try_get("role")?;
+        let role: ContributorRole =
+            role.parse()
+                .map_err(|e: String| sqlx::Error::ColumnDecode {
+                    index: "role".to_string(),
+                    source: e.into(),
+                })?;
+        Ok(Contributor {
+            partition_id: row.try_get("partition_id")?,
+            person_id: row.try_get("person_id")?,
+            full_name: row.try_get("full_name")?,
+            role,
+            role_label: role.label().to_string(),
+        })
+    })
+    .fetch_all(pool)
+    .await
+}
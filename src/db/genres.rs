@@ -1,61 +1,425 @@
 //! src/db/genres
 
 use sqlx::postgres::PgRow;
-use sqlx::{PgPool, Row};
+use sqlx::{PgPool, Postgres, Row, Transaction};
 
-use crate::models::genre::Genre;
+use crate::db::audit::{record, record_partition_change, snapshot};
+use crate::models::audit::{Actor, AuditAction, AuditEntity};
+use crate::models::deletion::{Deletion, Dependent};
+use crate::models::genre::{Genre, GenreName};
+use crate::models::merge::{Candidate, MergeResult, SimilarPair};
+use crate::models::pagination::{ListParams, Page};
+use crate::models::version::Update;
 
 //*******************************************************************************************
 // CRUD Operations on genres
 //
 
-pub async fn add_genre(pool: &PgPool, genre: Genre) -> sqlx::Result<Genre> {
+pub async fn add_genre(pool: &PgPool, name: GenreName, actor: &Actor) -> sqlx::Result<Genre> {
     let mut tx = pool.begin().await?;
     let rec = sqlx::query(
         "INSERT INTO genres (name)
                 VALUES ( $1 )
-                RETURNING id, name;",
+                RETURNING id, name, version;",
     )
-    .bind(&genre.name)
+    .bind(name.as_ref())
     .map(|row: PgRow| Genre {
         id: row.get(0),
         name: row.get(1),
+        version: row.get("version"),
     })
     .fetch_one(&mut tx)
     .await?;
+    let id = rec.id.unwrap_or_default();
+    let after = snapshot(&mut tx, AuditEntity::Genre, id).await?;
+    record(
+        &mut tx,
+        actor,
+        AuditAction::Create,
+        AuditEntity::Genre,
+        id,
+        None,
+        after,
+    )
+    .await?;
     tx.commit().await?;
 
     tracing::info!("db : genre added : {:?}", &rec);
     Ok(rec)
 }
 
-pub async fn update_genre(id: i32, genre_name: String, pool: &PgPool) -> sqlx::Result<Genre> {
-    let genre = sqlx::query("UPDATE genres SET name = $1 WHERE id = $2 RETURNING id, name;")
-        .bind(&genre_name)
-        .bind(id)
-        .map(|row: PgRow| Genre {
-            id: row.get(0),
-            name: row.get(1),
-        })
-        .fetch_one(pool)
-        .await?;
+///
+/// Renames a genre
+/// the row is only updated if its version is still the one sent back
+/// (by the edit form or the JSON) : Update::Conflict with the current row otherwise
+/// RowNotFound when the genre does not exist or is in the trash
+///
+pub async fn update_genre(
+    id: i32,
+    genre_name: GenreName,
+    version: i32,
+    actor: &Actor,
+    pool: &PgPool,
+) -> sqlx::Result<Update<Genre>> {
+    let mut tx = pool.begin().await?;
+    let before = snapshot(&mut tx, AuditEntity::Genre, id).await?;
+    let genre = sqlx::query(
+        "UPDATE genres SET name = $1, version = version + 1
+                WHERE id = $2 AND deleted_at IS NULL
+                AND version = $3
+                RETURNING id, name, version;",
+    )
+    .bind(genre_name.as_ref())
+    .bind(id)
+    .bind(version)
+    .map(|row: PgRow| Genre {
+        id: row.get(0),
+        name: row.get(1),
+        version: row.get("version"),
+    })
+    .fetch_optional(&mut tx)
+    .await?;
+    let genre = match genre {
+        Some(genre) => genre,
+        None => {
+            tx.rollback().await?;
+            let current = find_genre_by_id(id, pool).await?;
+            tracing::info!(
+                "db : Genre not updated (version {:?}) : {:?}",
+                version,
+                &current
+            );
+            return Ok(Update::Conflict { current });
+        }
+    };
+    let after = snapshot(&mut tx, AuditEntity::Genre, id).await?;
+    record(
+        &mut tx,
+        actor,
+        AuditAction::Update,
+        AuditEntity::Genre,
+        id,
+        before,
+        after,
+    )
+    .await?;
+    tx.commit().await?;
 
     tracing::info!("db : Genre updated : {:?}", &genre);
-    Ok(genre)
+    Ok(Update::Saved(genre))
 }
 
-pub async fn delete_genre(id: i32, pool: &PgPool) -> sqlx::Result<String> {
-    let genre = find_genre_by_id(id.clone(), pool).await?;
-    let name = genre.name;
+///
+/// Moves a genre to the trash in one transaction (db::trash restores or purges it)
+/// the partitions which depend on it (main genre or other genre)
+/// are first given to the genre reassign_to ;
+/// without reassign_to, the deletion is refused while partitions remain
+/// (trashed partitions keep their genre : it comes back with them)
+///
+pub async fn delete_genre(
+    id: i32,
+    reassign_to: Option<i32>,
+    actor: &Actor,
+    pool: &PgPool,
+) -> sqlx::Result<Deletion> {
+    let mut tx = pool.begin().await?;
+
+    // le verrou empêche l'ajout d'une partition pendant l'effacement
+    let name: String = sqlx::query_scalar(
+        "SELECT name FROM genres WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+    )
+    .bind(id)
+    .fetch_one(&mut tx)
+    .await?;
+    let dependents: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM partitions
+                WHERE deleted_at IS NULL
+                AND (genre_id = $1
+                    OR EXISTS (SELECT 1 FROM partition_genres
+                        WHERE partition_id = partitions.id AND genre_id = $1));",
+    )
+    .bind(id)
+    .fetch_one(&mut tx)
+    .await?;
+
+    if dependents > 0 {
+        let target = match reassign_to {
+            Some(target) if target != id => target,
+            _ => {
+                tx.rollback().await?;
+                tracing::info!(
+                    "db : Genre not deleted : {} ({} partitions)",
+                    &name,
+                    dependents
+                );
+                return Ok(Deletion::Refused { name, dependents });
+            }
+        };
+        // RowNotFound si le genre de remplacement n'existe pas (ou est à la corbeille)
+        sqlx::query("SELECT id FROM genres WHERE id = $1 AND deleted_at IS NULL FOR UPDATE")
+            .bind(target)
+            .fetch_one(&mut tx)
+            .await?;
+        repoint_partitions(&mut tx, &[id], target, false, actor).await?;
+    }
 
-    let _res = sqlx::query("DELETE FROM genres WHERE id = $1")
+    let before = snapshot(&mut tx, AuditEntity::Genre, id).await?;
+    sqlx::query("UPDATE genres SET deleted_at = now() WHERE id = $1")
         .bind(id)
-        .execute(pool)
+        .execute(&mut tx)
         .await?;
+    let mut after = snapshot(&mut tx, AuditEntity::Genre, id).await?;
+    if let (Some(target), Some(serde_json::Value::Object(fields))) =
+        (reassign_to.filter(|_| dependents > 0), after.as_mut())
+    {
+        fields.insert("reassigned_to".to_string(), target.into());
+        fields.insert("partitions".to_string(), dependents.into());
+    }
+    record(
+        &mut tx,
+        actor,
+        AuditAction::Delete,
+        AuditEntity::Genre,
+        id,
+        before,
+        after,
+    )
+    .await?;
+    tx.commit().await?;
+
+    tracing::info!(
+        "db : Genre trashed : {} ({} partitions reassigned)",
+        &name,
+        dependents
+    );
+
+    Ok(Deletion::Deleted {
+        name,
+        reassigned: dependents,
+    })
+}
+
+///
+/// Returns the partitions (not trashed) which depend on a genre
+///
+pub async fn list_genre_dependents(id: i32, pool: &PgPool) -> sqlx::Result<Vec<Dependent>> {
+    let dependents = sqlx::query(
+        "SELECT partitions.id, partitions.title, partitions.genre_id = $1 AS main
+                FROM partitions
+                WHERE partitions.deleted_at IS NULL
+                AND (partitions.genre_id = $1
+                    OR EXISTS (SELECT 1 FROM partition_genres
+                        WHERE partition_id = partitions.id AND genre_id = $1))
+                ORDER BY partitions.title;",
+    )
+    .bind(id)
+    .map(|row: PgRow| Dependent {
+        partition_id: row.get("id"),
+        title: row.get("title"),
+        main: row.get("main"),
+        roles: Vec::new(),
+    })
+    .fetch_all(pool)
+    .await?;
+
+    Ok(dependents)
+}
+
+///
+/// Merges duplicate genres into the survivor in one transaction :
+/// their partitions are given to the survivor, then they are deleted
+/// the survivor itself and the ids in the trash are left out of the duplicates
+///
+pub async fn merge_genres(
+    survivor: i32,
+    duplicates: &[i32],
+    actor: &Actor,
+    pool: &PgPool,
+) -> sqlx::Result<MergeResult> {
+    let mut tx = pool.begin().await?;
+
+    let survivor_name: String = sqlx::query_scalar(
+        "SELECT name FROM genres WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+    )
+    .bind(survivor)
+    .fetch_one(&mut tx)
+    .await?;
+    // seuls les doublons actifs, autres que le survivant, sont repris et effacés
+    let rows: Vec<(i32, String)> = sqlx::query_as(
+        "SELECT id, name FROM genres WHERE id = ANY($1) AND id <> $2 AND deleted_at IS NULL
+                ORDER BY name FOR UPDATE;",
+    )
+    .bind(duplicates)
+    .bind(survivor)
+    .fetch_all(&mut tx)
+    .await?;
+    if rows.is_empty() {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    let (ids, merged): (Vec<i32>, Vec<String>) = rows.into_iter().unzip();
+    let partitions: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM partitions
+                WHERE deleted_at IS NULL
+                AND (genre_id = ANY($1)
+                    OR EXISTS (SELECT 1 FROM partition_genres
+                        WHERE partition_id = partitions.id AND genre_id = ANY($1)));",
+    )
+    .bind(&ids)
+    .fetch_one(&mut tx)
+    .await?;
+
+    // les partitions à la corbeille passent aussi au survivant : les doublons sont effacés
+    repoint_partitions(&mut tx, &ids, survivor, true, actor).await?;
+    for &id in &ids {
+        let before = snapshot(&mut tx, AuditEntity::Genre, id).await?;
+        sqlx::query("DELETE FROM genres WHERE id = $1")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        let after = serde_json::json!({ "reassigned_to": survivor });
+        record(
+            &mut tx,
+            actor,
+            AuditAction::Delete,
+            AuditEntity::Genre,
+            id,
+            before,
+            Some(after),
+        )
+        .await?;
+    }
+    tx.commit().await?;
+
+    tracing::info!("db : Genres {:?} merged into {}", &merged, &survivor_name);
+
+    Ok(MergeResult {
+        survivor: survivor_name,
+        merged,
+        partitions,
+    })
+}
 
-    tracing::info!("db : Genre deleted : {}", &name);
+///
+/// gives the partitions of the genres "from" to the genre "to" :
+/// main genre and other genres
+/// the partitions in the trash are left to "from" unless with_trashed
+/// each partition changed is recorded in the journal
+///
+async fn repoint_partitions(
+    tx: &mut Transaction<'_, Postgres>,
+    from: &[i32],
+    to: i32,
+    with_trashed: bool,
+    actor: &Actor,
+) -> sqlx::Result<()> {
+    // les partitions concernées, copiées pour le journal avant la modification
+    let partition_ids: Vec<i32> = sqlx::query_scalar(
+        "SELECT id FROM partitions
+                WHERE ($2 OR deleted_at IS NULL)
+                AND (genre_id = ANY($1)
+                    OR EXISTS (SELECT 1 FROM partition_genres
+                        WHERE partition_id = partitions.id AND genre_id = ANY($1)))
+                ORDER BY id;",
+    )
+    .bind(from)
+    .bind(with_trashed)
+    .fetch_all(&mut *tx)
+    .await?;
+    let mut partitions = Vec::with_capacity(partition_ids.len());
+    for partition_id in partition_ids {
+        let before = snapshot(&mut *tx, AuditEntity::Partition, partition_id).await?;
+        partitions.push((partition_id, before));
+    }
 
-    Ok(name)
+    sqlx::query(
+        "UPDATE partitions SET genre_id = $2, version = version + 1
+                WHERE genre_id = ANY($1) AND ($3 OR deleted_at IS NULL);",
+    )
+    .bind(from)
+    .bind(to)
+    .bind(with_trashed)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "INSERT INTO partition_genres (partition_id, genre_id)
+                SELECT partition_id, $2 FROM partition_genres
+                WHERE genre_id = ANY($1)
+                AND ($3 OR partition_id IN (SELECT id FROM partitions WHERE deleted_at IS NULL))
+                ON CONFLICT DO NOTHING;",
+    )
+    .bind(from)
+    .bind(to)
+    .bind(with_trashed)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "DELETE FROM partition_genres
+                WHERE genre_id = ANY($1)
+                AND ($2 OR partition_id IN (SELECT id FROM partitions WHERE deleted_at IS NULL));",
+    )
+    .bind(from)
+    .bind(with_trashed)
+    .execute(&mut *tx)
+    .await?;
+    for (partition_id, before) in partitions {
+        record_partition_change(&mut *tx, actor, partition_id, before).await?;
+    }
+    Ok(())
+}
+
+///
+/// Returns the pairs of genres whose names look alike
+/// ("Jazz" and "jazz vocal") above the threshold
+///
+pub async fn find_similar_genres(threshold: f32, pool: &PgPool) -> sqlx::Result<Vec<SimilarPair>> {
+    let mut tx = pool.begin().await?;
+    // seuils des opérateurs % et <% : ils utilisent l'index trigrammes de genres
+    sqlx::query(
+        "SELECT set_config('pg_trgm.similarity_threshold', $1, true),
+                set_config('pg_trgm.word_similarity_threshold', $1, true);",
+    )
+    .bind(threshold.to_string())
+    .execute(&mut tx)
+    .await?;
+
+    let pairs = sqlx::query(
+        "SELECT a.id AS a_id, a.name AS a_name, b.id AS b_id, b.name AS b_name,
+                    (SELECT COUNT(*) FROM partitions
+                        WHERE genre_id = a.id AND deleted_at IS NULL) AS a_count,
+                    (SELECT COUNT(*) FROM partitions
+                        WHERE genre_id = b.id AND deleted_at IS NULL) AS b_count,
+                    greatest(similarity(an, bn), word_similarity(an, bn), word_similarity(bn, an)) AS score
+                FROM genres a
+                CROSS JOIN LATERAL (SELECT f_unaccent(lower(a.name)) AS an) AS na
+                INNER JOIN genres b
+                ON b.id > a.id
+                    AND b.deleted_at IS NULL
+                    AND (f_unaccent(lower(b.name)) % an
+                        OR an <% f_unaccent(lower(b.name))
+                        OR f_unaccent(lower(b.name)) <% an)
+                CROSS JOIN LATERAL (SELECT f_unaccent(lower(b.name)) AS bn) AS nb
+                WHERE a.deleted_at IS NULL
+                ORDER BY score DESC, a.name
+                LIMIT 500;",
+    )
+    .map(|row: PgRow| SimilarPair {
+        first: Candidate {
+            id: row.get("a_id"),
+            name: row.get("a_name"),
+            partitions: row.get("a_count"),
+        },
+        second: Candidate {
+            id: row.get("b_id"),
+            name: row.get("b_name"),
+            partitions: row.get("b_count"),
+        },
+        score: row.get("score"),
+    })
+    .fetch_all(&mut tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(pairs)
 }
 
 ///
@@ -64,22 +428,59 @@ pub async fn delete_genre(id: i32, pool: &PgPool) -> sqlx::Result<String> {
 /// or a sqlx Error
 ///
 pub async fn list_genres(pool: &PgPool) -> anyhow::Result<Vec<Genre>> {
-    let genres: Vec<Genre> = sqlx::query("SELECT id, name FROM genres ORDER BY name;")
+    let genres: Vec<Genre> =
+        sqlx::query("SELECT id, name, version FROM genres WHERE deleted_at IS NULL ORDER BY name;")
+            .map(|row: PgRow| Genre {
+                id: row.get(0),
+                name: row.get(1),
+                version: row.get("version"),
+            })
+            .fetch_all(pool)
+            .await?;
+    Ok(genres)
+}
+
+///
+/// Returns one page of genres
+/// sorted on "name" (default) or "id"
+/// with the total number of genres
+///
+pub async fn list_genres_page(pool: &PgPool, params: &ListParams) -> sqlx::Result<Page<Genre>> {
+    let (sort, column) = match params.sort.as_deref() {
+        Some("id") => ("id", "id"),
+        _ => ("name", "name"),
+    };
+    let query = format!(
+        "SELECT id, name, version FROM genres WHERE deleted_at IS NULL
+        ORDER BY {} {}, id LIMIT $1 OFFSET $2;",
+        column,
+        params.dir.as_sql()
+    );
+    let genres = sqlx::query(&query)
+        .bind(params.limit())
+        .bind(params.offset())
         .map(|row: PgRow| Genre {
             id: row.get(0),
             name: row.get(1),
+            version: row.get("version"),
         })
         .fetch_all(pool)
         .await?;
-    Ok(genres)
+
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM genres WHERE deleted_at IS NULL;")
+        .fetch_one(pool)
+        .await?;
+
+    Ok(Page::new(genres, total, params, sort))
 }
 
 pub async fn find_genre_by_id(id: i32, pool: &PgPool) -> sqlx::Result<Genre> {
-    let genre = sqlx::query("SELECT * FROM genres WHERE id = $1;")
+    let genre = sqlx::query("SELECT * FROM genres WHERE id = $1 AND deleted_at IS NULL;")
         .bind(id)
         .map(|row: PgRow| Genre {
             id: row.get(0),
             name: row.get(1),
+            version: row.get("version"),
         })
         .fetch_one(pool)
         .await?;
@@ -92,13 +493,139 @@ pub async fn find_genre_by_name(name: String, pool: &PgPool) -> sqlx::Result<Vec
     let mut part_name = name.clone();
     part_name.push('%');
 
-    let genre = sqlx::query("SELECT * FROM genres WHERE name LIKE $1;")
+    let genre = sqlx::query("SELECT * FROM genres WHERE name LIKE $1 AND deleted_at IS NULL;")
         .bind(part_name)
         .map(|row: PgRow| Genre {
             id: row.get("id"),
             name: row.get("name"),
+            version: row.get("version"),
         })
         .fetch_all(pool)
         .await?;
     Ok(genre)
 }
+
+///
+/// Returns the id of the active genre with this name,
+/// without case, accents nor surrounding spaces ("jazz " finds "Jazz") :
+/// the comparison of the unique index genres_name_key
+///
+pub async fn find_genre_id(name: &str, pool: &PgPool) -> sqlx::Result<Option<i32>> {
+    sqlx::query_scalar(
+        "SELECT id FROM genres
+                WHERE f_normalize(name) = f_normalize($1) AND deleted_at IS NULL;",
+    )
+    .bind(name)
+    .fetch_optional(pool)
+    .await
+}
+
+//**********************************************************************************
+// Genres des partitions (une partition peut avoir plusieurs genres)
+// le genre principal (partitions.genre_id) est toujours dans partition_genres
+//
+
+///
+/// Adds a genre to a partition
+/// returns false when the partition already had it
+/// the journal records an update of the partition (its genre_ids)
+///
+pub async fn add_partition_genre(
+    partition_id: i32,
+    genre_id: i32,
+    actor: &Actor,
+    pool: &PgPool,
+) -> sqlx::Result<bool> {
+    let mut tx = pool.begin().await?;
+    let before = snapshot(&mut tx, AuditEntity::Partition, partition_id).await?;
+    let result = sqlx::query(
+        "INSERT INTO partition_genres (partition_id, genre_id)
+                VALUES ( $1, $2 )
+                ON CONFLICT DO NOTHING;",
+    )
+    .bind(partition_id)
+    .bind(genre_id)
+    .execute(&mut tx)
+    .await?;
+    let added = result.rows_affected() > 0;
+    if added {
+        let after = snapshot(&mut tx, AuditEntity::Partition, partition_id).await?;
+        record(
+            &mut tx,
+            actor,
+            AuditAction::Update,
+            AuditEntity::Partition,
+            partition_id,
+            before,
+            after,
+        )
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(added)
+}
+
+pub async fn delete_partition_genre(
+    partition_id: i32,
+    genre_id: i32,
+    actor: &Actor,
+    pool: &PgPool,
+) -> sqlx::Result<bool> {
+    let mut tx = pool.begin().await?;
+    let before = snapshot(&mut tx, AuditEntity::Partition, partition_id).await?;
+    let result =
+        sqlx::query("DELETE FROM partition_genres WHERE partition_id = $1 AND genre_id = $2;")
+            .bind(partition_id)
+            .bind(genre_id)
+            .execute(&mut tx)
+            .await?;
+    let deleted = result.rows_affected() > 0;
+    if deleted {
+        let after = snapshot(&mut tx, AuditEntity::Partition, partition_id).await?;
+        record(
+            &mut tx,
+            actor,
+            AuditAction::Update,
+            AuditEntity::Partition,
+            partition_id,
+            before,
+            after,
+        )
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(deleted)
+}
+
+///
+/// Returns the genres of several partitions at once
+/// as (partition id, genre) ordered by name
+///
+pub async fn list_partition_genres(
+    partition_ids: &[i32],
+    pool: &PgPool,
+) -> sqlx::Result<Vec<(i32, Genre)>> {
+    sqlx::query(
+        "
+    SELECT partition_genres.partition_id, genres.id, genres.name, genres.version
+    FROM partition_genres
+    INNER JOIN genres
+    ON partition_genres.genre_id = genres.id
+    WHERE partition_genres.partition_id = ANY($1)
+    ORDER BY genres.name
+        ",
+    )
+    .bind(partition_ids)
+    .map(|row: PgRow| {
+        let genre = Genre {
+            id: row.get("id"),
+            name: row.get("name"),
+            version: row.get("version"),
+        };
+        (row.get("partition_id"), genre)
+    })
+    .fetch_all(pool)
+    .await
+}
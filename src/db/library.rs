@@ -0,0 +1,323 @@
+//! src/db/library.rs
+//!
+//! Bibliothèque : exemplaires imprimés des partitions (table copies)
+//! et prêts aux membres (table loans)
+//! un prêt est en cours tant que returned_at est NULL
+
+use chrono::NaiveDate;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Postgres, Row, Transaction};
+use uuid::Uuid;
+
+use crate::models::library::{CopyChange, CopyCondition, Loan, PartitionCopy};
+
+// colonnes des lots avec le nombre d'exemplaires prêtés
+const COPY_QUERY: &str = "
+    SELECT copies.id, copies.partition_id, copies.quantity, copies.location,
+        copies.condition, copies.added_at,
+        COALESCE((SELECT SUM(loans.quantity) FROM loans
+            WHERE loans.copy_id = copies.id AND loans.returned_at IS NULL), 0)::BIGINT AS on_loan
+    FROM copies";
+
+// colonnes des prêts avec la partition et le membre
+const LOAN_QUERY: &str = "
+    SELECT loans.id, loans.copy_id, copies.partition_id, partitions.title, copies.location,
+        loans.user_id, users.name AS user_name, loans.quantity,
+        loans.loaned_at, loans.due_date, loans.returned_at,
+        (loans.returned_at IS NULL AND loans.due_date < CURRENT_DATE) AS overdue
+    FROM loans
+    INNER JOIN copies
+    ON loans.copy_id = copies.id
+    INNER JOIN partitions
+    ON copies.partition_id = partitions.id
+    INNER JOIN users
+    ON loans.user_id = users.id";
+
+//*******************************************************************************************
+// CRUD Operations on copies
+//
+
+pub async fn add_copy(
+    partition_id: i32,
+    quantity: i32,
+    location: Option<String>,
+    condition: CopyCondition,
+    pool: &PgPool,
+) -> sqlx::Result<PartitionCopy> {
+    let id: i32 = sqlx::query_scalar(
+        "INSERT INTO copies (partition_id, quantity, location, condition)
+                VALUES ( $1, $2, $3, $4 )
+                RETURNING id;",
+    )
+    .bind(partition_id)
+    .bind(quantity)
+    .bind(&location)
+    .bind(condition.as_str())
+    .fetch_one(pool)
+    .await?;
+
+    tracing::info!(
+        "db : copies added : partition {} x {}",
+        partition_id,
+        quantity
+    );
+    find_copy_by_id(id, pool).await
+}
+
+///
+/// Modifies a batch of copies
+/// refused when fewer copies would remain than copies on loan
+/// (the batch is locked while checking, as in add_loan)
+///
+pub async fn update_copy(
+    id: i32,
+    quantity: i32,
+    location: Option<String>,
+    condition: CopyCondition,
+    pool: &PgPool,
+) -> sqlx::Result<CopyChange> {
+    let mut tx = pool.begin().await?;
+    let (_, on_loan) = lock_copy(&mut tx, id).await?;
+    if i64::from(quantity) < on_loan {
+        return Ok(CopyChange::OnLoan(on_loan));
+    }
+
+    sqlx::query("UPDATE copies SET quantity = $1, location = $2, condition = $3 WHERE id = $4;")
+        .bind(quantity)
+        .bind(&location)
+        .bind(condition.as_str())
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+
+    tracing::info!("db : copies updated : {}", id);
+    find_copy_by_id(id, pool).await.map(CopyChange::Done)
+}
+
+///
+/// Deletes a batch of copies and its loan history
+/// refused while some of them are on loan (the batch is locked while checking)
+///
+pub async fn delete_copy(id: i32, pool: &PgPool) -> sqlx::Result<CopyChange> {
+    let mut tx = pool.begin().await?;
+    let (_, on_loan) = lock_copy(&mut tx, id).await?;
+    if on_loan > 0 {
+        return Ok(CopyChange::OnLoan(on_loan));
+    }
+
+    let query = format!("{} WHERE copies.id = $1", COPY_QUERY);
+    let copy = sqlx::query(&query)
+        .bind(id)
+        .try_map(copy_from_row)
+        .fetch_one(&mut tx)
+        .await?;
+    sqlx::query("DELETE FROM copies WHERE id = $1;")
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+
+    tracing::info!("db : copies deleted : {}", id);
+    Ok(CopyChange::Done(copy))
+}
+
+pub async fn find_copy_by_id(id: i32, pool: &PgPool) -> sqlx::Result<PartitionCopy> {
+    let query = format!("{} WHERE copies.id = $1", COPY_QUERY);
+    sqlx::query(&query)
+        .bind(id)
+        .try_map(copy_from_row)
+        .fetch_one(pool)
+        .await
+}
+
+///
+/// Returns the copies of a partition, the best ones first
+///
+pub async fn list_copies(partition_id: i32, pool: &PgPool) -> sqlx::Result<Vec<PartitionCopy>> {
+    let query = format!(
+        "{} WHERE copies.partition_id = $1
+        ORDER BY array_position(ARRAY['new', 'good', 'worn', 'damaged'], copies.condition),
+            copies.location, copies.id",
+        COPY_QUERY
+    );
+    sqlx::query(&query)
+        .bind(partition_id)
+        .try_map(copy_from_row)
+        .fetch_all(pool)
+        .await
+}
+
+//*******************************************************************************************
+// Prêts
+//
+
+///
+/// Lends copies of a batch to a member
+/// returns None when not enough copies are available
+/// (the batch is locked while checking, two loans cannot take the same copies)
+///
+pub async fn add_loan(
+    copy_id: i32,
+    user_id: Uuid,
+    quantity: i32,
+    due_date: NaiveDate,
+    pool: &PgPool,
+) -> sqlx::Result<Option<Loan>> {
+    let mut tx = pool.begin().await?;
+    let (owned, on_loan) = lock_copy(&mut tx, copy_id).await?;
+    if i64::from(quantity) > i64::from(owned) - on_loan {
+        return Ok(None);
+    }
+
+    let id: i32 = sqlx::query_scalar(
+        "INSERT INTO loans (copy_id, user_id, quantity, due_date)
+                VALUES ( $1, $2, $3, $4 )
+                RETURNING id;",
+    )
+    .bind(copy_id)
+    .bind(user_id)
+    .bind(quantity)
+    .bind(due_date)
+    .fetch_one(&mut tx)
+    .await?;
+    tx.commit().await?;
+
+    tracing::info!("db : loan added : {}", id);
+    find_loan_by_id(id, pool).await.map(Some)
+}
+
+///
+/// locks a batch of copies until the end of the transaction
+/// and returns its number of copies and the number of them on loan
+///
+async fn lock_copy(tx: &mut Transaction<'_, Postgres>, id: i32) -> sqlx::Result<(i32, i64)> {
+    let quantity: i32 = sqlx::query_scalar("SELECT quantity FROM copies WHERE id = $1 FOR UPDATE;")
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+    let on_loan: i64 = sqlx::query_scalar(
+        "SELECT COALESCE(SUM(quantity), 0)::BIGINT FROM loans
+                WHERE copy_id = $1 AND returned_at IS NULL;",
+    )
+    .bind(id)
+    .fetch_one(&mut *tx)
+    .await?;
+    Ok((quantity, on_loan))
+}
+
+///
+/// Records the return of a loan
+/// returns None when the loan was already returned
+///
+pub async fn return_loan(id: i32, pool: &PgPool) -> sqlx::Result<Option<Loan>> {
+    let result =
+        sqlx::query("UPDATE loans SET returned_at = now() WHERE id = $1 AND returned_at IS NULL;")
+            .bind(id)
+            .execute(pool)
+            .await?;
+    if result.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    tracing::info!("db : loan returned : {}", id);
+    find_loan_by_id(id, pool).await.map(Some)
+}
+
+pub async fn find_loan_by_id(id: i32, pool: &PgPool) -> sqlx::Result<Loan> {
+    let query = format!("{} WHERE loans.id = $1", LOAN_QUERY);
+    sqlx::query(&query)
+        .bind(id)
+        .map(loan_from_row)
+        .fetch_one(pool)
+        .await
+}
+
+///
+/// Returns the loans in progress of a partition, ordered by due date
+///
+pub async fn list_active_loans(partition_id: i32, pool: &PgPool) -> sqlx::Result<Vec<Loan>> {
+    let query = format!(
+        "{} WHERE copies.partition_id = $1 AND loans.returned_at IS NULL
+        ORDER BY loans.due_date, loans.id",
+        LOAN_QUERY
+    );
+    sqlx::query(&query)
+        .bind(partition_id)
+        .map(loan_from_row)
+        .fetch_all(pool)
+        .await
+}
+
+///
+/// Returns the loans not returned after their due date, the oldest first
+///
+pub async fn list_overdue_loans(pool: &PgPool) -> sqlx::Result<Vec<Loan>> {
+    let query = format!(
+        "{} WHERE loans.returned_at IS NULL AND loans.due_date < CURRENT_DATE
+        ORDER BY loans.due_date, users.name",
+        LOAN_QUERY
+    );
+    sqlx::query(&query).map(loan_from_row).fetch_all(pool).await
+}
+
+///
+/// Returns the loans of a member : in progress first, then the returned ones
+///
+pub async fn list_user_loans(user_id: Uuid, pool: &PgPool) -> sqlx::Result<Vec<Loan>> {
+    let query = format!(
+        "{} WHERE loans.user_id = $1
+        ORDER BY loans.returned_at IS NOT NULL, loans.due_date, loans.returned_at DESC",
+        LOAN_QUERY
+    );
+    sqlx::query(&query)
+        .bind(user_id)
+        .map(loan_from_row)
+        .fetch_all(pool)
+        .await
+}
+
+//******************************************************************************************
+// Construction des structs à partir des lignes de la DB
+//
+
+fn copy_from_row(row: PgRow) -> sqlx::Result<PartitionCopy> {
+    let condition: String = row.try_get("condition")?;
+    let condition: CopyCondition =
+        condition
+            .parse()
+            .map_err(|e: String| sqlx::Error::ColumnDecode {
+                index: "condition".to_string(),
+                source: e.into(),
+            })?;
+    let quantity: i32 = row.try_get("quantity")?;
+    let on_loan: i64 = row.try_get("on_loan")?;
+    Ok(PartitionCopy {
+        id: row.try_get("id")?,
+        partition_id: row.try_get("partition_id")?,
+        quantity,
+        location: row.try_get("location")?,
+        condition,
+        condition_label: condition.label().to_string(),
+        added_at: row.try_get("added_at")?,
+        on_loan,
+        available: i64::from(quantity) - on_loan,
+    })
+}
+
+fn loan_from_row(row: PgRow) -> Loan {
+    Loan {
+        id: row.get("id"),
+        copy_id: row.get("copy_id"),
+        partition_id: row.get("partition_id"),
+        title: row.get("title"),
+        location: row.get("location"),
+        user_id: row.get("user_id"),
+        user_name: row.get("user_name"),
+        quantity: row.get("quantity"),
+        loaned_at: row.get("loaned_at"),
+        due_date: row.get("due_date"),
+        returned_at: row.get("returned_at"),
+        overdue: row.get("overdue"),
+    }
+}
@@ -1,6 +1,14 @@
 //! src/db/db
+pub mod attachments;
+pub mod audit;
 pub mod connect;
+pub mod contributors;
 pub mod genres;
+pub mod library;
 pub mod musicians;
 pub mod partitions;
+pub mod search;
+pub mod setlists;
+pub mod tags;
+pub mod trash;
 pub mod users;
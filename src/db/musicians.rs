@@ -1,61 +1,438 @@
 //! src/db/musicians.rs
 
 use sqlx::postgres::PgRow;
-use sqlx::{PgPool, Row};
+use sqlx::{PgPool, Postgres, Row, Transaction};
 
-use crate::models::musician::Person;
+use crate::db::audit::{record, record_partition_change, snapshot};
+use crate::models::audit::{Actor, AuditAction, AuditEntity};
+use crate::models::contributor::ContributorRole;
+use crate::models::deletion::{Deletion, Dependent};
+use crate::models::merge::{Candidate, MergeResult, SimilarPair};
+use crate::models::musician::{Person, PersonName};
+use crate::models::pagination::{ListParams, Page};
+use crate::models::version::Update;
 
 //*******************************************************************************************
 // CRUD Operations on persons - musicians
 //
-pub async fn add_person(pool: &PgPool, pers: Person) -> sqlx::Result<Person> {
+pub async fn add_person(pool: &PgPool, name: PersonName, actor: &Actor) -> sqlx::Result<Person> {
     let mut tx = pool.begin().await?;
     let person = sqlx::query(
         "INSERT INTO persons (full_name)
                 VALUES ( $1 )
-                RETURNING id, full_name;",
+                RETURNING id, full_name, version;",
     )
-    .bind(&pers.full_name)
+    .bind(name.as_ref())
     .map(|row: PgRow| Person {
         id: row.get(0),
         full_name: row.get(1),
+        version: row.get("version"),
     })
     .fetch_one(&mut tx)
     .await?;
+    let id = person.id.unwrap_or_default();
+    let after = snapshot(&mut tx, AuditEntity::Person, id).await?;
+    record(
+        &mut tx,
+        actor,
+        AuditAction::Create,
+        AuditEntity::Person,
+        id,
+        None,
+        after,
+    )
+    .await?;
     tx.commit().await?;
 
     tracing::info!("db : person added : {:?}", &person);
     Ok(person)
 }
 
-pub async fn update_person(id: i32, person_name: String, pool: &PgPool) -> sqlx::Result<Person> {
-    let person =
-        sqlx::query("UPDATE persons SET full_name = $1 WHERE id = $2 RETURNING id, full_name;")
-            .bind(&person_name)
-            .bind(id)
-            .map(|row: PgRow| Person {
-                id: row.get(0),
-                full_name: row.get(1),
-            })
-            .fetch_one(pool)
-            .await?;
+///
+/// Renames a musician
+/// the row is only updated if its version is still the one sent back
+/// (by the edit form or the JSON) : Update::Conflict with the current row otherwise
+/// RowNotFound when the musician does not exist or is in the trash
+///
+pub async fn update_person(
+    id: i32,
+    person_name: PersonName,
+    version: i32,
+    actor: &Actor,
+    pool: &PgPool,
+) -> sqlx::Result<Update<Person>> {
+    let mut tx = pool.begin().await?;
+    let before = snapshot(&mut tx, AuditEntity::Person, id).await?;
+    let person = sqlx::query(
+        "UPDATE persons SET full_name = $1, version = version + 1
+                WHERE id = $2 AND deleted_at IS NULL
+                AND version = $3
+                RETURNING id, full_name, version;",
+    )
+    .bind(person_name.as_ref())
+    .bind(id)
+    .bind(version)
+    .map(|row: PgRow| Person {
+        id: row.get(0),
+        full_name: row.get(1),
+        version: row.get("version"),
+    })
+    .fetch_optional(&mut tx)
+    .await?;
+    let person = match person {
+        Some(person) => person,
+        None => {
+            tx.rollback().await?;
+            let current = find_person_by_id(id, pool).await?;
+            tracing::info!(
+                "db : Person not updated (version {:?}) : {:?}",
+                version,
+                &current
+            );
+            return Ok(Update::Conflict { current });
+        }
+    };
+    let after = snapshot(&mut tx, AuditEntity::Person, id).await?;
+    record(
+        &mut tx,
+        actor,
+        AuditAction::Update,
+        AuditEntity::Person,
+        id,
+        before,
+        after,
+    )
+    .await?;
+    tx.commit().await?;
 
     tracing::info!("db : Person updated : {:?}", &person);
-    Ok(person)
+    Ok(Update::Saved(person))
 }
 
-pub async fn delete_person(id: i32, pool: &PgPool) -> sqlx::Result<String> {
-    let pers = find_person_by_id(id.clone(), pool).await?;
-    let name = pers.full_name;
+///
+/// Moves a musician to the trash in one transaction (db::trash restores or purges it)
+/// the partitions which depend on it (main musician or contributor)
+/// are first given to the musician reassign_to ;
+/// without reassign_to, the deletion is refused while partitions remain
+/// (trashed partitions keep their musician : it comes back with them)
+///
+pub async fn delete_person(
+    id: i32,
+    reassign_to: Option<i32>,
+    actor: &Actor,
+    pool: &PgPool,
+) -> sqlx::Result<Deletion> {
+    let mut tx = pool.begin().await?;
+
+    // le verrou empêche l'ajout d'une partition pendant l'effacement
+    let name: String = sqlx::query_scalar(
+        "SELECT full_name FROM persons WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+    )
+    .bind(id)
+    .fetch_one(&mut tx)
+    .await?;
+    let dependents: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM partitions
+                WHERE deleted_at IS NULL
+                AND (person_id = $1
+                    OR EXISTS (SELECT 1 FROM partition_contributors
+                        WHERE partition_id = partitions.id AND person_id = $1));",
+    )
+    .bind(id)
+    .fetch_one(&mut tx)
+    .await?;
+
+    if dependents > 0 {
+        let target = match reassign_to {
+            Some(target) if target != id => target,
+            _ => {
+                tx.rollback().await?;
+                tracing::info!(
+                    "db : Person not deleted : {} ({} partitions)",
+                    &name,
+                    dependents
+                );
+                return Ok(Deletion::Refused { name, dependents });
+            }
+        };
+        // RowNotFound si le musicien de remplacement n'existe pas (ou est à la corbeille)
+        sqlx::query("SELECT id FROM persons WHERE id = $1 AND deleted_at IS NULL FOR UPDATE")
+            .bind(target)
+            .fetch_one(&mut tx)
+            .await?;
+        repoint_partitions(&mut tx, &[id], target, false, actor).await?;
+    }
 
-    let _res = sqlx::query("DELETE FROM persons WHERE id = $1")
+    let before = snapshot(&mut tx, AuditEntity::Person, id).await?;
+    sqlx::query("UPDATE persons SET deleted_at = now() WHERE id = $1")
         .bind(id)
-        .execute(pool)
+        .execute(&mut tx)
         .await?;
+    let mut after = snapshot(&mut tx, AuditEntity::Person, id).await?;
+    if let (Some(target), Some(serde_json::Value::Object(fields))) =
+        (reassign_to.filter(|_| dependents > 0), after.as_mut())
+    {
+        fields.insert("reassigned_to".to_string(), target.into());
+        fields.insert("partitions".to_string(), dependents.into());
+    }
+    record(
+        &mut tx,
+        actor,
+        AuditAction::Delete,
+        AuditEntity::Person,
+        id,
+        before,
+        after,
+    )
+    .await?;
+    tx.commit().await?;
+
+    tracing::info!(
+        "db : Person trashed : {} ({} partitions reassigned)",
+        &name,
+        dependents
+    );
 
-    tracing::info!("db : Person deleted : {}", &name);
+    Ok(Deletion::Deleted {
+        name,
+        reassigned: dependents,
+    })
+}
+
+///
+/// Returns the partitions (not trashed) which depend on a musician
+/// with its roles in each of them
+///
+pub async fn list_person_dependents(id: i32, pool: &PgPool) -> sqlx::Result<Vec<Dependent>> {
+    let dependents = sqlx::query(
+        "SELECT partitions.id, partitions.title, partitions.person_id = $1 AS main,
+                    COALESCE(array_agg(partition_contributors.role ORDER BY partition_contributors.role)
+                        FILTER (WHERE partition_contributors.role IS NOT NULL), '{}') AS roles
+                FROM partitions
+                LEFT JOIN partition_contributors
+                ON partition_contributors.partition_id = partitions.id
+                    AND partition_contributors.person_id = $1
+                WHERE partitions.deleted_at IS NULL
+                AND (partitions.person_id = $1 OR partition_contributors.person_id IS NOT NULL)
+                GROUP BY partitions.id
+                ORDER BY partitions.title;",
+    )
+    .bind(id)
+    .map(|row: PgRow| {
+        let roles: Vec<String> = row.get("roles");
+        Dependent {
+            partition_id: row.get("id"),
+            title: row.get("title"),
+            main: row.get("main"),
+            roles: roles
+                .iter()
+                .filter_map(|role| role.parse::<ContributorRole>().ok())
+                .map(|role| role.label().to_string())
+                .collect(),
+        }
+    })
+    .fetch_all(pool)
+    .await?;
 
-    Ok(name)
+    Ok(dependents)
+}
+
+///
+/// Merges duplicate musicians into the survivor in one transaction :
+/// their partitions and roles are given to the survivor, then they are deleted
+/// the survivor itself and the ids in the trash are left out of the duplicates
+///
+pub async fn merge_persons(
+    survivor: i32,
+    duplicates: &[i32],
+    actor: &Actor,
+    pool: &PgPool,
+) -> sqlx::Result<MergeResult> {
+    let mut tx = pool.begin().await?;
+
+    let survivor_name: String = sqlx::query_scalar(
+        "SELECT full_name FROM persons WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+    )
+    .bind(survivor)
+    .fetch_one(&mut tx)
+    .await?;
+    // seuls les doublons actifs, autres que le survivant, sont repris et effacés
+    let rows: Vec<(i32, String)> = sqlx::query_as(
+        "SELECT id, full_name FROM persons WHERE id = ANY($1) AND id <> $2 AND deleted_at IS NULL
+                ORDER BY full_name FOR UPDATE;",
+    )
+    .bind(duplicates)
+    .bind(survivor)
+    .fetch_all(&mut tx)
+    .await?;
+    if rows.is_empty() {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    let (ids, merged): (Vec<i32>, Vec<String>) = rows.into_iter().unzip();
+    let partitions: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM partitions
+                WHERE deleted_at IS NULL
+                AND (person_id = ANY($1)
+                    OR EXISTS (SELECT 1 FROM partition_contributors
+                        WHERE partition_id = partitions.id AND person_id = ANY($1)));",
+    )
+    .bind(&ids)
+    .fetch_one(&mut tx)
+    .await?;
+
+    // les partitions à la corbeille passent aussi au survivant : les doublons sont effacés
+    repoint_partitions(&mut tx, &ids, survivor, true, actor).await?;
+    for &id in &ids {
+        let before = snapshot(&mut tx, AuditEntity::Person, id).await?;
+        sqlx::query("DELETE FROM persons WHERE id = $1")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        let after = serde_json::json!({ "reassigned_to": survivor });
+        record(
+            &mut tx,
+            actor,
+            AuditAction::Delete,
+            AuditEntity::Person,
+            id,
+            before,
+            Some(after),
+        )
+        .await?;
+    }
+    tx.commit().await?;
+
+    tracing::info!("db : Persons {:?} merged into {}", &merged, &survivor_name);
+
+    Ok(MergeResult {
+        survivor: survivor_name,
+        merged,
+        partitions,
+    })
+}
+
+///
+/// gives the partitions of the musicians "from" to the musician "to" :
+/// main musician, and roles that "to" does not already have in the partition
+/// the partitions in the trash are left to "from" unless with_trashed
+/// each partition changed is recorded in the journal
+///
+async fn repoint_partitions(
+    tx: &mut Transaction<'_, Postgres>,
+    from: &[i32],
+    to: i32,
+    with_trashed: bool,
+    actor: &Actor,
+) -> sqlx::Result<()> {
+    // les partitions concernées, copiées pour le journal avant la modification
+    let partition_ids: Vec<i32> = sqlx::query_scalar(
+        "SELECT id FROM partitions
+                WHERE ($2 OR deleted_at IS NULL)
+                AND (person_id = ANY($1)
+                    OR EXISTS (SELECT 1 FROM partition_contributors
+                        WHERE partition_id = partitions.id AND person_id = ANY($1)))
+                ORDER BY id;",
+    )
+    .bind(from)
+    .bind(with_trashed)
+    .fetch_all(&mut *tx)
+    .await?;
+    let mut partitions = Vec::with_capacity(partition_ids.len());
+    for partition_id in partition_ids {
+        let before = snapshot(&mut *tx, AuditEntity::Partition, partition_id).await?;
+        partitions.push((partition_id, before));
+    }
+
+    sqlx::query(
+        "UPDATE partitions SET person_id = $2, version = version + 1
+                WHERE person_id = ANY($1) AND ($3 OR deleted_at IS NULL);",
+    )
+    .bind(from)
+    .bind(to)
+    .bind(with_trashed)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "INSERT INTO partition_contributors (partition_id, person_id, role)
+                SELECT partition_id, $2, role FROM partition_contributors
+                WHERE person_id = ANY($1)
+                AND ($3 OR partition_id IN (SELECT id FROM partitions WHERE deleted_at IS NULL))
+                ON CONFLICT DO NOTHING;",
+    )
+    .bind(from)
+    .bind(to)
+    .bind(with_trashed)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "DELETE FROM partition_contributors
+                WHERE person_id = ANY($1)
+                AND ($2 OR partition_id IN (SELECT id FROM partitions WHERE deleted_at IS NULL));",
+    )
+    .bind(from)
+    .bind(with_trashed)
+    .execute(&mut *tx)
+    .await?;
+    for (partition_id, before) in partitions {
+        record_partition_change(&mut *tx, actor, partition_id, before).await?;
+    }
+    Ok(())
+}
+
+///
+/// Returns the pairs of musicians whose names look alike
+/// (trigram similarity without accents nor case, of the whole names or of
+/// one name inside the other : "Bach" and "J.S. Bach") above the threshold
+///
+pub async fn find_similar_persons(threshold: f32, pool: &PgPool) -> sqlx::Result<Vec<SimilarPair>> {
+    let mut tx = pool.begin().await?;
+    // seuils des opérateurs % et <% : ils utilisent l'index trigrammes de persons
+    sqlx::query(
+        "SELECT set_config('pg_trgm.similarity_threshold', $1, true),
+                set_config('pg_trgm.word_similarity_threshold', $1, true);",
+    )
+    .bind(threshold.to_string())
+    .execute(&mut tx)
+    .await?;
+
+    let pairs = sqlx::query(
+        "SELECT a.id AS a_id, a.full_name AS a_name, b.id AS b_id, b.full_name AS b_name,
+                    (SELECT COUNT(*) FROM partitions
+                        WHERE person_id = a.id AND deleted_at IS NULL) AS a_count,
+                    (SELECT COUNT(*) FROM partitions
+                        WHERE person_id = b.id AND deleted_at IS NULL) AS b_count,
+                    greatest(similarity(an, bn), word_similarity(an, bn), word_similarity(bn, an)) AS score
+                FROM persons a
+                CROSS JOIN LATERAL (SELECT f_unaccent(lower(a.full_name)) AS an) AS na
+                INNER JOIN persons b
+                ON b.id > a.id
+                    AND b.deleted_at IS NULL
+                    AND (f_unaccent(lower(b.full_name)) % an
+                        OR an <% f_unaccent(lower(b.full_name))
+                        OR f_unaccent(lower(b.full_name)) <% an)
+                CROSS JOIN LATERAL (SELECT f_unaccent(lower(b.full_name)) AS bn) AS nb
+                WHERE a.deleted_at IS NULL
+                ORDER BY score DESC, a.full_name
+                LIMIT 500;",
+    )
+    .map(|row: PgRow| SimilarPair {
+        first: Candidate {
+            id: row.get("a_id"),
+            name: row.get("a_name"),
+            partitions: row.get("a_count"),
+        },
+        second: Candidate {
+            id: row.get("b_id"),
+            name: row.get("b_name"),
+            partitions: row.get("b_count"),
+        },
+        score: row.get("score"),
+    })
+    .fetch_all(&mut tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(pairs)
 }
 
 //**********************************************************************************
@@ -67,11 +444,12 @@ pub async fn delete_person(id: i32, pool: &PgPool) -> sqlx::Result<String> {
 /// used as help function for others
 ///
 pub async fn find_person_by_id(id: i32, pool: &PgPool) -> sqlx::Result<Person> {
-    let person = sqlx::query("SELECT * FROM persons WHERE id = $1;")
+    let person = sqlx::query("SELECT * FROM persons WHERE id = $1 AND deleted_at IS NULL;")
         .bind(id)
         .map(|row: PgRow| Person {
             id: row.get(0),
             full_name: row.get(1),
+            version: row.get("version"),
         })
         .fetch_one(pool)
         .await?;
@@ -90,13 +468,14 @@ pub async fn find_person_by_name(full_name: String, pool: &PgPool) -> sqlx::Resu
 
     let select_query = sqlx::query(
         "SELECT * FROM persons \
-                         WHERE full_name LIKE $1",
+                         WHERE full_name LIKE $1 AND deleted_at IS NULL",
     );
     let person = select_query
         .bind(name)
         .map(|row: PgRow| Person {
             id: row.get("id"),
             full_name: row.get("full_name"),
+            version: row.get("version"),
         })
         .fetch_all(pool)
         .await?;
@@ -104,6 +483,21 @@ pub async fn find_person_by_name(full_name: String, pool: &PgPool) -> sqlx::Resu
     Ok(person)
 }
 
+///
+/// Returns the id of the active musician with this name,
+/// without case, accents nor surrounding spaces ("faure " finds "Fauré") :
+/// the comparison of the unique index persons_full_name_key
+///
+pub async fn find_person_id(full_name: &str, pool: &PgPool) -> sqlx::Result<Option<i32>> {
+    sqlx::query_scalar(
+        "SELECT id FROM persons
+                WHERE f_normalize(full_name) = f_normalize($1) AND deleted_at IS NULL;",
+    )
+    .bind(full_name)
+    .fetch_optional(pool)
+    .await
+}
+
 ///
 /// Returns a list of musicians
 /// under the form of a Vec<Person>
@@ -111,13 +505,50 @@ pub async fn find_person_by_name(full_name: String, pool: &PgPool) -> sqlx::Resu
 ///
 pub async fn list_persons(pool: &PgPool) -> sqlx::Result<Vec<Person>> {
     //let mut persons: Vec<Person> = Vec::new();
-    let recs = sqlx::query("SELECT id, full_name FROM persons ORDER BY full_name;")
+    let recs = sqlx::query(
+        "SELECT id, full_name, version FROM persons WHERE deleted_at IS NULL ORDER BY full_name;",
+    )
+    .map(|row: PgRow| Person {
+        id: row.get("id"),
+        full_name: row.get("full_name"),
+        version: row.get("version"),
+    })
+    .fetch_all(pool)
+    .await?;
+
+    Ok(recs)
+}
+
+///
+/// Returns one page of musicians
+/// sorted on "name" (default) or "id"
+/// with the total number of musicians
+///
+pub async fn list_persons_page(pool: &PgPool, params: &ListParams) -> sqlx::Result<Page<Person>> {
+    let (sort, column) = match params.sort.as_deref() {
+        Some("id") => ("id", "id"),
+        _ => ("name", "full_name"),
+    };
+    let query = format!(
+        "SELECT id, full_name, version FROM persons WHERE deleted_at IS NULL
+        ORDER BY {} {}, id LIMIT $1 OFFSET $2;",
+        column,
+        params.dir.as_sql()
+    );
+    let persons = sqlx::query(&query)
+        .bind(params.limit())
+        .bind(params.offset())
         .map(|row: PgRow| Person {
             id: row.get("id"),
             full_name: row.get("full_name"),
+            version: row.get("version"),
         })
         .fetch_all(pool)
         .await?;
 
-    Ok(recs)
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM persons WHERE deleted_at IS NULL;")
+        .fetch_one(pool)
+        .await?;
+
+    Ok(Page::new(persons, total, params, sort))
 }
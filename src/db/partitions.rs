@@ -1,100 +1,402 @@
 //! src/db/partitions.rs
 
+use std::collections::HashMap;
+
 use sqlx::postgres::PgRow;
-use sqlx::{PgPool, Row};
+use sqlx::{PgPool, Postgres, QueryBuilder, Row, Transaction};
+
+use crate::db::audit::{record, snapshot};
+use crate::db::contributors::list_contributors;
+use crate::db::genres::list_partition_genres;
+use crate::db::search::escape_like;
+use crate::db::tags::{find_or_insert_tag, list_partition_tags};
+use crate::models::audit::{Actor, AuditAction, AuditEntity};
+use crate::models::csv_import::ImportRow;
+use crate::models::pagination::{ListParams, Page};
+use crate::models::partition::{
+    DuplicatePartition, Partition, PartitionFilter, PartitionMetadata, PartitionTitle,
+    ShowPartition,
+};
+use crate::models::version::Update;
 
-use crate::db::genres::find_genre_by_name;
-use crate::db::musicians::find_person_by_name;
-use crate::models::partition::{Partition, ShowPartition};
+// colonnes des renseignements facultatifs (PartitionMetadata), dans l'ordre des binds
+const METADATA_COLUMNS: &str = "instrumentation, music_key, tempo, difficulty, duration_seconds, \
+     opus, publisher, edition_year, notes";
 
 //*******************************************************************************************
 // CRUD Operations on partitions
 //
-pub async fn add_partition(
-    title: String,
-    person_name: String,
-    genre_name: String,
+
+///
+/// Adds a partition when the ids of the person and the genre are already known
+/// (used by the JSON API and by the form, which finds them by their names)
+/// the person is also recorded as composer in partition_contributors
+/// and the genre in partition_genres
+///
+pub async fn add_partition_with_ids(
+    title: PartitionTitle,
+    person_id: i32,
+    genre_id: i32,
+    metadata: PartitionMetadata,
+    actor: &Actor,
     pool: &PgPool,
 ) -> sqlx::Result<Partition> {
-    let person_id: i32;
-    let genre_id: i32;
-
-    if let Ok(person) = find_person_by_name(person_name, &pool).await {
-        tracing::info!("from db::add_partition : personne : {:?}", person);
-        person_id = person[0].id.unwrap();
-    } else {
-        return Err(sqlx::Error::RowNotFound);
-    };
+    let mut tx = pool.begin().await?;
+    let partition = insert_partition(
+        &mut tx,
+        title.as_ref(),
+        person_id,
+        genre_id,
+        &metadata,
+        &[],
+        actor,
+    )
+    .await?;
+    tx.commit().await?;
 
-    if let Ok(genre) = find_genre_by_name(genre_name, &pool).await {
-        tracing::info!("from db::add_partition : genre : {:?}", genre);
-        genre_id = genre[0].id.unwrap();
-    } else {
-        return Err(sqlx::Error::RowNotFound);
-    };
+    tracing::info!("db : partition added : {:?}", &partition);
+
+    Ok(partition)
+}
 
-    let partition = sqlx::query(
-        "INSERT INTO partitions (title, person_id, genre_id)
-                VALUES ( $1, $2, $3 )
-                RETURNING id, title, person_id, genre_id;",
+///
+/// Inserts a partition inside a transaction opened by the caller
+/// with its composer in partition_contributors, its genre in partition_genres,
+/// its tags and its line in the journal
+/// (shared by add_partition_with_ids and the CSV import)
+///
+pub(crate) async fn insert_partition(
+    tx: &mut Transaction<'_, Postgres>,
+    title: &str,
+    person_id: i32,
+    genre_id: i32,
+    metadata: &PartitionMetadata,
+    tag_ids: &[i32],
+    actor: &Actor,
+) -> sqlx::Result<Partition> {
+    let query = format!(
+        "INSERT INTO partitions (title, person_id, genre_id, {columns})
+                VALUES ( $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12 )
+                RETURNING id, title, person_id, genre_id, version, {columns};",
+        columns = METADATA_COLUMNS
+    );
+    let partition = sqlx::query(&query)
+        .bind(title)
+        .bind(person_id)
+        .bind(genre_id)
+        .bind(&metadata.instrumentation)
+        .bind(&metadata.music_key)
+        .bind(&metadata.tempo)
+        .bind(&metadata.difficulty)
+        .bind(&metadata.duration_seconds)
+        .bind(&metadata.opus)
+        .bind(&metadata.publisher)
+        .bind(&metadata.edition_year)
+        .bind(&metadata.notes)
+        .map(partition_from_row)
+        .fetch_one(&mut *tx)
+        .await?;
+    sqlx::query(
+        "INSERT INTO partition_contributors (partition_id, person_id, role)
+                VALUES ( $1, $2, 'composer' )
+                ON CONFLICT DO NOTHING;",
     )
-    .bind(&title)
-    .bind(&person_id)
-    .bind(&genre_id)
-    .map(|row: PgRow| Partition {
-        id: row.get(0),
-        title: row.get(1),
-        person_id: row.get(2),
-        genre_id: row.get(3),
-    })
-    .fetch_one(pool)
+    .bind(partition.id)
+    .bind(person_id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "INSERT INTO partition_genres (partition_id, genre_id)
+                VALUES ( $1, $2 )
+                ON CONFLICT DO NOTHING;",
+    )
+    .bind(partition.id)
+    .bind(genre_id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "INSERT INTO partition_tags (partition_id, tag_id)
+                SELECT $1, UNNEST($2::INTEGER[])
+                ON CONFLICT DO NOTHING;",
+    )
+    .bind(partition.id)
+    .bind(tag_ids)
+    .execute(&mut *tx)
     .await?;
 
-    tracing::info!("db : partition added : {:?}", &partition);
+    let id = partition.id.unwrap_or_default();
+    let after = snapshot(&mut *tx, AuditEntity::Partition, id).await?;
+    record(
+        &mut *tx,
+        actor,
+        AuditAction::Create,
+        AuditEntity::Partition,
+        id,
+        None,
+        after,
+    )
+    .await?;
 
     Ok(partition)
 }
 
+///
+/// Imports the rows of a checked CSV file in a single transaction :
+/// the missing musicians, genres and tags are created,
+/// nothing is recorded if one insert fails
+/// returns the number of partitions added
+///
+pub async fn import_partitions(
+    rows: &[ImportRow],
+    actor: &Actor,
+    pool: &PgPool,
+) -> sqlx::Result<usize> {
+    // les noms créés pendant l'import, pour ne pas les chercher à chaque ligne
+    let mut persons: HashMap<&str, i32> = HashMap::new();
+    let mut genres: HashMap<&str, i32> = HashMap::new();
+
+    let mut tx = pool.begin().await?;
+    for row in rows {
+        let person_id = match row
+            .person_id
+            .or_else(|| persons.get(row.composer.as_str()).copied())
+        {
+            Some(id) => id,
+            None => {
+                // xmax = 0 : la ligne vient d'être créée, elle n'existait pas déjà
+                // (un nom qui ne diffère que par la casse, les accents ou les espaces
+                // reprend le musicien existant : index persons_full_name_key)
+                let (id, created): (i32, bool) = sqlx::query_as(
+                    "INSERT INTO persons (full_name) VALUES ( $1 )
+                            ON CONFLICT (f_normalize(full_name)) WHERE deleted_at IS NULL
+                            DO UPDATE SET full_name = persons.full_name
+                            RETURNING id, xmax = 0;",
+                )
+                .bind(&row.composer)
+                .fetch_one(&mut tx)
+                .await?;
+                if created {
+                    let after = snapshot(&mut tx, AuditEntity::Person, id).await?;
+                    record(
+                        &mut tx,
+                        actor,
+                        AuditAction::Create,
+                        AuditEntity::Person,
+                        id,
+                        None,
+                        after,
+                    )
+                    .await?;
+                }
+                persons.insert(&row.composer, id);
+                id
+            }
+        };
+        let genre_id = match row
+            .genre_id
+            .or_else(|| genres.get(row.genre.as_str()).copied())
+        {
+            Some(id) => id,
+            None => {
+                let (id, created): (i32, bool) = sqlx::query_as(
+                    "INSERT INTO genres (name) VALUES ( $1 )
+                            ON CONFLICT (f_normalize(name)) WHERE deleted_at IS NULL
+                            DO UPDATE SET name = genres.name
+                            RETURNING id, xmax = 0;",
+                )
+                .bind(&row.genre)
+                .fetch_one(&mut tx)
+                .await?;
+                if created {
+                    let after = snapshot(&mut tx, AuditEntity::Genre, id).await?;
+                    record(
+                        &mut tx,
+                        actor,
+                        AuditAction::Create,
+                        AuditEntity::Genre,
+                        id,
+                        None,
+                        after,
+                    )
+                    .await?;
+                }
+                genres.insert(&row.genre, id);
+                id
+            }
+        };
+
+        let mut tag_ids = Vec::with_capacity(row.tags.len());
+        for tag in &row.tags {
+            let tag = find_or_insert_tag(&mut tx, tag, actor).await?;
+            tag_ids.extend(tag.id);
+        }
+
+        insert_partition(
+            &mut tx,
+            &row.title,
+            person_id,
+            genre_id,
+            &row.metadata,
+            &tag_ids,
+            actor,
+        )
+        .await?;
+    }
+    tx.commit().await?;
+
+    tracing::info!("db : import CSV : {} partition(s) added", rows.len());
+    Ok(rows.len())
+}
+
+///
+/// Updates a partition
+/// when the main musician changes, the new one replaces the previous one
+/// as composer in partition_contributors ; the same for the main genre in partition_genres
+/// the row is only updated if its version is still the one sent back
+/// (by the edit form or the JSON) : Update::Conflict with the current row otherwise
+///
+#[allow(clippy::too_many_arguments)]
 pub async fn update_partition(
     id: i32,
-    partition_title: String,
+    partition_title: PartitionTitle,
     person_id: i32,
     genre_id: i32,
+    metadata: PartitionMetadata,
+    version: i32,
+    actor: &Actor,
     pool: &PgPool,
-) -> sqlx::Result<Partition> {
-    let row = sqlx::query!(
-        r#"
-        UPDATE partitions
-        SET title = $1, person_id = $2, genre_id = $3
-        WHERE id = $4
-        RETURNING id, title, person_id, genre_id
-        "#,
-        partition_title,
-        person_id,
-        genre_id,
+) -> sqlx::Result<Update<Partition>> {
+    let query = format!(
+        "UPDATE partitions
+        SET title = $1, person_id = $2, genre_id = $3,
+            instrumentation = $4, music_key = $5, tempo = $6, difficulty = $7,
+            duration_seconds = $8, opus = $9, publisher = $10, edition_year = $11, notes = $12,
+            version = version + 1
+        WHERE id = $13
+        RETURNING id, title, person_id, genre_id, version, {columns};",
+        columns = METADATA_COLUMNS
+    );
+    let mut tx = pool.begin().await?;
+    let (previous_person_id, previous_genre_id, current_version): (Option<i32>, Option<i32>, i32) =
+        sqlx::query_as(
+            "SELECT person_id, genre_id, version FROM partitions
+                WHERE id = $1 AND deleted_at IS NULL FOR UPDATE;",
+        )
+        .bind(id)
+        .fetch_one(&mut tx)
+        .await?;
+    if version != current_version {
+        tx.rollback().await?;
+        let current = find_partition_by_id(id, pool).await?;
+        tracing::info!(
+            "db : partition not updated (version {}) : {:?}",
+            version,
+            &current
+        );
+        return Ok(Update::Conflict { current });
+    }
+    let before = snapshot(&mut tx, AuditEntity::Partition, id).await?;
+    let partition = sqlx::query(&query)
+        .bind(partition_title.as_ref())
+        .bind(&person_id)
+        .bind(&genre_id)
+        .bind(&metadata.instrumentation)
+        .bind(&metadata.music_key)
+        .bind(&metadata.tempo)
+        .bind(&metadata.difficulty)
+        .bind(&metadata.duration_seconds)
+        .bind(&metadata.opus)
+        .bind(&metadata.publisher)
+        .bind(&metadata.edition_year)
+        .bind(&metadata.notes)
+        .bind(id)
+        .map(partition_from_row)
+        .fetch_one(&mut tx)
+        .await?;
+    if previous_person_id != Some(person_id) {
+        sqlx::query(
+            "DELETE FROM partition_contributors
+                WHERE partition_id = $1 AND person_id = $2 AND role = 'composer';",
+        )
+        .bind(id)
+        .bind(previous_person_id)
+        .execute(&mut tx)
+        .await?;
+        sqlx::query(
+            "INSERT INTO partition_contributors (partition_id, person_id, role)
+                VALUES ( $1, $2, 'composer' )
+                ON CONFLICT DO NOTHING;",
+        )
+        .bind(id)
+        .bind(person_id)
+        .execute(&mut tx)
+        .await?;
+    }
+    if previous_genre_id != Some(genre_id) {
+        sqlx::query("DELETE FROM partition_genres WHERE partition_id = $1 AND genre_id = $2;")
+            .bind(id)
+            .bind(previous_genre_id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query(
+            "INSERT INTO partition_genres (partition_id, genre_id)
+                VALUES ( $1, $2 )
+                ON CONFLICT DO NOTHING;",
+        )
+        .bind(id)
+        .bind(genre_id)
+        .execute(&mut tx)
+        .await?;
+    }
+    let after = snapshot(&mut tx, AuditEntity::Partition, id).await?;
+    record(
+        &mut tx,
+        actor,
+        AuditAction::Update,
+        AuditEntity::Partition,
         id,
+        before,
+        after,
     )
-    .fetch_one(pool)
     .await?;
-    let partition = Partition {
-        id: Some(row.id),
-        title: row.title,
-        person_id: row.person_id.unwrap(),
-        genre_id: row.genre_id.unwrap(),
-    };
-    Ok(partition)
+    tx.commit().await?;
+
+    tracing::info!("db : partition updated : {:?}", &partition);
+    Ok(Update::Saved(partition))
 }
 
-pub async fn delete_partition(id: i32, pool: &PgPool) -> sqlx::Result<String> {
-    let partition = find_partition_by_id(id.clone(), pool).await?;
-    let name = partition.title;
+///
+/// Moves a partition to the trash : it keeps its files, copies and loans
+/// until db::trash::purge deletes it for good
+///
+pub async fn delete_partition(id: i32, actor: &Actor, pool: &PgPool) -> sqlx::Result<String> {
+    let mut tx = pool.begin().await?;
+    let name: String = sqlx::query_scalar(
+        "SELECT title FROM partitions WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+    )
+    .bind(id)
+    .fetch_one(&mut tx)
+    .await?;
+    let before = snapshot(&mut tx, AuditEntity::Partition, id).await?;
 
-    let _res = sqlx::query("DELETE FROM partitions WHERE id = $1")
+    let _res = sqlx::query("UPDATE partitions SET deleted_at = now() WHERE id = $1")
         .bind(id)
-        .execute(pool)
+        .execute(&mut tx)
         .await?;
+    let after = snapshot(&mut tx, AuditEntity::Partition, id).await?;
+    record(
+        &mut tx,
+        actor,
+        AuditAction::Delete,
+        AuditEntity::Partition,
+        id,
+        before,
+        after,
+    )
+    .await?;
+    tx.commit().await?;
 
-    tracing::info!("db : Partition deleted : {}", &name);
+    tracing::info!("db : Partition trashed : {}", &name);
 
     Ok(name)
 }
@@ -104,31 +406,241 @@ pub async fn delete_partition(id: i32, pool: &PgPool) -> sqlx::Result<String> {
 //
 
 ///
-/// Returns a list of all partitions in the db
-/// under the form of a Vec<ShowPartition>
-/// or a sqlx Error
+/// Returns the partitions matching all the criteria of the filter
+/// (an empty filter returns every partition which is not in the trash)
+/// sorted on "title" (default), "author", "genre", "difficulty", "duration", "year" or "id"
+/// one page at a time, or all of them at once when params is None (printing)
 ///
-pub async fn list_show_partitions(pool: &PgPool) -> anyhow::Result<Vec<ShowPartition>> {
-    let rep: Vec<ShowPartition> = sqlx::query(
+pub async fn filter_partitions(
+    filter: &PartitionFilter,
+    params: Option<&ListParams>,
+    pool: &PgPool,
+) -> sqlx::Result<Page<ShowPartition>> {
+    let default_params = ListParams::default();
+    let sort_params = params.unwrap_or(&default_params);
+    let (sort, column) = match sort_params.sort.as_deref() {
+        Some("author") => ("author", "persons.full_name"),
+        Some("genre") => ("genre", "genres.name"),
+        Some("difficulty") => ("difficulty", "partitions.difficulty"),
+        Some("duration") => ("duration", "partitions.duration_seconds"),
+        Some("year") => ("year", "partitions.edition_year"),
+        Some("id") => ("id", "partitions.id"),
+        _ => ("title", "partitions.title"),
+    };
+
+    let mut query = QueryBuilder::new(format!(
         "
-    SELECT partitions.id, partitions.title, persons.full_name, genres.name
+    SELECT partitions.id, partitions.title, persons.full_name, genres.name, partitions.version, {}
     FROM partitions
     INNER JOIN persons
     ON partitions.person_id = persons.id
     INNER JOIN genres
-    ON partitions.genre_id = genres.id
-    ORDER BY partitions.title
-        ",
-    )
-    .map(|row: PgRow| ShowPartition {
-        id: row.get(0),
-        title: row.get(1),
-        full_name: row.get(2),
-        name: row.get(3),
-    })
-    .fetch_all(pool)
-    .await?;
-    Ok(rep)
+    ON partitions.genre_id = genres.id",
+        METADATA_COLUMNS
+    ));
+    push_filter(&mut query, filter);
+    query.push(format!(
+        " ORDER BY {} {} NULLS LAST, partitions.id",
+        column,
+        sort_params.dir.as_sql()
+    ));
+    if let Some(params) = params {
+        query
+            .push(" LIMIT ")
+            .push_bind(params.limit())
+            .push(" OFFSET ")
+            .push_bind(params.offset());
+    }
+    let mut partitions = query
+        .build()
+        .map(show_partition_from_row)
+        .fetch_all(pool)
+        .await?;
+    attach_details(&mut partitions, pool).await?;
+
+    let page = match params {
+        Some(params) => {
+            let mut count = QueryBuilder::new(
+                "
+    SELECT COUNT(*)
+    FROM partitions
+    INNER JOIN persons
+    ON partitions.person_id = persons.id
+    INNER JOIN genres
+    ON partitions.genre_id = genres.id",
+            );
+            push_filter(&mut count, filter);
+            let total: i64 = count.build().fetch_one(pool).await?.get(0);
+            Page::new(partitions, total, params, sort)
+        }
+        None => Page::all(partitions, sort, sort_params.dir),
+    };
+
+    tracing::info!("db : filtre {:?} : {} partition(s)", filter, page.total);
+    Ok(page)
+}
+
+///
+/// ajoute les critères du filtre à la requête, combinés par AND
+/// titre : commence par ; tonalité : égale ; textes : contient
+/// sans tenir compte des accents ni des majuscules (f_unaccent, migration 0002)
+/// le musicien est cherché parmi tous les musiciens de la partition,
+/// avec le rôle demandé s'il y en a un ; le genre parmi tous ses genres
+/// l'étiquette doit être exacte (sans tenir compte des majuscules)
+/// les partitions à la corbeille sont toujours exclues
+///
+fn push_filter(query: &mut QueryBuilder<Postgres>, filter: &PartitionFilter) {
+    query.push(" WHERE partitions.deleted_at IS NULL");
+    if let Some(title) = &filter.title {
+        query.push(" AND ");
+        query
+            .push("f_unaccent(lower(partitions.title)) LIKE f_unaccent(lower(")
+            .push_bind(format!("{}%", escape_like(title)))
+            .push("))");
+    }
+    if let Some(key) = &filter.key {
+        query.push(" AND ");
+        query
+            .push("f_unaccent(lower(partitions.music_key)) = f_unaccent(lower(")
+            .push_bind(key.clone())
+            .push("))");
+    }
+
+    if filter.author.is_some() || filter.role.is_some() {
+        query.push(" AND ");
+        query.push(
+            "EXISTS (SELECT 1 FROM partition_contributors
+            INNER JOIN persons AS contributors
+            ON partition_contributors.person_id = contributors.id
+            WHERE partition_contributors.partition_id = partitions.id",
+        );
+        if let Some(author) = &filter.author {
+            query
+                .push(" AND f_unaccent(lower(contributors.full_name)) LIKE f_unaccent(lower(")
+                .push_bind(format!("%{}%", escape_like(author)))
+                .push("))");
+        }
+        if let Some(role) = filter.role {
+            query
+                .push(" AND partition_contributors.role = ")
+                .push_bind(role.as_str());
+        }
+        query.push(")");
+    }
+
+    if let Some(genre) = &filter.genre {
+        query.push(" AND ");
+        query
+            .push(
+                "EXISTS (SELECT 1 FROM partition_genres
+            INNER JOIN genres AS all_genres
+            ON partition_genres.genre_id = all_genres.id
+            WHERE partition_genres.partition_id = partitions.id
+            AND f_unaccent(lower(all_genres.name)) LIKE f_unaccent(lower(",
+            )
+            .push_bind(format!("%{}%", escape_like(genre)))
+            .push(")))");
+    }
+    if let Some(tag) = &filter.tag {
+        query.push(" AND ");
+        query
+            .push(
+                "EXISTS (SELECT 1 FROM partition_tags
+            INNER JOIN tags
+            ON partition_tags.tag_id = tags.id
+            WHERE partition_tags.partition_id = partitions.id
+            AND lower(tags.name) = lower(",
+            )
+            .push_bind(tag.clone())
+            .push("))");
+    }
+
+    let contains = [
+        ("partitions.instrumentation", &filter.instrumentation),
+        ("partitions.tempo", &filter.tempo),
+        ("partitions.opus", &filter.opus),
+        ("partitions.publisher", &filter.publisher),
+        ("partitions.notes", &filter.notes),
+    ];
+    for (column, value) in contains {
+        if let Some(value) = value {
+            query.push(" AND ");
+            query
+                .push(format!(
+                    "f_unaccent(lower({})) LIKE f_unaccent(lower(",
+                    column
+                ))
+                .push_bind(format!("%{}%", escape_like(value)))
+                .push("))");
+        }
+    }
+
+    if let Some(difficulty) = filter.difficulty_min {
+        query.push(" AND ");
+        query
+            .push("partitions.difficulty >= ")
+            .push_bind(difficulty);
+    }
+    if let Some(difficulty) = filter.difficulty_max {
+        query.push(" AND ");
+        query
+            .push("partitions.difficulty <= ")
+            .push_bind(difficulty);
+    }
+    if let Some(duration) = filter.duration_max {
+        query.push(" AND ");
+        query
+            .push("partitions.duration_seconds <= ")
+            .push_bind(duration);
+    }
+    if let Some(year) = filter.year_from {
+        query.push(" AND ");
+        query.push("partitions.edition_year >= ").push_bind(year);
+    }
+    if let Some(year) = filter.year_to {
+        query.push(" AND ");
+        query.push("partitions.edition_year <= ").push_bind(year);
+    }
+}
+
+///
+/// complète les partitions avec la liste de leurs musiciens, genres et étiquettes
+/// (une requête par table pour toute la page)
+///
+async fn attach_details(partitions: &mut [ShowPartition], pool: &PgPool) -> sqlx::Result<()> {
+    let ids: Vec<i32> = partitions.iter().filter_map(|p| p.id).collect();
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let position = |id: i32| partitions.iter().position(|p| p.id == Some(id));
+
+    let contributors = list_contributors(&ids, pool).await?;
+    let genres = list_partition_genres(&ids, pool).await?;
+    let tags = list_partition_tags(&ids, pool).await?;
+
+    let contributors: Vec<_> = contributors
+        .into_iter()
+        .filter_map(|c| position(c.partition_id).map(|i| (i, c)))
+        .collect();
+    let genres: Vec<_> = genres
+        .into_iter()
+        .filter_map(|(id, genre)| position(id).map(|i| (i, genre)))
+        .collect();
+    let tags: Vec<_> = tags
+        .into_iter()
+        .filter_map(|(id, tag)| position(id).map(|i| (i, tag)))
+        .collect();
+
+    for (i, contributor) in contributors {
+        partitions[i].contributors.push(contributor);
+    }
+    for (i, genre) in genres {
+        partitions[i].genres.push(genre);
+    }
+    for (i, tag) in tags {
+        partitions[i].tags.push(tag);
+    }
+    Ok(())
 }
 
 ///
@@ -139,39 +651,33 @@ pub async fn show_one_partition(
     partition: Partition,
     pool: &PgPool,
 ) -> sqlx::Result<ShowPartition> {
-    let show_partition = sqlx::query(
+    let query = format!(
         "
-    SELECT partitions.id, partitions.title, persons.full_name, genres.name
+    SELECT partitions.id, partitions.title, persons.full_name, genres.name, partitions.version, {}
     FROM partitions
     INNER JOIN persons
     ON partitions.person_id = persons.id
     INNER JOIN genres
     ON partitions.genre_id = genres.id
-    WHERE partitions.title = $1
+    WHERE partitions.id = $1 AND partitions.deleted_at IS NULL
         ",
-    )
-    .bind(partition.title)
-    .map(|row: PgRow| ShowPartition {
-        id: row.get(0),
-        title: row.get(1),
-        full_name: row.get(2),
-        name: row.get(3),
-    })
-    .fetch_one(pool)
-    .await?;
+        METADATA_COLUMNS
+    );
+    let show_partition = sqlx::query(&query)
+        .bind(partition.id)
+        .map(show_partition_from_row)
+        .fetch_one(pool)
+        .await?;
+    let mut show_partitions = vec![show_partition];
+    attach_details(&mut show_partitions, pool).await?;
 
-    Ok(show_partition)
+    Ok(show_partitions.remove(0))
 }
 
 pub async fn find_partition_by_id(id: i32, pool: &PgPool) -> sqlx::Result<Partition> {
-    let partition = sqlx::query("SELECT * FROM partitions WHERE id = $1;")
+    let partition = sqlx::query("SELECT * FROM partitions WHERE id = $1 AND deleted_at IS NULL;")
         .bind(id)
-        .map(|row: PgRow| Partition {
-            id: row.get("id"),
-            title: row.get("title"),
-            person_id: row.get("person_id"),
-            genre_id: row.get("genre_id"),
-        })
+        .map(partition_from_row)
         .fetch_one(pool)
         .await?;
 
@@ -180,93 +686,75 @@ pub async fn find_partition_by_id(id: i32, pool: &PgPool) -> sqlx::Result<Partit
 }
 
 ///
-/// find_partition_by_title
-///
-/// Un titre tronqué entré retourne la liste des partitions qui commencent
-/// par les lettres entrées.
-/// Permet une recherche lorsqu'on ne connaît pas le titre exact.
+/// Returns the active partitions with the same title by the same composer
+/// as the ones given, without case, accents nor surrounding spaces
+/// ("La Pavane " by "faure" finds "la pavane" by "Fauré")
 ///
-pub async fn find_partition_by_title(title: String, pool: &PgPool) -> sqlx::Result<Vec<Partition>> {
-    let mut part_title = title.clone();
-    part_title.push('%');
-
-    let partitions = sqlx::query("SELECT * FROM partitions WHERE title LIKE $1;")
-        .bind(part_title)
-        .map(|row: PgRow| Partition {
-            id: row.get("id"),
-            title: row.get("title"),
-            person_id: row.get("person_id"),
-            genre_id: row.get("genre_id"),
-        })
-        .fetch_all(pool)
-        .await?;
-
-    Ok(partitions)
-}
-
-pub async fn find_partition_by_genre(
-    genre_name: String,
+pub async fn find_duplicate_partitions(
+    title: &str,
+    full_name: &str,
     pool: &PgPool,
-) -> sqlx::Result<Vec<Partition>> {
-    let genre = find_genre_by_name(genre_name.clone(), pool).await?;
-    let genre_id = genre[0].id.unwrap();
-
-    let partitions = sqlx::query(
-        "SELECT * FROM partitions \
-        WHERE genre_id = $1 \
-        ORDER BY partitions.title",
+) -> sqlx::Result<Vec<DuplicatePartition>> {
+    sqlx::query(
+        "SELECT partitions.id, partitions.title, persons.full_name
+                FROM partitions
+                INNER JOIN persons ON partitions.person_id = persons.id
+                WHERE partitions.deleted_at IS NULL AND persons.deleted_at IS NULL
+                AND f_normalize(persons.full_name) = f_normalize($2)
+                AND f_normalize(partitions.title) = f_normalize($1)
+                ORDER BY partitions.id;",
     )
-    .bind(genre_id)
-    .map(|row: PgRow| Partition {
+    .bind(title)
+    .bind(full_name)
+    .map(|row: PgRow| DuplicatePartition {
         id: row.get("id"),
         title: row.get("title"),
-        person_id: row.get("person_id"),
-        genre_id: row.get("genre_id"),
+        full_name: row.get("full_name"),
     })
     .fetch_all(pool)
-    .await?;
+    .await
+}
+
+//******************************************************************************************
+// Construction des structs à partir des lignes de la DB
+//
 
-    tracing::info!("db : partition(s) trouvée(s) pour genre : {}", &genre_name);
-    Ok(partitions)
+fn metadata_from_row(row: &PgRow) -> PartitionMetadata {
+    PartitionMetadata {
+        instrumentation: row.get("instrumentation"),
+        music_key: row.get("music_key"),
+        tempo: row.get("tempo"),
+        difficulty: row.get("difficulty"),
+        duration_seconds: row.get("duration_seconds"),
+        opus: row.get("opus"),
+        publisher: row.get("publisher"),
+        edition_year: row.get("edition_year"),
+        notes: row.get("notes"),
+    }
 }
 
-pub async fn find_partition_by_author(
-    author_name: String,
-    pool: &PgPool,
-) -> sqlx::Result<Vec<Partition>> {
-    let author = find_person_by_name(author_name.clone(), pool).await?;
-    let author_id = author[0].id.unwrap();
-
-    let partitions = sqlx::query(
-        "SELECT * FROM partitions \
-        WHERE person_id = $1\
-        ORDER BY partitions.title",
-    )
-    .bind(author_id)
-    .map(|row: PgRow| Partition {
+fn partition_from_row(row: PgRow) -> Partition {
+    Partition {
         id: row.get("id"),
         title: row.get("title"),
         person_id: row.get("person_id"),
         genre_id: row.get("genre_id"),
-    })
-    .fetch_all(pool)
-    .await?;
-
-    tracing::info!(
-        "db : partition(s) trouvée(s) pour auteur : {}",
-        &author_name
-    );
-    Ok(partitions)
+        version: row.get("version"),
+        metadata: metadata_from_row(&row),
+    }
 }
 
-pub async fn vec_showpartitions_from_vec_partitions(
-    partitions: Vec<Partition>,
-    pool: &PgPool,
-) -> Vec<ShowPartition> {
-    let mut show_partitions: Vec<ShowPartition> = Vec::new();
-    for partition in partitions {
-        let one_show_partition = show_one_partition(partition, pool).await.unwrap();
-        show_partitions.push(one_show_partition);
+fn show_partition_from_row(row: PgRow) -> ShowPartition {
+    ShowPartition {
+        id: row.get("id"),
+        title: row.get("title"),
+        full_name: row.get("full_name"),
+        name: row.get("name"),
+        version: row.get("version"),
+        duplicate_ok: false,
+        metadata: metadata_from_row(&row),
+        contributors: Vec::new(),
+        genres: Vec::new(),
+        tags: Vec::new(),
     }
-    show_partitions
 }
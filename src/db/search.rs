@@ -0,0 +1,191 @@
+//! src/db/search.rs
+//!
+//! Recherche unique sur les titres des partitions, les noms des musiciens et des genres
+//! ainsi que l'effectif, l'opus, l'éditeur et les notes des partitions
+//!
+//! Utilise la configuration plein texte "partitions_search" et f_unaccent
+//! créées par migrations/0002_search.sql :
+//! - insensible aux accents et à la casse ("faure" trouve "Fauré")
+//! - chaque mot cherché est un préfixe ("bach" trouve "Johann Sebastian Bach")
+//! - la phrase entière est aussi cherchée comme sous-chaîne
+//! Les résultats sont classés par pertinence : titre, puis musicien, puis genre,
+//! puis les autres renseignements
+
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+
+use crate::models::pagination::{ListParams, Page};
+use crate::models::partition::SearchPartition;
+
+// marqueurs posés par ts_headline autour des mots trouvés,
+// remplacés par <mark></mark> après l'échappement HTML
+const START_SEL: char = '\u{2}';
+const STOP_SEL: char = '\u{3}';
+
+///
+/// Searches partitions by title, musician and genre
+/// returns one page of results ordered by rank
+///
+pub async fn search_partitions(
+    search: &str,
+    params: &ListParams,
+    pool: &PgPool,
+) -> sqlx::Result<Page<SearchPartition>> {
+    let query = prefix_tsquery(search);
+    if query.is_empty() {
+        return Ok(Page::new(Vec::new(), 0, params, "rank"));
+    }
+    let pattern = format!("%{}%", escape_like(search.trim()));
+    let headline_options = format!(
+        "StartSel={}, StopSel={}, HighlightAll=true",
+        START_SEL, STOP_SEL
+    );
+
+    let rows = sqlx::query(
+        "
+    SELECT partitions.id, partitions.title, persons.full_name, genres.name,
+        (ts_rank(document, query)
+            + CASE WHEN f_unaccent(lower(partitions.title)) LIKE f_unaccent(lower($2))
+                THEN 0.5 ELSE 0 END
+            + CASE WHEN f_unaccent(lower(persons.full_name)) LIKE f_unaccent(lower($2))
+                THEN 0.3 ELSE 0 END
+            + CASE WHEN f_unaccent(lower(genres.name)) LIKE f_unaccent(lower($2))
+                THEN 0.1 ELSE 0 END
+        )::REAL AS rank,
+        ts_headline('partitions_search', partitions.title, query, $3) AS title_hl,
+        ts_headline('partitions_search', persons.full_name, query, $3) AS full_name_hl,
+        ts_headline('partitions_search', genres.name, query, $3) AS name_hl,
+        COUNT(*) OVER () AS total
+    FROM partitions
+    INNER JOIN persons
+    ON partitions.person_id = persons.id
+    INNER JOIN genres
+    ON partitions.genre_id = genres.id
+    CROSS JOIN to_tsquery('partitions_search', $1) AS query
+    CROSS JOIN LATERAL (
+        SELECT setweight(to_tsvector('partitions_search', partitions.title), 'A')
+            || setweight(to_tsvector('partitions_search', persons.full_name), 'B')
+            || setweight(to_tsvector('partitions_search', genres.name), 'C')
+            || setweight(to_tsvector('partitions_search', concat_ws(' ',
+                partitions.instrumentation, partitions.opus,
+                partitions.publisher, partitions.notes)), 'D') AS document
+    ) AS doc
+    WHERE partitions.deleted_at IS NULL
+        AND (document @@ query
+            OR f_unaccent(lower(partitions.title)) LIKE f_unaccent(lower($2))
+            OR f_unaccent(lower(persons.full_name)) LIKE f_unaccent(lower($2))
+            OR f_unaccent(lower(genres.name)) LIKE f_unaccent(lower($2)))
+    ORDER BY rank DESC, partitions.title, partitions.id
+    LIMIT $4 OFFSET $5
+        ",
+    )
+    .bind(&query)
+    .bind(&pattern)
+    .bind(&headline_options)
+    .bind(params.limit())
+    .bind(params.offset())
+    .map(|row: PgRow| {
+        let total: i64 = row.get("total");
+        let partition = SearchPartition {
+            id: row.get("id"),
+            title: row.get("title"),
+            full_name: row.get("full_name"),
+            name: row.get("name"),
+            rank: row.get("rank"),
+            title_hl: highlight(row.get("title_hl")),
+            full_name_hl: highlight(row.get("full_name_hl")),
+            name_hl: highlight(row.get("name_hl")),
+        };
+        (partition, total)
+    })
+    .fetch_all(pool)
+    .await?;
+
+    // le total est répété sur chaque ligne ; une page vide au-delà
+    // de la dernière ne le connaît pas, on le recompte alors
+    let total = match rows.first() {
+        Some((_, total)) => *total,
+        None if params.page > 1 => count_search_partitions(&query, &pattern, pool).await?,
+        None => 0,
+    };
+    let partitions = rows.into_iter().map(|(partition, _)| partition).collect();
+
+    tracing::info!("db : recherche '{}' : {} partition(s)", search, total);
+    Ok(Page::new(partitions, total, params, "rank"))
+}
+
+async fn count_search_partitions(query: &str, pattern: &str, pool: &PgPool) -> sqlx::Result<i64> {
+    sqlx::query_scalar(
+        "
+    SELECT COUNT(*)
+    FROM partitions
+    INNER JOIN persons
+    ON partitions.person_id = persons.id
+    INNER JOIN genres
+    ON partitions.genre_id = genres.id
+    CROSS JOIN to_tsquery('partitions_search', $1) AS query
+    WHERE partitions.deleted_at IS NULL
+        AND ((setweight(to_tsvector('partitions_search', partitions.title), 'A')
+            || setweight(to_tsvector('partitions_search', persons.full_name), 'B')
+            || setweight(to_tsvector('partitions_search', genres.name), 'C')
+            || setweight(to_tsvector('partitions_search', concat_ws(' ',
+                partitions.instrumentation, partitions.opus,
+                partitions.publisher, partitions.notes)), 'D')) @@ query
+            OR f_unaccent(lower(partitions.title)) LIKE f_unaccent(lower($2))
+            OR f_unaccent(lower(persons.full_name)) LIKE f_unaccent(lower($2))
+            OR f_unaccent(lower(genres.name)) LIKE f_unaccent(lower($2)))
+        ",
+    )
+    .bind(query)
+    .bind(pattern)
+    .fetch_one(pool)
+    .await
+}
+
+///
+/// "Bach, prél" -> "Bach:* & prél:*"
+/// seuls les lettres et les chiffres sont gardés :
+/// la syntaxe de to_tsquery ne peut pas venir de l'utilisateur
+///
+fn prefix_tsquery(search: &str) -> String {
+    search
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("{}:*", word))
+        .collect::<Vec<_>>()
+        .join(" & ")
+}
+
+///
+/// échappe les jokers de LIKE (\ est le caractère d'échappement par défaut)
+///
+pub(crate) fn escape_like(search: &str) -> String {
+    let mut escaped = String::with_capacity(search.len());
+    for c in search.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+///
+/// échappe le texte pour le HTML puis remplace les marqueurs de ts_headline
+///
+fn highlight(headline: String) -> String {
+    let mut html = String::with_capacity(headline.len());
+    for c in headline.chars() {
+        match c {
+            START_SEL => html.push_str("<mark>"),
+            STOP_SEL => html.push_str("</mark>"),
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+    html
+}
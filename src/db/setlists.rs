@@ -0,0 +1,354 @@
+//! src/db/setlists.rs
+//!
+//! Programmes de concert (tables setlists et setlist_items)
+//! les pièces sont numérotées sans trou à partir de 1
+//! (la purge d'une partition renumérote les programmes qui la contenaient)
+//! les pièces dont la partition est à la corbeille ne sont ni montrées ni comptées :
+//! elles reviennent avec la partition
+
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Postgres, Row, Transaction};
+
+use crate::models::pagination::{ListParams, Page};
+use crate::models::setlist::{MoveDirection, Setlist, SetlistItem, SetlistSummary};
+
+//*******************************************************************************************
+// CRUD Operations on setlists
+//
+
+pub async fn add_setlist(setlist: Setlist, pool: &PgPool) -> sqlx::Result<Setlist> {
+    let setlist = sqlx::query(
+        "INSERT INTO setlists (name, concert_date, venue, notes)
+                VALUES ( $1, $2, $3, $4 )
+                RETURNING id, name, concert_date, venue, notes;",
+    )
+    .bind(&setlist.name)
+    .bind(setlist.concert_date)
+    .bind(&setlist.venue)
+    .bind(&setlist.notes)
+    .map(setlist_from_row)
+    .fetch_one(pool)
+    .await?;
+
+    tracing::info!("db : setlist added : {:?}", &setlist);
+    Ok(setlist)
+}
+
+pub async fn update_setlist(id: i32, setlist: Setlist, pool: &PgPool) -> sqlx::Result<Setlist> {
+    let setlist = sqlx::query(
+        "UPDATE setlists SET name = $1, concert_date = $2, venue = $3, notes = $4
+                WHERE id = $5
+                RETURNING id, name, concert_date, venue, notes;",
+    )
+    .bind(&setlist.name)
+    .bind(setlist.concert_date)
+    .bind(&setlist.venue)
+    .bind(&setlist.notes)
+    .bind(id)
+    .map(setlist_from_row)
+    .fetch_one(pool)
+    .await?;
+
+    tracing::info!("db : setlist updated : {:?}", &setlist);
+    Ok(setlist)
+}
+
+pub async fn delete_setlist(id: i32, pool: &PgPool) -> sqlx::Result<String> {
+    let name: String = sqlx::query_scalar("DELETE FROM setlists WHERE id = $1 RETURNING name;")
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+
+    tracing::info!("db : setlist deleted : {}", &name);
+    Ok(name)
+}
+
+pub async fn find_setlist_by_id(id: i32, pool: &PgPool) -> sqlx::Result<Setlist> {
+    sqlx::query("SELECT id, name, concert_date, venue, notes FROM setlists WHERE id = $1;")
+        .bind(id)
+        .map(setlist_from_row)
+        .fetch_one(pool)
+        .await
+}
+
+///
+/// Returns one page of setlists with their number of pieces and total duration
+/// (without the pieces whose partition is in the trash)
+/// sorted on "date" (default, date of the concert), "name" or "id"
+///
+pub async fn list_setlists_page(
+    pool: &PgPool,
+    params: &ListParams,
+) -> sqlx::Result<Page<SetlistSummary>> {
+    let (sort, column) = match params.sort.as_deref() {
+        Some("name") => ("name", "setlists.name"),
+        Some("id") => ("id", "setlists.id"),
+        _ => ("date", "setlists.concert_date"),
+    };
+    let query = format!(
+        "
+    SELECT setlists.id, setlists.name, setlists.concert_date, setlists.venue, setlists.notes,
+        COUNT(partitions.id) AS item_count,
+        COALESCE(SUM(partitions.duration_seconds), 0)::BIGINT AS total_seconds,
+        COUNT(partitions.id) - COUNT(partitions.duration_seconds) AS unknown_durations
+    FROM setlists
+    LEFT JOIN setlist_items
+    ON setlist_items.setlist_id = setlists.id
+    LEFT JOIN partitions
+    ON setlist_items.partition_id = partitions.id AND partitions.deleted_at IS NULL
+    GROUP BY setlists.id
+    ORDER BY {} {} NULLS LAST, setlists.id
+    LIMIT $1 OFFSET $2
+        ",
+        column,
+        params.dir.as_sql()
+    );
+    let setlists = sqlx::query(&query)
+        .bind(params.limit())
+        .bind(params.offset())
+        .map(|row: PgRow| SetlistSummary {
+            item_count: row.get("item_count"),
+            total_seconds: row.get("total_seconds"),
+            unknown_durations: row.get("unknown_durations"),
+            setlist: setlist_from_row(row),
+        })
+        .fetch_all(pool)
+        .await?;
+
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM setlists;")
+        .fetch_one(pool)
+        .await?;
+
+    Ok(Page::new(setlists, total, params, sort))
+}
+
+//*******************************************************************************************
+// Pièces des programmes
+//
+
+///
+/// Returns the pieces of a setlist in the order of the concert
+/// (without the pieces whose partition is in the trash)
+///
+pub async fn list_setlist_items(setlist_id: i32, pool: &PgPool) -> sqlx::Result<Vec<SetlistItem>> {
+    sqlx::query(
+        "
+    SELECT setlist_items.id, setlist_items.setlist_id, setlist_items.position,
+        setlist_items.partition_id, partitions.title, persons.full_name,
+        partitions.instrumentation, partitions.music_key, partitions.duration_seconds,
+        setlist_items.notes
+    FROM setlist_items
+    INNER JOIN partitions
+    ON setlist_items.partition_id = partitions.id
+    INNER JOIN persons
+    ON partitions.person_id = persons.id
+    WHERE setlist_items.setlist_id = $1
+    AND partitions.deleted_at IS NULL
+    ORDER BY setlist_items.position
+        ",
+    )
+    .bind(setlist_id)
+    .map(|row: PgRow| SetlistItem {
+        id: row.get("id"),
+        setlist_id: row.get("setlist_id"),
+        position: row.get("position"),
+        partition_id: row.get("partition_id"),
+        title: row.get("title"),
+        full_name: row.get("full_name"),
+        instrumentation: row.get("instrumentation"),
+        music_key: row.get("music_key"),
+        duration_seconds: row.get("duration_seconds"),
+        notes: row.get("notes"),
+    })
+    .fetch_all(pool)
+    .await
+}
+
+///
+/// Adds a partition at the end of a setlist
+/// (the same partition can be played twice, e.g. as an encore)
+///
+pub async fn add_setlist_item(
+    setlist_id: i32,
+    partition_id: i32,
+    notes: Option<String>,
+    pool: &PgPool,
+) -> sqlx::Result<i32> {
+    let mut tx = pool.begin().await?;
+    // verrouille le programme : deux ajouts simultanés auraient la même position
+    sqlx::query("SELECT id FROM setlists WHERE id = $1 FOR UPDATE;")
+        .bind(setlist_id)
+        .fetch_one(&mut tx)
+        .await?;
+    let id: i32 = sqlx::query_scalar(
+        "INSERT INTO setlist_items (setlist_id, partition_id, position, notes)
+                VALUES ( $1, $2,
+                    (SELECT COALESCE(MAX(position), 0) + 1 FROM setlist_items WHERE setlist_id = $1),
+                    $3 )
+                RETURNING id;",
+    )
+    .bind(setlist_id)
+    .bind(partition_id)
+    .bind(&notes)
+    .fetch_one(&mut tx)
+    .await?;
+    tx.commit().await?;
+
+    tracing::info!(
+        "db : setlist {} : partition {} added",
+        setlist_id,
+        partition_id
+    );
+    Ok(id)
+}
+
+pub async fn update_setlist_item_notes(
+    item_id: i32,
+    notes: Option<String>,
+    pool: &PgPool,
+) -> sqlx::Result<i32> {
+    sqlx::query_scalar("UPDATE setlist_items SET notes = $1 WHERE id = $2 RETURNING setlist_id;")
+        .bind(&notes)
+        .bind(item_id)
+        .fetch_one(pool)
+        .await
+}
+
+///
+/// Removes a piece from its setlist and renumbers the following ones
+/// returns the id of the setlist
+///
+pub async fn delete_setlist_item(item_id: i32, pool: &PgPool) -> sqlx::Result<i32> {
+    let mut tx = pool.begin().await?;
+    let (setlist_id, position): (i32, i32) =
+        sqlx::query_as("DELETE FROM setlist_items WHERE id = $1 RETURNING setlist_id, position;")
+            .bind(item_id)
+            .fetch_one(&mut tx)
+            .await?;
+    sqlx::query(
+        "UPDATE setlist_items SET position = position - 1
+                WHERE setlist_id = $1 AND position > $2;",
+    )
+    .bind(setlist_id)
+    .bind(position)
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+
+    tracing::info!("db : setlist {} : item {} deleted", setlist_id, item_id);
+    Ok(setlist_id)
+}
+
+///
+/// Moves a piece one place up or down by swapping it with its neighbour,
+/// the nearest piece shown before or after it (see list_setlist_items)
+/// nothing changes for the first piece moved up or the last one moved down
+/// returns the id of the setlist
+///
+pub async fn move_setlist_item(
+    item_id: i32,
+    direction: MoveDirection,
+    pool: &PgPool,
+) -> sqlx::Result<i32> {
+    let mut tx = pool.begin().await?;
+    let (setlist_id, position): (i32, i32) =
+        sqlx::query_as("SELECT setlist_id, position FROM setlist_items WHERE id = $1 FOR UPDATE;")
+            .bind(item_id)
+            .fetch_one(&mut tx)
+            .await?;
+    // la pièce montrée juste avant ou juste après
+    let (op, order) = match direction {
+        MoveDirection::Up => ("<", "DESC"),
+        MoveDirection::Down => (">", "ASC"),
+    };
+    let query = format!(
+        "SELECT setlist_items.id, setlist_items.position
+                FROM setlist_items
+                INNER JOIN partitions
+                ON setlist_items.partition_id = partitions.id
+                WHERE setlist_items.setlist_id = $1
+                AND setlist_items.position {} $2
+                AND partitions.deleted_at IS NULL
+                ORDER BY setlist_items.position {}
+                LIMIT 1
+                FOR UPDATE OF setlist_items;",
+        op, order
+    );
+    let neighbour: Option<(i32, i32)> = sqlx::query_as(&query)
+        .bind(setlist_id)
+        .bind(position)
+        .fetch_optional(&mut tx)
+        .await?;
+    // la contrainte (setlist_id, position) est vérifiée au COMMIT : l'échange est possible
+    if let Some((neighbour_id, target)) = neighbour {
+        sqlx::query("UPDATE setlist_items SET position = $1 WHERE id = $2;")
+            .bind(position)
+            .bind(neighbour_id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("UPDATE setlist_items SET position = $1 WHERE id = $2;")
+            .bind(target)
+            .bind(item_id)
+            .execute(&mut tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    Ok(setlist_id)
+}
+
+///
+/// Locks the setlists where a partition is played, before it is purged
+/// returns their ids, for renumber_setlists
+///
+pub(crate) async fn lock_partition_setlists(
+    tx: &mut Transaction<'_, Postgres>,
+    partition_id: i32,
+) -> sqlx::Result<Vec<i32>> {
+    sqlx::query_scalar(
+        "SELECT id FROM setlists
+                WHERE id IN (SELECT setlist_id FROM setlist_items WHERE partition_id = $1)
+                ORDER BY id
+                FOR UPDATE;",
+    )
+    .bind(partition_id)
+    .fetch_all(&mut *tx)
+    .await
+}
+
+///
+/// Numbers again the pieces of the setlists from 1, without gaps,
+/// once the pieces of a purged partition are gone with it (ON DELETE CASCADE)
+///
+pub(crate) async fn renumber_setlists(
+    tx: &mut Transaction<'_, Postgres>,
+    setlist_ids: &[i32],
+) -> sqlx::Result<()> {
+    sqlx::query(
+        "UPDATE setlist_items SET position = numbered.position
+                FROM (SELECT id,
+                        ROW_NUMBER() OVER (PARTITION BY setlist_id ORDER BY position)::INTEGER
+                            AS position
+                    FROM setlist_items WHERE setlist_id = ANY($1)) AS numbered
+                WHERE setlist_items.id = numbered.id
+                AND setlist_items.position <> numbered.position;",
+    )
+    .bind(setlist_ids)
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
+//******************************************************************************************
+// Construction des structs à partir des lignes de la DB
+//
+
+fn setlist_from_row(row: PgRow) -> Setlist {
+    Setlist {
+        id: row.get("id"),
+        name: row.get("name"),
+        concert_date: row.get("concert_date"),
+        venue: row.get("venue"),
+        notes: row.get("notes"),
+    }
+}
@@ -0,0 +1,364 @@
+//! src/db/tags.rs
+//!
+//! Étiquettes libres des partitions (tables tags et partition_tags)
+//! les noms ne tiennent pas compte des majuscules : "Noël" = "noël"
+
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Postgres, Row, Transaction};
+
+use crate::db::audit::{record, record_partition_change, snapshot};
+use crate::models::audit::{Actor, AuditAction, AuditEntity};
+use crate::models::pagination::{ListParams, Page};
+use crate::models::tag::{Tag, TagCount};
+
+//*******************************************************************************************
+// CRUD Operations on tags
+//
+
+pub async fn add_tag(name: &str, actor: &Actor, pool: &PgPool) -> sqlx::Result<Tag> {
+    let mut tx = pool.begin().await?;
+    let tag = sqlx::query("INSERT INTO tags (name) VALUES ( $1 ) RETURNING id, name;")
+        .bind(name)
+        .map(|row: PgRow| Tag {
+            id: row.get(0),
+            name: row.get(1),
+        })
+        .fetch_one(&mut tx)
+        .await?;
+    let id = tag.id.unwrap_or_default();
+    let after = snapshot(&mut tx, AuditEntity::Tag, id).await?;
+    record(
+        &mut tx,
+        actor,
+        AuditAction::Create,
+        AuditEntity::Tag,
+        id,
+        None,
+        after,
+    )
+    .await?;
+    tx.commit().await?;
+
+    tracing::info!("db : tag added : {:?}", &tag);
+    Ok(tag)
+}
+
+///
+/// Returns the tag with this name, created when it does not exist yet
+/// (the existing tag keeps its spelling)
+///
+pub async fn find_or_add_tag(name: &str, actor: &Actor, pool: &PgPool) -> sqlx::Result<Tag> {
+    let mut tx = pool.begin().await?;
+    let tag = find_or_insert_tag(&mut tx, name, actor).await?;
+    tx.commit().await?;
+
+    Ok(tag)
+}
+
+///
+/// find_or_add_tag inside a transaction opened by the caller
+/// (shared by find_or_add_tag and the CSV import)
+///
+pub(crate) async fn find_or_insert_tag(
+    tx: &mut Transaction<'_, Postgres>,
+    name: &str,
+    actor: &Actor,
+) -> sqlx::Result<Tag> {
+    // xmax = 0 : la ligne vient d'être créée, elle n'existait pas déjà
+    let (tag, created) = sqlx::query(
+        "INSERT INTO tags (name) VALUES ( $1 )
+                ON CONFLICT (lower(name)) DO UPDATE SET name = tags.name
+                RETURNING id, name, xmax = 0 AS created;",
+    )
+    .bind(name)
+    .map(|row: PgRow| {
+        let tag = Tag {
+            id: row.get(0),
+            name: row.get(1),
+        };
+        (tag, row.get::<bool, _>("created"))
+    })
+    .fetch_one(&mut *tx)
+    .await?;
+    if created {
+        let id = tag.id.unwrap_or_default();
+        let after = snapshot(&mut *tx, AuditEntity::Tag, id).await?;
+        record(
+            &mut *tx,
+            actor,
+            AuditAction::Create,
+            AuditEntity::Tag,
+            id,
+            None,
+            after,
+        )
+        .await?;
+    }
+
+    Ok(tag)
+}
+
+pub async fn update_tag(id: i32, name: &str, actor: &Actor, pool: &PgPool) -> sqlx::Result<Tag> {
+    let mut tx = pool.begin().await?;
+    let before = snapshot(&mut tx, AuditEntity::Tag, id).await?;
+    let tag = sqlx::query("UPDATE tags SET name = $1 WHERE id = $2 RETURNING id, name;")
+        .bind(name)
+        .bind(id)
+        .map(|row: PgRow| Tag {
+            id: row.get(0),
+            name: row.get(1),
+        })
+        .fetch_one(&mut tx)
+        .await?;
+    let after = snapshot(&mut tx, AuditEntity::Tag, id).await?;
+    record(
+        &mut tx,
+        actor,
+        AuditAction::Update,
+        AuditEntity::Tag,
+        id,
+        before,
+        after,
+    )
+    .await?;
+    tx.commit().await?;
+
+    tracing::info!("db : tag updated : {:?}", &tag);
+    Ok(tag)
+}
+
+///
+/// Deletes a tag, it is removed from all the partitions
+/// the journal gets the deletion of the tag and a change of each of its partitions
+///
+pub async fn delete_tag(id: i32, actor: &Actor, pool: &PgPool) -> sqlx::Result<String> {
+    let mut tx = pool.begin().await?;
+    let before = snapshot(&mut tx, AuditEntity::Tag, id).await?;
+    let partition_ids: Vec<i32> = sqlx::query_scalar(
+        "SELECT partition_id FROM partition_tags WHERE tag_id = $1 ORDER BY partition_id;",
+    )
+    .bind(id)
+    .fetch_all(&mut tx)
+    .await?;
+    let mut partitions = Vec::with_capacity(partition_ids.len());
+    for partition_id in partition_ids {
+        let before = snapshot(&mut tx, AuditEntity::Partition, partition_id).await?;
+        partitions.push((partition_id, before));
+    }
+
+    let name: String = sqlx::query_scalar("DELETE FROM tags WHERE id = $1 RETURNING name;")
+        .bind(id)
+        .fetch_one(&mut tx)
+        .await?;
+    record(
+        &mut tx,
+        actor,
+        AuditAction::Delete,
+        AuditEntity::Tag,
+        id,
+        before,
+        None,
+    )
+    .await?;
+    for (partition_id, before) in partitions {
+        record_partition_change(&mut tx, actor, partition_id, before).await?;
+    }
+    tx.commit().await?;
+
+    tracing::info!("db : tag deleted : {}", &name);
+    Ok(name)
+}
+
+//*******************************************************************************************
+// Étiquettes des partitions
+//
+
+///
+/// Puts a tag on a partition
+/// returns false when the partition already had it
+/// the change is recorded in the journal of the partition
+///
+pub async fn add_partition_tag(
+    partition_id: i32,
+    tag_id: i32,
+    actor: &Actor,
+    pool: &PgPool,
+) -> sqlx::Result<bool> {
+    let mut tx = pool.begin().await?;
+    let before = snapshot(&mut tx, AuditEntity::Partition, partition_id).await?;
+    let result = sqlx::query(
+        "INSERT INTO partition_tags (partition_id, tag_id)
+                VALUES ( $1, $2 )
+                ON CONFLICT DO NOTHING;",
+    )
+    .bind(partition_id)
+    .bind(tag_id)
+    .execute(&mut tx)
+    .await?;
+    let added = result.rows_affected() > 0;
+    if added {
+        record_partition_change(&mut tx, actor, partition_id, before).await?;
+    }
+    tx.commit().await?;
+
+    Ok(added)
+}
+
+pub async fn delete_partition_tag(
+    partition_id: i32,
+    tag_id: i32,
+    actor: &Actor,
+    pool: &PgPool,
+) -> sqlx::Result<bool> {
+    let mut tx = pool.begin().await?;
+    let before = snapshot(&mut tx, AuditEntity::Partition, partition_id).await?;
+    let result = sqlx::query("DELETE FROM partition_tags WHERE partition_id = $1 AND tag_id = $2;")
+        .bind(partition_id)
+        .bind(tag_id)
+        .execute(&mut tx)
+        .await?;
+    let deleted = result.rows_affected() > 0;
+    if deleted {
+        record_partition_change(&mut tx, actor, partition_id, before).await?;
+    }
+    tx.commit().await?;
+
+    Ok(deleted)
+}
+
+///
+/// Returns the tags of several partitions at once
+/// as (partition id, tag) ordered by name
+///
+pub async fn list_partition_tags(
+    partition_ids: &[i32],
+    pool: &PgPool,
+) -> sqlx::Result<Vec<(i32, Tag)>> {
+    sqlx::query(
+        "
+    SELECT partition_tags.partition_id, tags.id, tags.name
+    FROM partition_tags
+    INNER JOIN tags
+    ON partition_tags.tag_id = tags.id
+    WHERE partition_tags.partition_id = ANY($1)
+    ORDER BY lower(tags.name)
+        ",
+    )
+    .bind(partition_ids)
+    .map(|row: PgRow| {
+        let tag = Tag {
+            id: row.get("id"),
+            name: row.get("name"),
+        };
+        (row.get("partition_id"), tag)
+    })
+    .fetch_all(pool)
+    .await
+}
+
+//*******************************************************************************************
+// Listes des étiquettes
+//
+
+///
+/// Returns all the tags ordered by name (for the lists of choices)
+///
+pub async fn list_tags(pool: &PgPool) -> sqlx::Result<Vec<Tag>> {
+    sqlx::query("SELECT id, name FROM tags ORDER BY lower(name);")
+        .map(|row: PgRow| Tag {
+            id: row.get(0),
+            name: row.get(1),
+        })
+        .fetch_all(pool)
+        .await
+}
+
+///
+/// Returns one page of tags with the number of partitions of each one
+/// sorted on "name" (default), "count" or "id"
+///
+pub async fn list_tags_page(pool: &PgPool, params: &ListParams) -> sqlx::Result<Page<TagCount>> {
+    let (sort, column) = match params.sort.as_deref() {
+        Some("count") => ("count", "count"),
+        Some("id") => ("id", "tags.id"),
+        _ => ("name", "lower(tags.name)"),
+    };
+    let query = format!(
+        "
+    SELECT tags.id, tags.name, COUNT(partitions.id) AS count
+    FROM tags
+    LEFT JOIN partition_tags
+    ON partition_tags.tag_id = tags.id
+    LEFT JOIN partitions
+    ON partition_tags.partition_id = partitions.id AND partitions.deleted_at IS NULL
+    GROUP BY tags.id, tags.name
+    ORDER BY {} {}, tags.id
+    LIMIT $1 OFFSET $2
+        ",
+        column,
+        params.dir.as_sql()
+    );
+    let counts: Vec<(i32, String, i64)> = sqlx::query(&query)
+        .bind(params.limit())
+        .bind(params.offset())
+        .map(|row: PgRow| (row.get("id"), row.get("name"), row.get("count")))
+        .fetch_all(pool)
+        .await?;
+
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM tags;")
+        .fetch_one(pool)
+        .await?;
+
+    Ok(Page::new(with_weights(counts), total, params, sort))
+}
+
+///
+/// Returns the tags used by at least one partition, ordered by name,
+/// with their weight in the tag cloud
+///
+pub async fn tag_cloud(pool: &PgPool) -> sqlx::Result<Vec<TagCount>> {
+    let counts: Vec<(i32, String, i64)> = sqlx::query(
+        "
+    SELECT tags.id, tags.name, COUNT(*) AS count
+    FROM tags
+    INNER JOIN partition_tags
+    ON partition_tags.tag_id = tags.id
+    INNER JOIN partitions
+    ON partition_tags.partition_id = partitions.id AND partitions.deleted_at IS NULL
+    GROUP BY tags.id, tags.name
+    ORDER BY lower(tags.name)
+        ",
+    )
+    .map(|row: PgRow| (row.get("id"), row.get("name"), row.get("count")))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(with_weights(counts))
+}
+
+///
+/// poids de 1 à 5, proportionnel au logarithme du nombre de partitions :
+/// une étiquette très utilisée n'écrase pas les autres
+///
+fn with_weights(counts: Vec<(i32, String, i64)>) -> Vec<TagCount> {
+    let max = counts.iter().map(|(_, _, count)| *count).max().unwrap_or(0);
+    let max_log = ((max.max(1)) as f64).ln();
+    counts
+        .into_iter()
+        .map(|(id, name, count)| {
+            let weight = if count == 0 {
+                1
+            } else if max_log == 0.0 {
+                3
+            } else {
+                1 + ((count as f64).ln() / max_log * 4.0).round() as u8
+            };
+            TagCount {
+                id,
+                name,
+                count,
+                weight,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,248 @@
+//! src/db/trash.rs
+//!
+//! Corbeille des partitions, des musiciens et des genres (colonne deleted_at, migration 0010)
+//! delete_partition, delete_person et delete_genre mettent la ligne à la corbeille ;
+//! elle y est restaurée ou purgée (effacée pour de bon) ici, avec sa ligne au journal
+
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Postgres, Row, Transaction};
+
+use crate::db::audit::{record, snapshot};
+use crate::db::setlists::{lock_partition_setlists, renumber_setlists};
+use crate::models::audit::{Actor, AuditAction};
+use crate::models::deletion::Deletion;
+use crate::models::trash::{Restoration, TrashKind, TrashedItem};
+
+///
+/// Returns the trashed rows of a table, the most recently trashed first
+/// retention_days : the delay before the automatic purge (None : no purge)
+///
+pub async fn list_trash(
+    kind: TrashKind,
+    retention_days: Option<i32>,
+    pool: &PgPool,
+) -> sqlx::Result<Vec<TrashedItem>> {
+    let query = format!(
+        "SELECT id, {name} AS name, deleted_at,
+            deleted_at + make_interval(days => $1) AS purge_at,
+            {references} AS refs
+        FROM {table}
+        WHERE deleted_at IS NOT NULL
+        ORDER BY deleted_at DESC, id;",
+        name = kind.name_column(),
+        references = references(kind),
+        table = kind.table(),
+    );
+    sqlx::query(&query)
+        .bind(retention_days)
+        .map(|row: PgRow| TrashedItem {
+            id: row.get("id"),
+            name: row.get("name"),
+            deleted_at: row.get("deleted_at"),
+            purge_at: row.get("purge_at"),
+            references: row.get("refs"),
+        })
+        .fetch_all(pool)
+        .await
+}
+
+///
+/// Takes a row out of the trash in one transaction
+/// a partition comes back with its musicians and genres if they were trashed too
+/// RowNotFound when the row is not in the trash ;
+/// a unique violation when the name has been given to another row meanwhile
+///
+pub async fn restore(
+    kind: TrashKind,
+    id: i32,
+    actor: &Actor,
+    pool: &PgPool,
+) -> sqlx::Result<Restoration> {
+    let mut tx = pool.begin().await?;
+    let name = restore_row(&mut tx, kind, id, actor).await?;
+
+    let mut with = Vec::new();
+    if kind == TrashKind::Partitions {
+        let persons: Vec<i32> = sqlx::query_scalar(
+            "SELECT id FROM persons
+                WHERE deleted_at IS NOT NULL
+                AND (id = (SELECT person_id FROM partitions WHERE id = $1)
+                    OR id IN (SELECT person_id FROM partition_contributors WHERE partition_id = $1))
+                ORDER BY id;",
+        )
+        .bind(id)
+        .fetch_all(&mut tx)
+        .await?;
+        for person_id in persons {
+            with.push(restore_row(&mut tx, TrashKind::Persons, person_id, actor).await?);
+        }
+        let genres: Vec<i32> = sqlx::query_scalar(
+            "SELECT id FROM genres
+                WHERE deleted_at IS NOT NULL
+                AND (id = (SELECT genre_id FROM partitions WHERE id = $1)
+                    OR id IN (SELECT genre_id FROM partition_genres WHERE partition_id = $1))
+                ORDER BY id;",
+        )
+        .bind(id)
+        .fetch_all(&mut tx)
+        .await?;
+        for genre_id in genres {
+            with.push(restore_row(&mut tx, TrashKind::Genres, genre_id, actor).await?);
+        }
+    }
+    tx.commit().await?;
+
+    tracing::info!("db : {} restored : {} {:?}", kind, &name, &with);
+    Ok(Restoration { name, with })
+}
+
+///
+/// Deletes a trashed row for good
+/// a musician or a genre still used by trashed partitions is kept
+/// (Deletion::Refused) until they are purged themselves
+/// a partition leaves the setlists where it was played, which are renumbered
+/// the files of a partition are removed by the caller (crate::trash)
+///
+pub async fn purge(
+    kind: TrashKind,
+    id: i32,
+    actor: &Actor,
+    pool: &PgPool,
+) -> sqlx::Result<Deletion> {
+    let mut tx = pool.begin().await?;
+    let query = format!(
+        "SELECT {} FROM {} WHERE id = $1 AND deleted_at IS NOT NULL FOR UPDATE;",
+        kind.name_column(),
+        kind.table()
+    );
+    let name: String = sqlx::query_scalar(&query)
+        .bind(id)
+        .fetch_one(&mut tx)
+        .await?;
+    let query = format!(
+        "SELECT {} FROM {} WHERE id = $1;",
+        references(kind),
+        kind.table()
+    );
+    let dependents: i64 = sqlx::query_scalar(&query)
+        .bind(id)
+        .fetch_one(&mut tx)
+        .await?;
+    if dependents > 0 {
+        tx.rollback().await?;
+        tracing::info!(
+            "db : {} not purged : {} ({} partitions)",
+            kind,
+            &name,
+            dependents
+        );
+        return Ok(Deletion::Refused { name, dependents });
+    }
+
+    let setlist_ids = match kind {
+        TrashKind::Partitions => lock_partition_setlists(&mut tx, id).await?,
+        _ => Vec::new(),
+    };
+    let before = snapshot(&mut tx, kind.entity(), id).await?;
+    sqlx::query(&format!("DELETE FROM {} WHERE id = $1;", kind.table()))
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+    renumber_setlists(&mut tx, &setlist_ids).await?;
+    record(
+        &mut tx,
+        actor,
+        AuditAction::Delete,
+        kind.entity(),
+        id,
+        before,
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    tracing::info!("db : {} purged : {}", kind, &name);
+    Ok(Deletion::Deleted {
+        name,
+        reassigned: 0,
+    })
+}
+
+///
+/// Returns the ids of the rows trashed more than retention_days ago,
+/// the oldest first
+///
+pub async fn list_expired(
+    kind: TrashKind,
+    retention_days: i32,
+    pool: &PgPool,
+) -> sqlx::Result<Vec<i32>> {
+    let query = format!(
+        "SELECT id FROM {}
+        WHERE deleted_at < now() - make_interval(days => $1)
+        ORDER BY deleted_at, id;",
+        kind.table()
+    );
+    sqlx::query_scalar(&query)
+        .bind(retention_days)
+        .fetch_all(pool)
+        .await
+}
+
+///
+/// remet deleted_at à NULL, avec sa ligne au journal (une modification)
+/// renvoie le titre ou le nom de la ligne
+///
+async fn restore_row(
+    tx: &mut Transaction<'_, Postgres>,
+    kind: TrashKind,
+    id: i32,
+    actor: &Actor,
+) -> sqlx::Result<String> {
+    let before = snapshot(&mut *tx, kind.entity(), id).await?;
+    let query = format!(
+        "UPDATE {} SET deleted_at = NULL
+        WHERE id = $1 AND deleted_at IS NOT NULL
+        RETURNING {};",
+        kind.table(),
+        kind.name_column()
+    );
+    let name: String = sqlx::query_scalar(&query)
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+    let after = snapshot(&mut *tx, kind.entity(), id).await?;
+    record(
+        &mut *tx,
+        actor,
+        AuditAction::Update,
+        kind.entity(),
+        id,
+        before,
+        after,
+    )
+    .await?;
+    Ok(name)
+}
+
+///
+/// le nombre de partitions, à la corbeille ou non, qui utilisent la ligne
+/// (musicien principal ou contributeur, genre principal ou autre genre)
+///
+fn references(kind: TrashKind) -> &'static str {
+    match kind {
+        TrashKind::Partitions => "0::BIGINT",
+        TrashKind::Persons => {
+            "(SELECT COUNT(*) FROM partitions
+                WHERE partitions.person_id = persons.id
+                OR EXISTS (SELECT 1 FROM partition_contributors
+                    WHERE partition_id = partitions.id AND person_id = persons.id))"
+        }
+        TrashKind::Genres => {
+            "(SELECT COUNT(*) FROM partitions
+                WHERE partitions.genre_id = genres.id
+                OR EXISTS (SELECT 1 FROM partition_genres
+                    WHERE partition_id = partitions.id AND genre_id = genres.id))"
+        }
+    }
+}
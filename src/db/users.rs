@@ -1,10 +1,11 @@
 //! src/db/users.rs
 
-use sha3::Digest;
 use sqlx::postgres::PgRow;
 use sqlx::{PgPool, Row, };
 use uuid::Uuid;
 
+use crate::db::audit::{record, snapshot};
+use crate::models::audit::{Actor, AuditAction, AuditEntity};
 use crate::models::user::{NewUser, User};
 
 //******************************************************************************************
@@ -29,34 +30,6 @@ pub async fn find_user_by_name(name: String, pool: &PgPool) -> sqlx::Result<User
     Ok(user)
 }
 #[allow(dead_code)]
-pub async fn find_user_by_credentials(
-    user_name: String,
-    user_password: String,
-    pool: &PgPool,
-) -> sqlx::Result<User> {
-    let password_hash = sha3::Sha3_256::digest(user_password.as_bytes());
-    let password_hash = format!("{:x}", password_hash);
-
-    let row = sqlx::query!(
-        r#"
-    SELECT * FROM users
-    WHERE name = $1 AND password_hash = $2
-        "#,
-        user_name,
-        password_hash,
-    )
-    .fetch_one(pool)
-    .await?;
-
-    let user = User {
-        id: row.id,
-        name: row.name,
-        password_hash: row.password_hash,
-        role: row.role.unwrap(),
-    };
-    Ok(user)
-}
-#[allow(dead_code)]
 pub async fn find_user_by_id(id: Uuid, pool: &PgPool) -> sqlx::Result<User> {
     let row = sqlx::query!(
         r#"
@@ -83,6 +56,7 @@ CRUD FUNCTIONS
 ///
 /// Adds a user to the users table
 /// a hashed password must be set before entering the function
+/// the new user is recorded in the journal as the author of its own sign up
 ///
 #[allow(dead_code)]
 pub async fn add_user(new_user: &NewUser, pool: &PgPool) -> sqlx::Result<User> {
@@ -91,6 +65,7 @@ pub async fn add_user(new_user: &NewUser, pool: &PgPool) -> sqlx::Result<User> {
     //let password_hash = format!("{:x}", password_hash);
     let uuid = Uuid::new_v4();
 
+    let mut tx = pool.begin().await?;
     let row = sqlx::query!(
         r#"
             INSERT INTO users (id, name, password_hash, role)
@@ -102,7 +77,7 @@ pub async fn add_user(new_user: &NewUser, pool: &PgPool) -> sqlx::Result<User> {
         new_user.password,
         new_user.role,
     )
-    .fetch_one(pool)
+    .fetch_one(&mut tx)
     .await?;
 
     let user = User {
@@ -111,6 +86,96 @@ pub async fn add_user(new_user: &NewUser, pool: &PgPool) -> sqlx::Result<User> {
         password_hash: row.password_hash,
         role: row.role.unwrap(),
     };
+    let after = snapshot(&mut tx, AuditEntity::User, user.id).await?;
+    record(
+        &mut tx,
+        &Actor::from(&user),
+        AuditAction::Create,
+        AuditEntity::User,
+        user.id,
+        None,
+        after,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(user)
+}
+
+///
+/// Replaces the stored password hash of a user
+/// used to upgrade old hashes to Argon2id after a login
+/// the journal only says that the password changed, never the hash
+///
+pub async fn update_password_hash(
+    id: Uuid,
+    password_hash: String,
+    actor: &Actor,
+    pool: &PgPool,
+) -> sqlx::Result<()> {
+    let mut tx = pool.begin().await?;
+    let before = snapshot(&mut tx, AuditEntity::User, id).await?;
+    sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2")
+        .bind(password_hash)
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+    let after = snapshot(&mut tx, AuditEntity::User, id)
+        .await?
+        .map(|mut user| {
+            user["password_hash"] = serde_json::json!("(modifié)");
+            user
+        });
+    record(
+        &mut tx,
+        actor,
+        AuditAction::Update,
+        AuditEntity::User,
+        id,
+        before,
+        after,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+///
+/// Gives another role to a user (admin only, see auth::roles)
+/// returns the user with its new role, RowNotFound for an unknown user
+///
+pub async fn update_user_role(
+    id: Uuid,
+    role: &str,
+    actor: &Actor,
+    pool: &PgPool,
+) -> sqlx::Result<User> {
+    let mut tx = pool.begin().await?;
+    let before = snapshot(&mut tx, AuditEntity::User, id).await?;
+    let user = sqlx::query("UPDATE users SET role = $1 WHERE id = $2 RETURNING id, name, role")
+        .bind(role)
+        .bind(id)
+        .map(|row: PgRow| User {
+            id: row.get("id"),
+            name: row.get("name"),
+            password_hash: String::new(),
+            role: row.get("role"),
+        })
+        .fetch_one(&mut tx)
+        .await?;
+    let after = snapshot(&mut tx, AuditEntity::User, id).await?;
+    record(
+        &mut tx,
+        actor,
+        AuditAction::Update,
+        AuditEntity::User,
+        id,
+        before,
+        after,
+    )
+    .await?;
+    tx.commit().await?;
+
+    tracing::info!("db : role of {} set to {}", &user.name, &user.role);
     Ok(user)
 }
 
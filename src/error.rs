@@ -28,13 +28,23 @@ pub enum AppError {
     #[error("Page non trouvée")]
     NotFound,
 
+    /// Return `409 Conflict` : the row has been modified since the version sent
+    #[error("Modifié entre-temps par quelqu'un d'autre")]
+    Conflict,
+
+    /// Return `409 Conflict` : a unique index refused the row (sqlx error 23505),
+    /// with the message of the index (see `duplicate_message`)
+    #[error("{0}")]
+    Duplicate(&'static str),
+
     #[error("error in the request body")]
     UnprocessableEntity {
         errors: HashMap<Cow<'static, str>, Vec<Cow<'static, str>>>,
     },
 
+    /// unique violations become `Error::Duplicate` (see `From<sqlx::Error>`)
     #[error(transparent)]
-    Sqlx(#[from] sqlx::Error),
+    Sqlx(sqlx::Error),
 
     /// Via the generated `From<anyhow::Error> for Error` impl, this allows the
     /// use of `?` in handler functions to automatically convert `anyhow::Error` into a response.
@@ -110,6 +120,8 @@ impl AppError {
             Self::Unauthorized => StatusCode::UNAUTHORIZED,
             Self::Forbidden => StatusCode::FORBIDDEN,
             Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Conflict => StatusCode::CONFLICT,
+            Self::Duplicate(_) => StatusCode::CONFLICT,
             Self::UnprocessableEntity { .. } => StatusCode::UNPROCESSABLE_ENTITY,
             Self::Sqlx(_) | Self::Anyhow(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::Tera(_) => StatusCode::INTERNAL_SERVER_ERROR,
@@ -209,6 +221,104 @@ impl IntoResponse for AppError {
     }
 }
 
+impl From<sqlx::Error> for AppError {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::Database(ref e) if e.code().as_deref() == Some("23505") => {
+                Self::Duplicate(duplicate_message(e.constraint()))
+            }
+            e => Self::Sqlx(e),
+        }
+    }
+}
+
+///
+/// le message d'un doublon refusé par un index unique
+/// (noms comparés sans majuscules, accents ni espaces autour, migration 0012)
+///
+fn duplicate_message(constraint: Option<&str>) -> &'static str {
+    match constraint {
+        Some("persons_full_name_key") => "Un musicien de ce nom existe déjà",
+        Some("genres_name_key") => "Un genre de ce nom existe déjà",
+        Some("tags_name_key") => "Une étiquette de ce nom existe déjà",
+        _ => "Cet élément existe déjà",
+    }
+}
+
+///
+/// Erreur renvoyée par l'API JSON (/api/v1/...)
+///
+/// Contrairement à AppError, toutes les variantes sont rendues sous la forme
+/// `{ "error": { "status": 404, "message": "..." } }`
+/// Les détails des erreurs internes (sqlx, anyhow, tera) ne sont pas envoyés au client.
+///
+#[derive(Debug)]
+pub struct ApiError(pub AppError);
+
+impl From<AppError> for ApiError {
+    fn from(err: AppError) -> Self {
+        Self(err)
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::RowNotFound => Self(AppError::NotFound),
+            e => Self(AppError::from(e)),
+        }
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        Self(AppError::Anyhow(err))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> axum::response::Response {
+        let status = self.0.status_code();
+        let body = match self.0 {
+            AppError::UnprocessableEntity { errors } => json!({
+                "error": {
+                    "status": status.as_u16(),
+                    "message": "error in the request body",
+                    "errors": errors,
+                }
+            }),
+            AppError::Sqlx(ref e) => {
+                tracing::error!("API SQLx error: {:?}", e);
+                internal_error_body(status)
+            }
+            AppError::Anyhow(ref e) => {
+                tracing::error!("API generic error: {:?}", e);
+                internal_error_body(status)
+            }
+            AppError::Tera(ref e) => {
+                tracing::error!("API Tera error: {:?}", e);
+                internal_error_body(status)
+            }
+            ref e => json!({
+                "error": {
+                    "status": status.as_u16(),
+                    "message": e.to_string(),
+                }
+            }),
+        };
+        (status, Json(body)).into_response()
+    }
+}
+
+fn internal_error_body(status: StatusCode) -> serde_json::Value {
+    json!({
+        "error": {
+            "status": status.as_u16(),
+            "message": "Erreur interne du serveur",
+        }
+    })
+}
+
 
 #[derive(Debug)]
 pub enum SignupError {
@@ -218,7 +328,6 @@ pub enum SignupError {
     MissingPassword,
     MissingUserName,
     MissingPwConfirm,
-    MissingRole,
     InvalidPassword,
     InternalError,
 }
@@ -232,7 +341,6 @@ impl Display for SignupError {
             SignupError::MissingPassword => f.write_str("Il faut entrer un mot de passe"),
             SignupError::MissingUserName => f.write_str("Il faut entrer un nom d'utilisateur"),
             SignupError::MissingPwConfirm => f.write_str("Il faut confirmer le mot de passe"),
-            SignupError::MissingRole => f.write_str("Il faut entrer un rôle"),
             SignupError::InvalidPassword => f.write_str("Mot de passe incorrect"),
             SignupError::InternalError => f.write_str("Erreur Serveur"),
         }
@@ -263,7 +371,6 @@ impl IntoResponse for SignupError {
             SignupError::MissingPwConfirm => {
                 (StatusCode::BAD_REQUEST, "Il faut confirmer le mot de passe")
             }
-            SignupError::MissingRole => (StatusCode::BAD_REQUEST, "Il faut entrer un rôle"),
             SignupError::InvalidPassword => (StatusCode::UNAUTHORIZED, "Mot de passe incorrect"),
             SignupError::InternalError => (StatusCode::INTERNAL_SERVER_ERROR, "Erreur Serveur"),
         };
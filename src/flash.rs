@@ -24,14 +24,30 @@ pub fn person_response(
     (StatusCode::SEE_OTHER, header)
 }
 
-pub fn genre_response(flash: &mut Flash, message: String) -> (StatusCode, HeaderMap) {
-    flash.info(message);
+pub fn genre_response(
+    flash: &mut Flash,
+    level: axum_flash::Level,
+    message: String,
+) -> (StatusCode, HeaderMap) {
+    flash.push(level, message);
     let mut header = HeaderMap::new();
     header.insert(header::LOCATION, HeaderValue::from_static("/genres"));
 
     (StatusCode::SEE_OTHER, header)
 }
 
+pub fn tag_response(
+    flash: &mut Flash,
+    level: axum_flash::Level,
+    message: String,
+) -> (StatusCode, HeaderMap) {
+    flash.push(level, message);
+    let mut header = HeaderMap::new();
+    header.insert(header::LOCATION, HeaderValue::from_static("/tags"));
+
+    (StatusCode::SEE_OTHER, header)
+}
+
 pub fn partition_response(
     flash: &mut Flash,
     level: axum_flash::Level,
@@ -44,6 +60,117 @@ pub fn partition_response(
     (StatusCode::SEE_OTHER, header)
 }
 
+///
+/// retourne un message flash sur la page d'une partition ('/partitions/show/:id')
+/// après une modification ou une action sur ses fichiers
+///
+pub fn partition_page_response(
+    flash: &mut Flash,
+    level: axum_flash::Level,
+    message: String,
+    partition_id: i32,
+) -> (StatusCode, HeaderMap) {
+    flash.push(level, message);
+    let mut header = HeaderMap::new();
+    let location = format!("/partitions/show/{}", partition_id);
+    header.insert(
+        header::LOCATION,
+        HeaderValue::from_str(&location).expect("un chemin ASCII est un en-tête valide"),
+    );
+
+    (StatusCode::SEE_OTHER, header)
+}
+
+///
+/// retourne un message flash sur la page '/setlists' (liste des programmes)
+///
+pub fn setlist_response(
+    flash: &mut Flash,
+    level: axum_flash::Level,
+    message: String,
+) -> (StatusCode, HeaderMap) {
+    flash.push(level, message);
+    let mut header = HeaderMap::new();
+    header.insert(header::LOCATION, HeaderValue::from_static("/setlists"));
+
+    (StatusCode::SEE_OTHER, header)
+}
+
+///
+/// retourne un message flash sur la page d'un programme ('/setlists/show/:id')
+/// après une modification du programme ou de ses pièces
+///
+pub fn setlist_page_response(
+    flash: &mut Flash,
+    level: axum_flash::Level,
+    message: String,
+    setlist_id: i32,
+) -> (StatusCode, HeaderMap) {
+    flash.push(level, message);
+    let mut header = HeaderMap::new();
+    let location = format!("/setlists/show/{}", setlist_id);
+    header.insert(
+        header::LOCATION,
+        HeaderValue::from_str(&location).expect("un chemin ASCII est un en-tête valide"),
+    );
+
+    (StatusCode::SEE_OTHER, header)
+}
+
+///
+/// retourne un message flash sur le rapport des doublons
+/// ('/persons/duplicates' ou '/genres/duplicates') après une fusion
+///
+pub fn duplicates_response(
+    flash: &mut Flash,
+    level: axum_flash::Level,
+    message: String,
+    location: &'static str,
+) -> (StatusCode, HeaderMap) {
+    flash.push(level, message);
+    let mut header = HeaderMap::new();
+    header.insert(header::LOCATION, HeaderValue::from_static(location));
+
+    (StatusCode::SEE_OTHER, header)
+}
+
+///
+/// retourne un message flash sur la corbeille ('/trash/partitions', ...)
+/// après une restauration ou une purge
+///
+pub fn trash_response(
+    flash: &mut Flash,
+    level: axum_flash::Level,
+    message: String,
+    kind: &str,
+) -> (StatusCode, HeaderMap) {
+    flash.push(level, message);
+    let mut header = HeaderMap::new();
+    let location = format!("/trash/{}", kind);
+    header.insert(
+        header::LOCATION,
+        HeaderValue::from_str(&location).expect("un chemin ASCII est un en-tête valide"),
+    );
+
+    (StatusCode::SEE_OTHER, header)
+}
+
+///
+/// retourne un message flash sur la page '/auth/users' (liste des utilisateurs)
+/// après un changement de rôle
+///
+pub fn users_response(
+    flash: &mut Flash,
+    level: axum_flash::Level,
+    message: String,
+) -> (StatusCode, HeaderMap) {
+    flash.push(level, message);
+    let mut header = HeaderMap::new();
+    header.insert(header::LOCATION, HeaderValue::from_static("/auth/users"));
+
+    (StatusCode::SEE_OTHER, header)
+}
+
 pub fn signup_response(flash: &mut Flash, level: axum_flash::Level, message: String) -> Redirect {
     flash.push(level, message);
     /*    let mut header = HeaderMap::new();
@@ -0,0 +1,274 @@
+//! src/handlers/api_hdl.rs
+//!
+//! API JSON versionnée (/api/v1/...) à côté des pages Tera
+//! Les handlers utilisent les mêmes fonctions de la DB que les pages HTML
+//! et renvoient les erreurs sous forme de JSON via ApiError
+//! Les listes sont paginées : ?page=1&per_page=50&sort=...&dir=asc|desc
+
+use axum::extract::{Extension, Path, Query};
+use axum::http::StatusCode;
+use axum::Json;
+
+use sqlx::PgPool;
+
+use crate::db::{genres::*, musicians::*, partitions::*};
+use crate::error::{ApiError, AppError};
+use crate::models::audit::Actor;
+use crate::models::deletion::{DeleteForm, Deletion};
+use crate::models::genre::{Genre, GenreName};
+use crate::models::musician::{Person, PersonName};
+use crate::models::pagination::{ListParams, Page};
+use crate::models::partition::{Partition, PartitionFilter, PartitionTitle, ShowPartition};
+use crate::models::version::{required_version, Update};
+
+//***********************************************************************************
+// Persons - musicians
+//
+
+pub async fn api_list_persons_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Query(params): Query<ListParams>,
+) -> Result<Json<Page<Person>>, ApiError> {
+    let persons = list_persons_page(pool, &params).await?;
+    Ok(Json(persons))
+}
+
+pub async fn api_get_person_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Path(id): Path<i32>,
+) -> Result<Json<Person>, ApiError> {
+    let person = find_person_by_id(id, pool).await?;
+    Ok(Json(person))
+}
+
+pub async fn api_create_person_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    Json(person): Json<Person>,
+) -> Result<(StatusCode, Json<Person>), ApiError> {
+    let name = valid("full_name", PersonName::parse(&person.full_name))?;
+    let person = add_person(pool, name, &actor).await?;
+    Ok((StatusCode::CREATED, Json(person)))
+}
+
+pub async fn api_update_person_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    Path(id): Path<i32>,
+    Json(person): Json<Person>,
+) -> Result<Json<Person>, ApiError> {
+    let name = valid("full_name", PersonName::parse(&person.full_name))?;
+    let version = required_version(person.version)?;
+    let person = update_person(id, name, version, &actor, pool).await?;
+    saved(person)
+}
+
+///
+/// DELETE /api/v1/persons/:id?reassign_to=12
+/// refusé (422) tant que des partitions dépendent du musicien
+/// et qu'aucun musicien de remplacement n'est donné
+///
+pub async fn api_delete_person_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    Path(id): Path<i32>,
+    Query(form): Query<DeleteForm>,
+) -> Result<StatusCode, ApiError> {
+    deletion_status(delete_person(id, form.reassign_to, &actor, pool).await?)
+}
+
+//***********************************************************************************
+// Genres
+//
+
+pub async fn api_list_genres_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Query(params): Query<ListParams>,
+) -> Result<Json<Page<Genre>>, ApiError> {
+    let genres = list_genres_page(pool, &params).await?;
+    Ok(Json(genres))
+}
+
+pub async fn api_get_genre_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Path(id): Path<i32>,
+) -> Result<Json<Genre>, ApiError> {
+    let genre = find_genre_by_id(id, pool).await?;
+    Ok(Json(genre))
+}
+
+pub async fn api_create_genre_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    Json(genre): Json<Genre>,
+) -> Result<(StatusCode, Json<Genre>), ApiError> {
+    let name = valid("name", GenreName::parse(&genre.name))?;
+    let genre = add_genre(pool, name, &actor).await?;
+    Ok((StatusCode::CREATED, Json(genre)))
+}
+
+pub async fn api_update_genre_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    Path(id): Path<i32>,
+    Json(genre): Json<Genre>,
+) -> Result<Json<Genre>, ApiError> {
+    let name = valid("name", GenreName::parse(&genre.name))?;
+    let version = required_version(genre.version)?;
+    let genre = update_genre(id, name, version, &actor, pool).await?;
+    saved(genre)
+}
+
+///
+/// DELETE /api/v1/genres/:id?reassign_to=3
+/// refusé (422) tant que des partitions dépendent du genre
+/// et qu'aucun genre de remplacement n'est donné
+///
+pub async fn api_delete_genre_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    Path(id): Path<i32>,
+    Query(form): Query<DeleteForm>,
+) -> Result<StatusCode, ApiError> {
+    deletion_status(delete_genre(id, form.reassign_to, &actor, pool).await?)
+}
+
+///
+/// la ligne modifiée, ou 409 quand le champ "version" du JSON est périmé
+/// (un PUT sans "version" est refusé avant, 422 : voir required_version)
+///
+fn saved<T>(update: Update<T>) -> Result<Json<T>, ApiError> {
+    match update {
+        Update::Saved(row) => Ok(Json(row)),
+        Update::Conflict { .. } => Err(AppError::Conflict.into()),
+    }
+}
+
+fn deletion_status(deletion: Deletion) -> Result<StatusCode, ApiError> {
+    match deletion {
+        Deletion::Deleted { .. } => Ok(StatusCode::NO_CONTENT),
+        Deletion::Refused { dependents, .. } => Err(AppError::unprocessable_entity([(
+            "reassign_to",
+            format!(
+                "{} partition(s) à réaffecter avant l'effacement",
+                dependents
+            ),
+        )])
+        .into()),
+    }
+}
+
+//***********************************************************************************
+// Partitions
+//
+// les partitions sont lues sous forme de ShowPartition (titre, musicien, genre)
+// et filtrées comme sur la page HTML : ?title=...&author=...&genre=...
+// mais créées et modifiées avec les ids du musicien et du genre (Partition)
+//
+
+pub async fn api_list_partitions_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Query(filter): Query<PartitionFilter>,
+    Query(params): Query<ListParams>,
+) -> Result<Json<Page<ShowPartition>>, ApiError> {
+    let partitions = filter_partitions(&filter, Some(&params), pool).await?;
+    Ok(Json(partitions))
+}
+
+pub async fn api_get_partition_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Path(id): Path<i32>,
+) -> Result<Json<ShowPartition>, ApiError> {
+    let partition = find_partition_by_id(id, pool).await?;
+    let show_partition = show_one_partition(partition, pool).await?;
+    Ok(Json(show_partition))
+}
+
+pub async fn api_create_partition_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    Json(partition): Json<Partition>,
+) -> Result<(StatusCode, Json<Partition>), ApiError> {
+    let title = check_partition(&partition, pool).await?;
+    let partition = add_partition_with_ids(
+        title,
+        partition.person_id,
+        partition.genre_id,
+        partition.metadata,
+        &actor,
+        pool,
+    )
+    .await?;
+    Ok((StatusCode::CREATED, Json(partition)))
+}
+
+pub async fn api_update_partition_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    Path(id): Path<i32>,
+    Json(partition): Json<Partition>,
+) -> Result<Json<Partition>, ApiError> {
+    // vérifie d'abord que la partition existe (404 sinon)
+    find_partition_by_id(id, pool).await?;
+    let title = check_partition(&partition, pool).await?;
+    let version = required_version(partition.version)?;
+    let partition = update_partition(
+        id,
+        title,
+        partition.person_id,
+        partition.genre_id,
+        partition.metadata,
+        version,
+        &actor,
+        pool,
+    )
+    .await?;
+    saved(partition)
+}
+
+pub async fn api_delete_partition_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, ApiError> {
+    delete_partition(id, &actor, pool).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+///
+/// Vérifie le titre d'une partition et que le musicien et le genre référencés existent
+/// Renvoie le titre validé, ou une erreur 422 avec tous les champs fautifs
+///
+async fn check_partition(partition: &Partition, pool: &PgPool) -> Result<PartitionTitle, ApiError> {
+    let mut errors = Vec::new();
+    let title = match PartitionTitle::parse(&partition.title) {
+        Ok(title) => Some(title),
+        Err(error) => {
+            errors.push(("title", error));
+            None
+        }
+    };
+
+    match find_person_by_id(partition.person_id, pool).await {
+        Ok(_) => (),
+        Err(sqlx::Error::RowNotFound) => errors.push(("person_id", "musicien inconnu".into())),
+        Err(e) => return Err(e.into()),
+    }
+    match find_genre_by_id(partition.genre_id, pool).await {
+        Ok(_) => (),
+        Err(sqlx::Error::RowNotFound) => errors.push(("genre_id", "genre inconnu".into())),
+        Err(e) => return Err(e.into()),
+    }
+
+    match title {
+        Some(title) if errors.is_empty() => Ok(title),
+        _ => Err(AppError::unprocessable_entity(errors).into()),
+    }
+}
+
+///
+/// un nom validé (PersonName::parse, GenreName::parse), ou 422 avec son erreur
+///
+fn valid<T>(field: &'static str, parsed: Result<T, String>) -> Result<T, ApiError> {
+    parsed.map_err(|error| AppError::unprocessable_entity([(field, error)]).into())
+}
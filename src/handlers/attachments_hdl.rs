@@ -0,0 +1,423 @@
+//! src/handlers/attachments_hdl.rs
+//!
+//! Fichiers des partitions (PDF, MusicXML, images)
+//! page de détail d'une partition, envoi des fichiers (multipart),
+//! téléchargement et affichage dans le navigateur
+//!
+//! Taille maximale d'un fichier dans le fichier .env : MAX_UPLOAD_MB (défaut 50)
+
+use axum::body::Bytes;
+use axum::extract::{Extension, Multipart, Path};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use axum_flash::{Flash, IncomingFlashes};
+
+use chrono::{Duration, Utc};
+use once_cell::sync::Lazy;
+use sqlx::PgPool;
+use tera::Tera;
+
+use crate::auth::current_user::MaybeUser;
+use crate::db::attachments::*;
+use crate::db::genres::list_genres;
+use crate::db::library::{list_active_loans, list_copies};
+use crate::db::musicians::list_persons;
+use crate::db::partitions::{find_partition_by_id, show_one_partition};
+use crate::db::tags::list_tags;
+use crate::error::AppError;
+use crate::flash::partition_page_response;
+use crate::models::attachment::Attachment;
+use crate::storage::{content_hash, SharedStorage};
+
+static MAX_UPLOAD_BYTES: Lazy<usize> = Lazy::new(|| {
+    let megabytes = std::env::var("MAX_UPLOAD_MB")
+        .ok()
+        .and_then(|value| value.parse::<usize>().ok())
+        .unwrap_or(50);
+    megabytes * 1024 * 1024
+});
+
+///
+/// Shows one partition with its details, the form to modify it,
+/// the list of its files and the form to upload new ones,
+/// its printed copies and the loans in progress
+///
+/// Returns a HTML Page or AppError
+///
+pub async fn show_partition_hdl(
+    Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
+    Extension(ref pool): Extension<PgPool>,
+    Path(id): Path<i32>,
+    flash: IncomingFlashes,
+) -> Result<Html<String>, AppError> {
+    let flash = flash
+        .into_iter()
+        .map(|(level, text)| format!("{:?}: {}", level, text))
+        .collect::<Vec<_>>()
+        .join(", ");
+    tracing::info!("flash : {}", flash);
+
+    let ctx = partition_context(&current_user, id, pool, &flash).await?;
+
+    let body = templates
+        .render("partition.html", &ctx)
+        .map_err(|e| AppError::Tera(e))?;
+
+    Ok(Html(body))
+}
+
+///
+/// le contexte de la page d'une partition (partition.html) :
+/// la partition, ses fichiers, ses exemplaires et ses prêts,
+/// les listes des formulaires et le message flash
+/// (partagé avec update_partition_hdl qui y réaffiche un formulaire refusé)
+///
+pub async fn partition_context(
+    current_user: &MaybeUser,
+    id: i32,
+    pool: &PgPool,
+    flash: &str,
+) -> Result<tera::Context, AppError> {
+    let partition = find_partition_by_id(id, pool).await.map_err(not_found)?;
+    let show_partition = show_one_partition(partition, pool).await?;
+    let attachments = list_attachments(id, pool).await?;
+    // pour le formulaire de modification
+    let persons = list_persons(pool).await?;
+    let genres = list_genres(pool).await?;
+    let tags = list_tags(pool).await?;
+    // bibliothèque : exemplaires et prêts en cours, retour proposé dans 3 semaines
+    let copies = list_copies(id, pool).await?;
+    let loans = list_active_loans(id, pool).await?;
+    let default_due_date = (Utc::now() + Duration::weeks(3)).date_naive();
+
+    let title = format!("Partition : {}", show_partition.title);
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
+    ctx.insert("partition", &show_partition);
+    ctx.insert("attachments", &attachments);
+    ctx.insert("persons", &persons);
+    ctx.insert("genres", &genres);
+    ctx.insert("tags", &tags);
+    ctx.insert("copies", &copies);
+    ctx.insert("loans", &loans);
+    ctx.insert("default_due_date", &default_due_date);
+    ctx.insert("max_upload_mb", &(*MAX_UPLOAD_BYTES / (1024 * 1024)));
+    ctx.insert("flash", &flash);
+    Ok(ctx)
+}
+
+///
+/// Receives one or several files (field "file" of a multipart form)
+/// and attaches them to the partition
+///
+/// Returns to the page of the partition with a flash message
+///
+pub async fn upload_attachments_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Extension(ref storage): Extension<SharedStorage>,
+    Path(id): Path<i32>,
+    mut flash: Flash,
+    mut multipart: Multipart,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    find_partition_by_id(id, pool).await.map_err(not_found)?;
+
+    let mut added: Vec<String> = Vec::new();
+    let mut refused: Vec<String> = Vec::new();
+
+    loop {
+        let mut field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => {
+                tracing::info!("erreur multipart : {}", e);
+                refused.push("envoi interrompu".to_string());
+                break;
+            }
+        };
+        if field.name() != Some("file") {
+            continue;
+        }
+        // aucun fichier choisi : le navigateur envoie un nom vide
+        let file_name = match field.file_name().map(clean_file_name) {
+            Some(name) if !name.is_empty() => name,
+            _ => continue,
+        };
+        let content_type = match content_type_for(&file_name) {
+            Some(content_type) => content_type,
+            None => {
+                refused.push(format!("{} (type de fichier non accepté)", file_name));
+                continue;
+            }
+        };
+
+        let mut content: Vec<u8> = Vec::new();
+        let mut too_big = false;
+        loop {
+            match field.chunk().await {
+                Ok(Some(chunk)) => {
+                    if content.len() + chunk.len() > *MAX_UPLOAD_BYTES {
+                        too_big = true;
+                        break;
+                    }
+                    content.extend_from_slice(&chunk);
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    tracing::info!("erreur multipart : {}", e);
+                    too_big = true;
+                    break;
+                }
+            }
+        }
+        if too_big {
+            refused.push(format!("{} (fichier trop grand ou incomplet)", file_name));
+            // le reste du corps de la requête n'est pas lu
+            break;
+        }
+        if !has_signature(content_type, &content) {
+            refused.push(format!(
+                "{} (contenu ne correspondant pas au type)",
+                file_name
+            ));
+            continue;
+        }
+
+        let hash = content_hash(&content);
+        let size = content.len() as i64;
+        storage.put(&hash, Bytes::from(content)).await?;
+        let attachment = add_attachment(id, &file_name, content_type, size, &hash, pool).await?;
+        tracing::info!("fichier ajouté : {:?}", attachment);
+        added.push(attachment.file_name);
+    }
+
+    let (level, message) = match (added.is_empty(), refused.is_empty()) {
+        (true, true) => (
+            axum_flash::Level::Warning,
+            "Aucun fichier choisi".to_string(),
+        ),
+        (false, true) => (
+            axum_flash::Level::Success,
+            format!("Fichier(s) ajouté(s) : {}", added.join(", ")),
+        ),
+        (true, false) => (
+            axum_flash::Level::Error,
+            format!("Fichier(s) refusé(s) : {}", refused.join(", ")),
+        ),
+        (false, false) => (
+            axum_flash::Level::Warning,
+            format!(
+                "Fichier(s) ajouté(s) : {} ; refusé(s) : {}",
+                added.join(", "),
+                refused.join(", ")
+            ),
+        ),
+    };
+    Ok(partition_page_response(&mut flash, level, message, id))
+}
+
+///
+/// Sends a file to be saved by the browser
+///
+pub async fn download_attachment_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Extension(ref storage): Extension<SharedStorage>,
+    Path(id): Path<i32>,
+) -> Result<Response, AppError> {
+    let attachment = find_attachment_by_id(id, pool).await.map_err(not_found)?;
+    let content = storage.get(&attachment.sha3_256).await?;
+    Ok(file_response(&attachment, content, "attachment"))
+}
+
+///
+/// Sends a file to be shown in the browser (PDF, images)
+/// MusicXML files are shown as plain text
+///
+pub async fn view_attachment_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Extension(ref storage): Extension<SharedStorage>,
+    Path(id): Path<i32>,
+) -> Result<Response, AppError> {
+    let attachment = find_attachment_by_id(id, pool).await.map_err(not_found)?;
+    let content = storage.get(&attachment.sha3_256).await?;
+    Ok(file_response(&attachment, content, "inline"))
+}
+
+///
+/// Deletes a file of a partition
+/// the content is removed from the storage when no other partition uses it
+///
+pub async fn delete_attachment_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Extension(ref storage): Extension<SharedStorage>,
+    Path(id): Path<i32>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let (attachment, remaining) = delete_attachment(id, pool).await.map_err(not_found)?;
+    if remaining == 0 {
+        storage.delete(&attachment.sha3_256).await?;
+    }
+    let message = format!("Fichier effacé : {}", attachment.file_name);
+    let level = axum_flash::Level::Success;
+    Ok(partition_page_response(
+        &mut flash,
+        level,
+        message,
+        attachment.partition_id,
+    ))
+}
+
+//*******************************************************************************
+// Fonctions d'aide
+//
+
+///
+/// empreintes des fichiers d'une partition, à relever avant de la purger
+/// (ses fichiers sont effacés en cascade dans la DB)
+///
+pub(crate) async fn attachment_hashes(
+    partition_id: i32,
+    pool: &PgPool,
+) -> sqlx::Result<Vec<String>> {
+    let attachments = list_attachments(partition_id, pool).await?;
+    Ok(attachments
+        .into_iter()
+        .map(|attachment| attachment.sha3_256)
+        .collect())
+}
+
+///
+/// retire du stockage les contenus qui ne servent plus à aucune partition
+///
+pub(crate) async fn purge_unused_contents(
+    hashes: &[String],
+    storage: &SharedStorage,
+    pool: &PgPool,
+) -> Result<(), AppError> {
+    for hash in unused_hashes(hashes, pool).await? {
+        storage.delete(&hash).await?;
+    }
+    Ok(())
+}
+
+fn not_found(err: sqlx::Error) -> AppError {
+    match err {
+        sqlx::Error::RowNotFound => AppError::NotFound,
+        e => AppError::Sqlx(e),
+    }
+}
+
+///
+/// type MIME d'après l'extension du nom de fichier
+/// (celui envoyé par le navigateur n'est pas fiable)
+/// None : type de fichier non accepté
+///
+fn content_type_for(file_name: &str) -> Option<&'static str> {
+    let extension = file_name.rsplit_once('.')?.1.to_lowercase();
+    let content_type = match extension.as_str() {
+        "pdf" => "application/pdf",
+        "musicxml" => "application/vnd.recordare.musicxml+xml",
+        "mxl" => "application/vnd.recordare.musicxml",
+        "xml" => "application/xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "tif" | "tiff" => "image/tiff",
+        _ => return None,
+    };
+    Some(content_type)
+}
+
+///
+/// vérifie les premiers octets du fichier pour les types qui en ont
+///
+fn has_signature(content_type: &str, content: &[u8]) -> bool {
+    match content_type {
+        "application/pdf" => content.starts_with(b"%PDF-"),
+        "application/vnd.recordare.musicxml" => content.starts_with(b"PK\x03\x04"),
+        "image/png" => content.starts_with(b"\x89PNG\r\n\x1a\n"),
+        "image/jpeg" => content.starts_with(b"\xff\xd8\xff"),
+        "image/gif" => content.starts_with(b"GIF87a") || content.starts_with(b"GIF89a"),
+        "image/webp" => {
+            content.len() >= 12 && &content[..4] == b"RIFF" && &content[8..12] == b"WEBP"
+        }
+        "image/tiff" => content.starts_with(b"II*\0") || content.starts_with(b"MM\0*"),
+        _ => !content.is_empty(),
+    }
+}
+
+///
+/// garde le nom du fichier sans le chemin ni les caractères de contrôle
+///
+fn clean_file_name(name: &str) -> String {
+    let base = name.rsplit(|c| c == '/' || c == '\\').next().unwrap_or("");
+    base.chars()
+        .filter(|c| !c.is_control() && *c != '"')
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+///
+/// réponse avec le contenu du fichier
+/// disposition : "attachment" (téléchargement) ou "inline" (affichage)
+///
+fn file_response(attachment: &Attachment, content: Bytes, disposition: &str) -> Response {
+    // un XML affiché dans le navigateur pourrait contenir du script
+    let content_type = if disposition == "inline" && attachment.content_type.ends_with("xml") {
+        "text/plain; charset=utf-8"
+    } else {
+        attachment.content_type.as_str()
+    };
+
+    let ascii_name: String = attachment
+        .file_name
+        .chars()
+        .map(|c| if c.is_ascii() && c != '\\' { c } else { '_' })
+        .collect();
+    let content_disposition = format!(
+        "{}; filename=\"{}\"; filename*=UTF-8''{}",
+        disposition,
+        ascii_name,
+        percent_encode(&attachment.file_name)
+    );
+
+    let mut response = content.into_response();
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(content_type) {
+        headers.insert(header::CONTENT_TYPE, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&content_disposition) {
+        headers.insert(header::CONTENT_DISPOSITION, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&format!("\"{}\"", attachment.sha3_256)) {
+        headers.insert(header::ETAG, value);
+    }
+    headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("private, max-age=3600"),
+    );
+    response
+}
+
+///
+/// encodage RFC 5987 du nom de fichier (filename*=UTF-8''...)
+///
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
@@ -0,0 +1,113 @@
+//! src/handlers/audit_hdl.rs
+//!
+//! Pages du journal des modifications, réservées aux administrateurs :
+//! /audit (tout le journal, avec filtre) et /audit/:entity_type/:id
+//! (l'historique d'un musicien, d'un genre, d'une partition ou d'un utilisateur)
+
+use axum::extract::{Extension, Path, Query};
+use axum::response::Html;
+
+use sqlx::PgPool;
+use tera::Tera;
+
+use crate::auth::current_user::MaybeUser;
+use crate::db::audit::{entity_history, list_audit_page};
+use crate::error::AppError;
+use crate::models::audit::{AuditAction, AuditEntity, AuditFilter};
+use crate::models::pagination::{ListParams, SortDir};
+
+///
+/// Shows the journal, the most recent changes first,
+/// filtered by author, action, type, id and dates
+/// /audit?actor=leon&entity_type=partition&from=2026-10-01&to=2026-10-31
+///
+/// Returns a HTML Page or AppError
+///
+pub async fn list_audit_hdl(
+    Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
+    Extension(ref pool): Extension<PgPool>,
+    Query(filter): Query<AuditFilter>,
+    Query(mut params): Query<ListParams>,
+) -> Result<Html<String>, AppError> {
+    // première visite : les modifications les plus récentes d'abord
+    if params.sort.is_none() {
+        params.dir = SortDir::Desc;
+    }
+    let page = list_audit_page(&filter, &params, pool).await?;
+
+    let title = "Journal des modifications";
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
+    ctx.insert("entries", &page.items);
+    ctx.insert("page", &page);
+    ctx.insert("filter", &filter);
+    ctx.insert("actions", &action_options());
+    ctx.insert("entities", &entity_options());
+
+    let body = templates
+        .render("audit.html", &ctx)
+        .map_err(|err| AppError::Tera(err))?;
+
+    Ok(Html(body))
+}
+
+///
+/// Shows every change of one musician, genre, partition or user
+/// /audit/partition/12, /audit/user/<uuid>
+///
+/// Returns a HTML Page or AppError (404 for an unknown type)
+///
+pub async fn entity_history_hdl(
+    Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
+    Extension(ref pool): Extension<PgPool>,
+    Path((entity_type, entity_id)): Path<(String, String)>,
+) -> Result<Html<String>, AppError> {
+    let entity: AuditEntity = entity_type.parse().map_err(|_| AppError::NotFound)?;
+    let entries = entity_history(entity, &entity_id, pool).await?;
+
+    let title = format!("Historique : {} {}", entity.label(), entity_id);
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
+    ctx.insert("entries", &entries);
+    ctx.insert("entity_type", &entity);
+    ctx.insert("entity_id", &entity_id);
+
+    let body = templates
+        .render("audit.html", &ctx)
+        .map_err(|err| AppError::Tera(err))?;
+
+    Ok(Html(body))
+}
+
+///
+/// (valeur, libellé) des listes déroulantes du filtre
+///
+fn action_options() -> Vec<(&'static str, &'static str)> {
+    [
+        AuditAction::Create,
+        AuditAction::Update,
+        AuditAction::Delete,
+    ]
+    .iter()
+    .map(|action| (action.as_str(), action.label()))
+    .collect()
+}
+
+fn entity_options() -> Vec<(&'static str, &'static str)> {
+    [
+        AuditEntity::Person,
+        AuditEntity::Genre,
+        AuditEntity::Partition,
+        AuditEntity::User,
+        AuditEntity::Tag,
+    ]
+    .iter()
+    .map(|entity| (entity.as_str(), entity.label()))
+    .collect()
+}
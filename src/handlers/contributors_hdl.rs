@@ -0,0 +1,111 @@
+//! src/handlers/contributors_hdl.rs
+//!
+//! Les musiciens d'une partition et leur rôle (compositeur, arrangeur, parolier)
+//! les formulaires sont sur la page de la partition (/partitions/show/:id)
+
+use axum::extract::{Extension, Form, Path};
+use axum::http::{HeaderMap, StatusCode};
+use axum_flash::Flash;
+
+use sqlx::PgPool;
+
+use crate::db::contributors::{add_contributor, delete_contributor};
+use crate::db::musicians::find_person_by_name;
+use crate::db::partitions::find_partition_by_id;
+use crate::error::AppError;
+use crate::flash::partition_page_response;
+use crate::models::audit::Actor;
+use crate::models::contributor::{ContributorForm, ContributorKey, ContributorRole};
+
+///
+/// Adds a musician with a role to the partition
+///
+/// Returns to the page of the partition with a flash message
+///
+pub async fn add_contributor_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    Path(id): Path<i32>,
+    form: Form<ContributorForm>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let contributor = form.0;
+
+    // la partition doit exister, le musicien est choisi dans la liste
+    find_partition_by_id(id, pool).await?;
+    let persons = find_person_by_name(contributor.full_name.clone(), pool).await?;
+    let person = match persons
+        .iter()
+        .find(|person| person.full_name == contributor.full_name)
+    {
+        Some(person) => person,
+        None => {
+            let message = format!("Erreur : musicien inconnu : {}", contributor.full_name);
+            let level = axum_flash::Level::Error;
+            return Ok(partition_page_response(&mut flash, level, message, id));
+        }
+    };
+    let person_id = person.id.unwrap();
+
+    let (level, message) = if add_contributor(id, person_id, contributor.role, &actor, pool).await?
+    {
+        (
+            axum_flash::Level::Success,
+            format!(
+                "Musicien ajouté : {} ({})",
+                contributor.full_name,
+                contributor.role.label()
+            ),
+        )
+    } else {
+        (
+            axum_flash::Level::Warning,
+            format!(
+                "{} est déjà {} de cette partition",
+                contributor.full_name,
+                contributor.role.label()
+            ),
+        )
+    };
+    Ok(partition_page_response(&mut flash, level, message, id))
+}
+
+///
+/// Removes a role of a musician from the partition
+/// the main musician stays composer : it is changed with the form of the partition
+///
+/// Returns to the page of the partition with a flash message
+///
+pub async fn delete_contributor_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    Path(id): Path<i32>,
+    form: Form<ContributorKey>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let contributor = form.0;
+
+    let partition = find_partition_by_id(id, pool).await?;
+    if contributor.role == ContributorRole::Composer && partition.person_id == contributor.person_id
+    {
+        let message = String::from(
+            "Erreur : le musicien principal se change avec le formulaire de modification",
+        );
+        let level = axum_flash::Level::Error;
+        return Ok(partition_page_response(&mut flash, level, message, id));
+    }
+
+    let (level, message) =
+        if delete_contributor(id, contributor.person_id, contributor.role, &actor, pool).await? {
+            (
+                axum_flash::Level::Success,
+                format!("Musicien retiré ({})", contributor.role.label()),
+            )
+        } else {
+            (
+                axum_flash::Level::Warning,
+                format!("Ce musicien n'était pas {}", contributor.role.label()),
+            )
+        };
+    Ok(partition_page_response(&mut flash, level, message, id))
+}
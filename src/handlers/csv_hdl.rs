@@ -0,0 +1,535 @@
+//! src/handlers/csv_hdl.rs
+//!
+//! Export CSV des listes (musiciens, genres, partitions)
+//! et import CSV des partitions
+//!
+//! L'export reprend la recherche ou le filtre affiché par l'utilisateur,
+//! comme les pages d'impression.
+//! L'import se fait en deux temps : le fichier est d'abord vérifié
+//! et chaque ligne est montrée avec ses erreurs ; l'import n'est enregistré,
+//! en une seule transaction, qu'une fois confirmé et sans aucune erreur.
+
+use std::collections::HashMap;
+
+use axum::extract::{Extension, Form, Multipart, Query};
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use axum_flash::Flash;
+
+use csv::StringRecord;
+use sqlx::PgPool;
+use tera::Tera;
+
+use crate::auth::current_user::MaybeUser;
+use crate::db::genres::{find_genre_by_name, find_genre_id, list_genres};
+use crate::db::musicians::{find_person_by_name, find_person_id, list_persons};
+use crate::db::partitions::{filter_partitions, find_duplicate_partitions, import_partitions};
+use crate::error::AppError;
+use crate::flash::partition_response;
+use crate::handlers::genres_hdl::PrintQuery as GenreQuery;
+use crate::handlers::musicians_hdl::PrintQuery as PersonQuery;
+use crate::models::audit::Actor;
+use crate::models::csv_import::{ImportForm, ImportReport, ImportRow, PARTITION_COLUMNS};
+use crate::models::genre::GenreName;
+use crate::models::musician::PersonName;
+use crate::models::partition::{PartitionFilter, PartitionTitle};
+use crate::utils::serde_utils::{format_duration, parse_duration};
+
+// taille maximale du fichier CSV envoyé
+const MAX_CSV_BYTES: usize = 5 * 1024 * 1024;
+
+//*******************************************************************************
+// Export
+//
+
+///
+/// Exports the musicians (or the search shown on the page) : /persons/export.csv?name=Ba
+///
+pub async fn export_persons_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Query(query): Query<PersonQuery>,
+) -> Result<Response, AppError> {
+    let persons = match query.name {
+        Some(name) if !name.is_empty() => find_person_by_name(name, pool).await?,
+        _ => list_persons(pool).await?,
+    };
+    let records = persons.into_iter().map(|person| vec![person.full_name]);
+
+    csv_response("musiciens.csv", write_csv(&["nom"], records)?)
+}
+
+///
+/// Exports the genres (or the search shown on the page) : /genres/export.csv?name=Ja
+///
+pub async fn export_genres_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Query(query): Query<GenreQuery>,
+) -> Result<Response, AppError> {
+    let genres = match query.name {
+        Some(name) if !name.is_empty() => find_genre_by_name(name, pool).await?,
+        _ => list_genres(pool).await?,
+    };
+    let records = genres.into_iter().map(|genre| vec![genre.name]);
+
+    csv_response("genres.csv", write_csv(&["genre"], records)?)
+}
+
+///
+/// Exports the partitions matching the filter of the list, without pagination
+/// the columns are those read by the import
+///
+pub async fn export_partitions_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Query(filter): Query<PartitionFilter>,
+) -> Result<Response, AppError> {
+    let partitions = filter_partitions(&filter, None, pool).await?.items;
+
+    let text = |value: Option<String>| value.unwrap_or_default();
+    let number = |value: Option<i32>| value.map(|v| v.to_string()).unwrap_or_default();
+    let records = partitions.into_iter().map(|partition| {
+        let metadata = partition.metadata;
+        let tags = partition
+            .tags
+            .into_iter()
+            .map(|tag| tag.name)
+            .collect::<Vec<_>>()
+            .join(", ");
+        vec![
+            partition.title,
+            partition.full_name,
+            partition.name,
+            text(metadata.instrumentation),
+            text(metadata.music_key),
+            text(metadata.tempo),
+            number(metadata.difficulty.map(i32::from)),
+            metadata
+                .duration_seconds
+                .map(format_duration)
+                .unwrap_or_default(),
+            text(metadata.opus),
+            text(metadata.publisher),
+            number(metadata.edition_year),
+            text(metadata.notes),
+            tags,
+        ]
+    });
+    let header: Vec<&str> = PARTITION_COLUMNS.iter().map(|(_, label)| *label).collect();
+
+    csv_response("partitions.csv", write_csv(&header, records)?)
+}
+
+///
+/// écrit l'en-tête et les lignes, séparées par des points-virgules
+/// le fichier commence par un BOM pour qu'un tableur reconnaisse l'UTF-8
+///
+fn write_csv(
+    header: &[&str],
+    records: impl Iterator<Item = Vec<String>>,
+) -> anyhow::Result<Vec<u8>> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b';')
+        .from_writer(b"\xEF\xBB\xBF".to_vec());
+    writer.write_record(header)?;
+    for record in records {
+        writer.write_record(&record)?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| anyhow::anyhow!("écriture du CSV : {}", e.error()))
+}
+
+fn csv_response(file_name: &str, content: Vec<u8>) -> Result<Response, AppError> {
+    let mut response = content.into_response();
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/csv; charset=utf-8"),
+    );
+    let disposition = format!("attachment; filename=\"{}\"", file_name);
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&disposition).map_err(anyhow::Error::from)?,
+    );
+    Ok(response)
+}
+
+//*******************************************************************************
+// Import
+//
+
+///
+/// Shows the form to send a CSV file of partitions
+///
+pub async fn import_form_hdl(
+    Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
+) -> Result<Html<String>, AppError> {
+    render_import(templates, &current_user, None)
+}
+
+///
+/// Reads the CSV file (field "file" of a multipart form) and shows each row
+/// with its errors ; nothing is recorded yet
+///
+pub async fn preview_import_hdl(
+    Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
+    Extension(ref pool): Extension<PgPool>,
+    mut multipart: Multipart,
+) -> Result<Html<String>, AppError> {
+    let mut content: Vec<u8> = Vec::new();
+    let mut create_missing = false;
+    let mut file_error: Option<String> = None;
+
+    loop {
+        let mut field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => {
+                tracing::info!("erreur multipart : {}", e);
+                file_error = Some("envoi interrompu".to_string());
+                break;
+            }
+        };
+        let name = field.name().map(str::to_string);
+        match name.as_deref() {
+            Some("create_missing") => create_missing = true,
+            Some("file") => loop {
+                match field.chunk().await {
+                    Ok(Some(chunk)) if content.len() + chunk.len() <= MAX_CSV_BYTES => {
+                        content.extend_from_slice(&chunk)
+                    }
+                    Ok(Some(_)) => {
+                        file_error = Some("fichier trop grand".to_string());
+                        break;
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        tracing::info!("erreur multipart : {}", e);
+                        file_error = Some("envoi interrompu".to_string());
+                        break;
+                    }
+                }
+            },
+            _ => continue,
+        }
+        if file_error.is_some() {
+            break;
+        }
+    }
+
+    let report = match (file_error, String::from_utf8(content)) {
+        (Some(error), _) => ImportReport {
+            file_error: Some(error),
+            ..Default::default()
+        },
+        (None, Err(_)) => ImportReport {
+            file_error: Some("le fichier doit être encodé en UTF-8".to_string()),
+            ..Default::default()
+        },
+        (None, Ok(text)) => check_import(text, create_missing, pool).await?,
+    };
+
+    render_import(templates, &current_user, Some(&report))
+}
+
+///
+/// Imports the file confirmed from the preview
+/// the file is checked again (size included) : with one error, nothing is recorded
+/// and the preview is shown again
+///
+pub async fn import_partitions_hdl(
+    Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    mut flash: Flash,
+    Form(form): Form<ImportForm>,
+) -> Result<Response, AppError> {
+    // le fichier revient de l'aperçu : la même limite que dans preview_import_hdl
+    if form.csv.len() > MAX_CSV_BYTES {
+        let report = ImportReport {
+            file_error: Some("fichier trop grand".to_string()),
+            ..Default::default()
+        };
+        let body = render_import(templates, &current_user, Some(&report))?;
+        return Ok((StatusCode::PAYLOAD_TOO_LARGE, body).into_response());
+    }
+
+    let report = check_import(form.csv, form.create_missing.is_some(), pool).await?;
+    if !report.is_valid() {
+        let body = render_import(templates, &current_user, Some(&report))?;
+        return Ok((StatusCode::UNPROCESSABLE_ENTITY, body).into_response());
+    }
+
+    let (level, message) = match import_partitions(&report.rows, &actor, pool).await {
+        Ok(count) => (
+            axum_flash::Level::Success,
+            format!("Import terminé : {} partition(s) ajoutée(s)", count),
+        ),
+        Err(e) => {
+            tracing::error!("db : import CSV annulé : {}", e);
+            (
+                axum_flash::Level::Error,
+                String::from("Erreur : import annulé, aucune partition ajoutée"),
+            )
+        }
+    };
+    Ok(partition_response(&mut flash, level, message).into_response())
+}
+
+fn render_import(
+    templates: &Tera,
+    current_user: &MaybeUser,
+    report: Option<&ImportReport>,
+) -> Result<Html<String>, AppError> {
+    let title = "Importer des partitions";
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
+    ctx.insert("columns", &PARTITION_COLUMNS);
+    if let Some(report) = report {
+        ctx.insert("report", report);
+        ctx.insert("valid", &report.is_valid());
+    }
+
+    let body = templates
+        .render("import_partitions.html", &ctx)
+        .map_err(AppError::Tera)?;
+
+    Ok(Html(body))
+}
+
+///
+/// lit le fichier et vérifie chaque ligne :
+/// champs obligatoires, nombres et durées, musicien et genre connus
+/// (ou à créer si create_missing) ; une partition déjà au catalogue
+/// (même titre, même compositeur) est signalée sans bloquer l'import
+///
+async fn check_import(
+    text: String,
+    create_missing: bool,
+    pool: &PgPool,
+) -> sqlx::Result<ImportReport> {
+    let mut report = ImportReport {
+        create_missing,
+        ..Default::default()
+    };
+    match parse_partitions_csv(&text) {
+        Ok(rows) if rows.is_empty() => {
+            report.file_error = Some("le fichier ne contient aucune partition".to_string())
+        }
+        Ok(rows) => report.rows = rows,
+        Err(error) => report.file_error = Some(error),
+    }
+    report.csv = text;
+
+    // un même nom n'est cherché qu'une fois
+    let mut person_ids: HashMap<String, Option<i32>> = HashMap::new();
+    let mut genre_ids: HashMap<String, Option<i32>> = HashMap::new();
+
+    for row in report.rows.iter_mut() {
+        if !row.composer.is_empty() {
+            row.person_id = match person_ids.get(&row.composer) {
+                Some(id) => *id,
+                None => {
+                    let id = find_person_id(&row.composer, pool).await?;
+                    person_ids.insert(row.composer.clone(), id);
+                    id
+                }
+            };
+            if row.person_id.is_none() {
+                let name = row.composer.clone();
+                missing(row, create_missing, "musicien", name);
+            }
+        }
+        if !row.genre.is_empty() {
+            row.genre_id = match genre_ids.get(&row.genre) {
+                Some(id) => *id,
+                None => {
+                    let id = find_genre_id(&row.genre, pool).await?;
+                    genre_ids.insert(row.genre.clone(), id);
+                    id
+                }
+            };
+            if row.genre_id.is_none() {
+                let name = row.genre.clone();
+                missing(row, create_missing, "genre", name);
+            }
+        }
+        if row.person_id.is_some() && !row.title.is_empty() {
+            for duplicate in find_duplicate_partitions(&row.title, &row.composer, pool).await? {
+                row.warnings.push(format!(
+                    "déjà au catalogue : {} ({})",
+                    duplicate.title, duplicate.full_name
+                ));
+            }
+        }
+    }
+    report.error_count = report
+        .rows
+        .iter()
+        .filter(|row| !row.errors.is_empty())
+        .count();
+
+    Ok(report)
+}
+
+fn missing(row: &mut ImportRow, create_missing: bool, what: &str, name: String) {
+    if create_missing {
+        row.warnings.push(format!("{} à créer : {}", what, name));
+    } else {
+        row.errors.push(format!("{} inconnu : {}", what, name));
+    }
+}
+
+///
+/// découpe le fichier en lignes
+/// le séparateur (";" ou ",") est celui qui revient le plus dans l'en-tête
+/// Err : le fichier entier est illisible (en-tête incorrect)
+///
+fn parse_partitions_csv(text: &str) -> Result<Vec<ImportRow>, String> {
+    let text = text.trim_start_matches('\u{feff}');
+    let first_line = text.lines().next().unwrap_or_default();
+    let delimiter = if first_line.matches(',').count() > first_line.matches(';').count() {
+        b','
+    } else {
+        b';'
+    };
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(text.as_bytes());
+
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("en-tête illisible : {}", e))?
+        .clone();
+    let mut columns: HashMap<&str, usize> = HashMap::new();
+    for (index, header) in headers.iter().enumerate() {
+        let header = header.to_lowercase();
+        match PARTITION_COLUMNS
+            .iter()
+            .find(|(key, label)| *key == header || *label == header)
+        {
+            Some((key, _)) => {
+                columns.insert(*key, index);
+            }
+            None if header.is_empty() => (),
+            None => return Err(format!("colonne inconnue : {}", header)),
+        }
+    }
+    for (key, label) in &PARTITION_COLUMNS[..3] {
+        if !columns.contains_key(*key) {
+            return Err(format!("colonne obligatoire absente : {}", label));
+        }
+    }
+
+    let mut rows = Vec::new();
+    for result in reader.records() {
+        match result {
+            Ok(record) => rows.push(parse_row(&record, &columns)),
+            Err(e) => rows.push(ImportRow {
+                line: e.position().map(|p| p.line()).unwrap_or_default(),
+                errors: vec![format!("ligne illisible : {}", e)],
+                ..Default::default()
+            }),
+        }
+    }
+    Ok(rows)
+}
+
+///
+/// une ligne du fichier ; les erreurs sont notées dans la ligne
+///
+fn parse_row(record: &StringRecord, columns: &HashMap<&str, usize>) -> ImportRow {
+    let field = |key: &str| {
+        columns
+            .get(key)
+            .and_then(|&index| record.get(index))
+            .filter(|value| !value.is_empty())
+    };
+    let text = |key: &str| field(key).map(str::to_string);
+
+    let mut row = ImportRow {
+        line: record.position().map(|p| p.line()).unwrap_or_default(),
+        title: text("title").unwrap_or_default(),
+        composer: text("composer").unwrap_or_default(),
+        genre: text("genre").unwrap_or_default(),
+        ..Default::default()
+    };
+    for (key, label) in &PARTITION_COLUMNS[..3] {
+        if field(*key).is_none() {
+            row.errors.push(format!("{} manquant", label));
+        }
+    }
+    // les mêmes règles que les formulaires ; les noms sont gardés nettoyés
+    if field("title").is_some() {
+        match PartitionTitle::parse(&row.title) {
+            Ok(title) => row.title = title.as_ref().to_string(),
+            Err(error) => row.errors.push(error),
+        }
+    }
+    if field("composer").is_some() {
+        match PersonName::parse(&row.composer) {
+            Ok(name) => row.composer = name.as_ref().to_string(),
+            Err(error) => row.errors.push(error),
+        }
+    }
+    if field("genre").is_some() {
+        match GenreName::parse(&row.genre) {
+            Ok(name) => row.genre = name.as_ref().to_string(),
+            Err(error) => row.errors.push(error),
+        }
+    }
+
+    row.metadata.instrumentation = text("instrumentation");
+    row.metadata.music_key = text("key");
+    row.metadata.tempo = text("tempo");
+    row.metadata.opus = text("opus");
+    row.metadata.publisher = text("publisher");
+    row.metadata.notes = text("notes");
+
+    if let Some(value) = field("difficulty") {
+        match value.parse::<i16>() {
+            Ok(level) if (1..=5).contains(&level) => row.metadata.difficulty = Some(level),
+            _ => row
+                .errors
+                .push(format!("difficulté incorrecte (de 1 à 5) : {}", value)),
+        }
+    }
+    if let Some(value) = field("duration") {
+        match parse_duration(value) {
+            Some(seconds) => row.metadata.duration_seconds = Some(seconds),
+            None => row.errors.push(format!("durée incorrecte : {}", value)),
+        }
+    }
+    if let Some(value) = field("edition_year") {
+        match value.parse::<i32>() {
+            Ok(year) if (1000..=9999).contains(&year) => row.metadata.edition_year = Some(year),
+            _ => row.errors.push(format!("année incorrecte : {}", value)),
+        }
+    }
+
+    // "Noël, mariage" : les étiquettes sont séparées par des virgules
+    if let Some(value) = field("tags") {
+        for tag in value
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+        {
+            if !row
+                .tags
+                .iter()
+                .any(|t| t.to_lowercase() == tag.to_lowercase())
+            {
+                row.tags.push(tag.to_string());
+            }
+        }
+    }
+
+    row
+}
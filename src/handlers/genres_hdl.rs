@@ -1,26 +1,52 @@
 //! src/handlers/genres_hdl.rs
 
-use axum::extract::{Extension, Form, Path};
+use axum::extract::{Extension, Form, Path, Query};
 use axum::http::{HeaderMap, StatusCode};
-use axum::response::Html;
+use axum::response::{Html, IntoResponse, Response};
 use axum_flash::{Flash, IncomingFlashes};
 
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use tera::Tera;
 
+use crate::auth::current_user::MaybeUser;
 use crate::db::genres::*;
+use crate::db::partitions::find_partition_by_id;
 
 use crate::error::AppError;
-use crate::flash::genre_response;
-use crate::globals::{get_static_vec_genres, set_static_vec_genres};
-use crate::models::genre::Genre;
+use crate::flash::{duplicates_response, genre_response, partition_page_response};
+use crate::handlers::helpers_hdl::{conflict_page, invalid_form_page};
+use crate::models::audit::Actor;
+use crate::models::deletion::{DeleteForm, Deletion};
+use crate::models::genre::{Genre, GenreName};
+use crate::models::merge::{group_pairs, DuplicatesQuery, MergeForm};
+use crate::models::pagination::ListParams;
+use crate::models::version::{required_version, ConflictField, ConflictPage, Update};
+use crate::pdf::{pdf_response, PdfTable};
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Demande {
     pub name: String,
 }
 
+///
+/// formulaire de retrait d'un genre d'une partition
+///
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GenreKey {
+    pub genre_id: i32,
+}
+
+///
+/// paramètres de la page d'impression
+/// reprend la recherche affichée par l'utilisateur : /genres/print?name=Ja
+/// sans paramètre, toute la liste est imprimée
+///
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct PrintQuery {
+    pub name: Option<String>,
+}
+
 //***********************************************************************************
 // CRUD Operations
 //
@@ -28,46 +54,193 @@ pub struct Demande {
 ///
 /// Create a new genre in the genres table
 /// and shows the list of all genres
+/// an invalid name, or one already used, is shown again next to the field with its error
 ///
-/// Returns GenreResponse or AppError
+/// Returns GenreResponse, the page with the errors or AppError
 ///
 
 pub async fn create_genre_hdl(
+    Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
     Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
     form: Form<Genre>,
     mut flash: Flash,
     //mut cookies: Cookies,
-) -> Result<(StatusCode, HeaderMap), AppError> {
+) -> Result<Response, AppError> {
     let genre = form.0;
-    let new_genre = add_genre(pool, genre).await?;
+    let name = match GenreName::parse(&genre.name) {
+        Ok(name) => name,
+        Err(error) => {
+            let ctx = genres_context(&current_user, pool, &ListParams::default(), "").await?;
+            let error = AppError::unprocessable_entity([("name", error)]);
+            return invalid_form_page(templates, "genres.html", ctx, error, &genre);
+        }
+    };
+    let new_genre = match add_genre(pool, name, &actor).await.map_err(AppError::from) {
+        Ok(new_genre) => new_genre,
+        Err(AppError::Duplicate(error)) => {
+            let ctx = genres_context(&current_user, pool, &ListParams::default(), "").await?;
+            let error = AppError::unprocessable_entity([("name", error)]);
+            return invalid_form_page(templates, "genres.html", ctx, error, &genre);
+        }
+        Err(error) => return Err(error),
+    };
     let message = format!("Genre ajouté : {}", new_genre.name);
+    let level = axum_flash::Level::Success;
 
-    Ok(genre_response(&mut flash, message))
+    Ok(genre_response(&mut flash, level, message).into_response())
 }
 
+///
+/// Modifies a genre in the genres table
+/// or shows the conflict page when the genre has been modified meanwhile
+/// an invalid name, or one already used, is shown again in a form
+/// above the list with its error
+///
 pub async fn update_genre_hdl(
+    Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
     Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
     Path(id): Path<i32>,
-    form: Form<Demande>,
+    form: Form<Genre>,
     mut flash: Flash,
     //mut cookies: Cookies,
-) -> Result<(StatusCode, HeaderMap), AppError> {
-    let updated_genre = form.0;
-    let genre_name = updated_genre.name;
-    let genre = update_genre(id, genre_name, pool).await?;
-    let message = format!("Genre modifié avec succès : {:?}", genre.name).to_owned();
-    Ok(genre_response(&mut flash, message))
+) -> Result<Response, AppError> {
+    let mut updated_genre = form.0;
+    // l'id distingue le formulaire de modification de celui d'ajout dans genres.html
+    updated_genre.id = Some(id);
+    let genre_name = match GenreName::parse(&updated_genre.name) {
+        Ok(name) => name,
+        Err(error) => {
+            let ctx = genres_context(&current_user, pool, &ListParams::default(), "").await?;
+            let error = AppError::unprocessable_entity([("name", error)]);
+            return invalid_form_page(templates, "genres.html", ctx, error, &updated_genre);
+        }
+    };
+    let version = required_version(updated_genre.version)?;
+    let update = match update_genre(id, genre_name, version, &actor, pool)
+        .await
+        .map_err(AppError::from)
+    {
+        Ok(update) => update,
+        Err(AppError::Duplicate(error)) => {
+            let ctx = genres_context(&current_user, pool, &ListParams::default(), "").await?;
+            let error = AppError::unprocessable_entity([("name", error)]);
+            return invalid_form_page(templates, "genres.html", ctx, error, &updated_genre);
+        }
+        Err(error) => return Err(error),
+    };
+    match update {
+        Update::Saved(genre) => {
+            let message = format!("Genre modifié avec succès : {:?}", genre.name).to_owned();
+            let level = axum_flash::Level::Success;
+            Ok(genre_response(&mut flash, level, message).into_response())
+        }
+        Update::Conflict { current } => {
+            let conflict = ConflictPage {
+                what: String::from("le genre"),
+                action: format!("/genres/{}", id),
+                back: String::from("/genres"),
+                version: current.version,
+                fields: vec![ConflictField::new(
+                    "name",
+                    "Nom",
+                    updated_genre.name,
+                    current.name,
+                )],
+            };
+            conflict_page(templates, &current_user, &conflict)
+        }
+    }
+}
+
+///
+/// Shows the confirmation page before deleting a genre :
+/// the partitions which depend on it and the genres they can be given to
+///
+/// Returns a HTML Page or AppError
+///
+pub async fn confirm_delete_genre_hdl(
+    Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
+    Extension(ref pool): Extension<PgPool>,
+    Path(id): Path<i32>,
+) -> Result<Html<String>, AppError> {
+    let genre = find_genre_by_id(id, pool).await.map_err(not_found)?;
+    let dependents = list_genre_dependents(id, pool).await?;
+    let candidates: Vec<(i32, String)> = list_genres(pool)
+        .await?
+        .into_iter()
+        .filter_map(|g| {
+            g.id.filter(|&other| other != id)
+                .map(|other| (other, g.name))
+        })
+        .collect();
+
+    let title = "Effacer un genre";
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
+    ctx.insert("kind", "le genre");
+    ctx.insert("name", &genre.name);
+    ctx.insert("action", &format!("/genres/delete/{}", id));
+    ctx.insert("back", "/genres");
+    ctx.insert("dependents", &dependents);
+    ctx.insert("candidates", &candidates);
+
+    let body = templates
+        .render("confirm_delete.html", &ctx)
+        .map_err(|err| AppError::Tera(err))?;
+
+    Ok(Html(body))
 }
 
+///
+/// Moves a genre to the trash (/trash/genres) after giving its partitions
+/// to the genre chosen on the confirmation page ; refused when partitions still depend on it
+///
 pub async fn delete_genre_hdl(
     Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
     Path(id): Path<i32>,
     mut flash: Flash,
+    Form(form): Form<DeleteForm>,
 ) -> Result<(StatusCode, HeaderMap), AppError> {
-    let genre_nom = delete_genre(id, pool).await?;
-    let message = format!("Genre effacé : {}", genre_nom).to_owned();
+    let (level, message) = match delete_genre(id, form.reassign_to, &actor, pool)
+        .await
+        .map_err(not_found)?
+    {
+        Deletion::Deleted { name, reassigned } if reassigned > 0 => (
+            axum_flash::Level::Success,
+            format!(
+                "Genre mis à la corbeille : {} ; {} partition(s) réaffectée(s)",
+                name, reassigned
+            ),
+        ),
+        Deletion::Deleted { name, .. } => (
+            axum_flash::Level::Success,
+            format!("Genre mis à la corbeille : {}", name),
+        ),
+        Deletion::Refused { name, dependents } => (
+            axum_flash::Level::Error,
+            format!(
+                "Erreur : genre pas effacé, {} partition(s) dépendent de {}",
+                dependents, name
+            ),
+        ),
+    };
+
+    Ok(genre_response(&mut flash, level, message))
+}
 
-    Ok(genre_response(&mut flash, message))
+fn not_found(err: sqlx::Error) -> AppError {
+    match err {
+        sqlx::Error::RowNotFound => AppError::NotFound,
+        e => AppError::from(e),
+    }
 }
 
 //*******************************************************************************
@@ -76,12 +249,15 @@ pub async fn delete_genre_hdl(
 
 ///
 /// Shows the page with the list of genres
+/// one page at a time : /genres?page=2&per_page=50&sort=name&dir=desc
 ///
 /// Returns a HTML Page or AppError
 ///
 pub async fn list_genres_hdl(
     Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
     Extension(ref pool): Extension<PgPool>,
+    Query(params): Query<ListParams>,
     flash: IncomingFlashes,
 ) -> Result<Html<String>, AppError> {
     let flash = flash
@@ -91,16 +267,7 @@ pub async fn list_genres_hdl(
         .join(", ");
     tracing::info!("flash : {}", flash);
 
-    //let genres = list_genres(pool).await?;
-    set_static_vec_genres(list_genres(pool).await?);
-    let genres = get_static_vec_genres();
-
-    let title = "Gestion des Genres";
-
-    let mut ctx = tera::Context::new();
-    ctx.insert("title", &title);
-    ctx.insert("genres", &genres);
-    ctx.insert("flash", &flash);
+    let ctx = genres_context(&current_user, pool, &params, &flash).await?;
 
     let body = templates
         .render("genres.html", &ctx)
@@ -109,24 +276,52 @@ pub async fn list_genres_hdl(
     Ok(Html(body))
 }
 
+///
+/// le contexte de la page des genres (genres.html) :
+/// une page de la liste avec le message flash
+/// (partagé avec create_genre_hdl qui y réaffiche un nom refusé)
+///
+async fn genres_context(
+    current_user: &MaybeUser,
+    pool: &PgPool,
+    params: &ListParams,
+    flash: &str,
+) -> Result<tera::Context, AppError> {
+    let page = list_genres_page(pool, params).await?;
+
+    let title = "Gestion des Genres";
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
+    ctx.insert("genres", &page.items);
+    ctx.insert("page", &page);
+    ctx.insert("flash", &flash);
+    Ok(ctx)
+}
+
 ///
 /// Shows a printable list of Genres
+/// the search shown on the page is passed in the query string
 ///
 /// Returns a HTML Page or AppError
 ///
 pub async fn print_list_genres_hdl(
-    //Extension(ref templates): Extension<Tera>,
-    //templates: Extension<Arc<Tera>>,
     templates: Extension<Tera>,
-    //Extension(ref pool): Extension<PgPool>,
+    current_user: MaybeUser,
+    Extension(ref pool): Extension<PgPool>,
+    Query(query): Query<PrintQuery>,
 ) -> Result<Html<String>, AppError> {
-    //let genres = list_genres(pool).await?;
-    let genres = get_static_vec_genres();
+    let genres = match query.name {
+        Some(name) if !name.is_empty() => find_genre_by_name(name, pool).await?,
+        _ => list_genres(pool).await?,
+    };
 
     let title = "Liste des Genres";
 
     let mut ctx = tera::Context::new();
     ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
     ctx.insert("genres", &genres);
 
     let body = templates
@@ -136,6 +331,126 @@ pub async fn print_list_genres_hdl(
     Ok(Html(body))
 }
 
+///
+/// The printable list of genres as a PDF file (A4)
+/// with the same search as the HTML page : /genres/print.pdf?name=Ja
+///
+/// Returns the PDF or AppError
+///
+pub async fn print_pdf_genres_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Query(query): Query<PrintQuery>,
+) -> Result<Response, AppError> {
+    let genres = match query.name {
+        Some(name) if !name.is_empty() => find_genre_by_name(name, pool).await?,
+        _ => list_genres(pool).await?,
+    };
+
+    let mut table = PdfTable::new("Liste des Genres", &[("Genre", 1.0)]);
+    for genre in &genres {
+        table.row(&[&genre.name]);
+    }
+    table.line(&format!("{} genre(s)", genres.len()));
+
+    Ok(pdf_response("genres.pdf", table.finish()))
+}
+
+//****************************************************************************************
+// Duplicates
+//
+
+///
+/// Shows the groups of genres whose names look alike
+/// to choose the one to keep and merge the others into it
+/// /genres/duplicates?threshold=0.6
+///
+/// Returns a HTML Page or AppError
+///
+pub async fn list_duplicate_genres_hdl(
+    Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
+    Extension(ref pool): Extension<PgPool>,
+    Query(query): Query<DuplicatesQuery>,
+    flash: IncomingFlashes,
+) -> Result<Html<String>, AppError> {
+    let flash = flash
+        .into_iter()
+        .map(|(level, text)| format!("{:?}: {}", level, text))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let threshold = query.threshold();
+    let groups = group_pairs(find_similar_genres(threshold, pool).await?);
+
+    let title = "Doublons des Genres";
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
+    ctx.insert("groups", &groups);
+    ctx.insert("threshold", &threshold);
+    ctx.insert("base_url", "/genres");
+    ctx.insert("flash", &flash);
+
+    let body = templates
+        .render("duplicates.html", &ctx)
+        .map_err(|err| AppError::Tera(err))?;
+
+    Ok(Html(body))
+}
+
+///
+/// Merges the checked genres into the one to keep
+/// survivor=3&duplicates=7&duplicates=12
+///
+/// Returns to the duplicates report with a flash message
+///
+pub async fn merge_genres_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    mut flash: Flash,
+    Form(pairs): Form<Vec<(String, String)>>,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let form = match MergeForm::from_pairs(&pairs) {
+        Ok(form) => form,
+        Err(message) => {
+            let message = format!("Fusion impossible : {}", message);
+            let level = axum_flash::Level::Error;
+            return Ok(duplicates_response(
+                &mut flash,
+                level,
+                message,
+                "/genres/duplicates",
+            ));
+        }
+    };
+
+    let (level, message) = match merge_genres(form.survivor, &form.duplicates, &actor, pool).await {
+        Ok(result) => (
+            axum_flash::Level::Success,
+            format!(
+                "Fusionné(s) dans {} : {} ; {} partition(s) réaffectée(s)",
+                result.survivor,
+                result.merged.join(", "),
+                result.partitions
+            ),
+        ),
+        Err(e) => {
+            tracing::error!("db : genres not merged : {}", e);
+            (
+                axum_flash::Level::Error,
+                String::from("Erreur : genres pas fusionnés"),
+            )
+        }
+    };
+    Ok(duplicates_response(
+        &mut flash,
+        level,
+        message,
+        "/genres/duplicates",
+    ))
+}
+
 //****************************************************************************************
 // Functions to find genres by different criteria
 //
@@ -147,22 +462,25 @@ pub async fn print_list_genres_hdl(
 ///
 pub async fn find_genre_by_name_hdl(
     Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
     Extension(ref pool): Extension<PgPool>,
     form: Form<Demande>,
 ) -> Result<Html<String>, AppError> {
     let demande = form.0;
     tracing::debug!("name : {:?}", demande);
 
-    let name = demande.name;
-    //let genres = find_genre_by_name(name, pool).await?;
-    set_static_vec_genres(find_genre_by_name(name, pool).await?);
-    let genres = get_static_vec_genres();
+    let genres = find_genre_by_name(demande.name.clone(), pool).await?;
+    let search = PrintQuery {
+        name: Some(demande.name),
+    };
 
     let title = "Genre(s) trouvé(s)";
 
     let mut ctx = tera::Context::new();
     ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
     ctx.insert("genres", &genres);
+    ctx.insert("search", &search);
 
     let body = templates
         .render("genres.html", &ctx)
@@ -170,3 +488,82 @@ pub async fn find_genre_by_name_hdl(
 
     Ok(Html(body))
 }
+
+//****************************************************************************************
+// Genres des partitions
+// les formulaires sont sur la page de la partition (/partitions/show/:id)
+//
+
+///
+/// Adds a genre (chosen by its exact name) to the partition
+///
+/// Returns to the page of the partition with a flash message
+///
+pub async fn add_partition_genre_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    Path(id): Path<i32>,
+    form: Form<Demande>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let demande = form.0;
+
+    find_partition_by_id(id, pool).await?;
+    let genres = find_genre_by_name(demande.name.clone(), pool).await?;
+    let genre = match genres.iter().find(|genre| genre.name == demande.name) {
+        Some(genre) => genre,
+        None => {
+            let message = format!("Erreur : genre inconnu : {}", demande.name);
+            let level = axum_flash::Level::Error;
+            return Ok(partition_page_response(&mut flash, level, message, id));
+        }
+    };
+
+    let (level, message) = if add_partition_genre(id, genre.id.unwrap(), &actor, pool).await? {
+        (
+            axum_flash::Level::Success,
+            format!("Genre ajouté : {}", genre.name),
+        )
+    } else {
+        (
+            axum_flash::Level::Warning,
+            format!("La partition a déjà le genre {}", genre.name),
+        )
+    };
+    Ok(partition_page_response(&mut flash, level, message, id))
+}
+
+///
+/// Removes a genre from the partition
+/// the main genre stays : it is changed with the form of the partition
+///
+/// Returns to the page of the partition with a flash message
+///
+pub async fn delete_partition_genre_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    Path(id): Path<i32>,
+    form: Form<GenreKey>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let genre_id = form.0.genre_id;
+
+    let partition = find_partition_by_id(id, pool).await?;
+    if partition.genre_id == genre_id {
+        let message = String::from(
+            "Erreur : le genre principal se change avec le formulaire de modification",
+        );
+        let level = axum_flash::Level::Error;
+        return Ok(partition_page_response(&mut flash, level, message, id));
+    }
+
+    let (level, message) = if delete_partition_genre(id, genre_id, &actor, pool).await? {
+        (axum_flash::Level::Success, String::from("Genre retiré"))
+    } else {
+        (
+            axum_flash::Level::Warning,
+            String::from("La partition n'avait pas ce genre"),
+        )
+    };
+    Ok(partition_page_response(&mut flash, level, message, id))
+}
@@ -1,29 +1,41 @@
 //! src/handlers/helpers_hdl.rs
 
+use crate::auth::current_user::MaybeUser;
 use crate::AppError;
 use axum::body::Bytes;
 use axum::extract::Extension;
 use axum::http::{header, StatusCode, Uri};
-use axum::response::{Html, IntoResponse};
+use axum::response::{Html, IntoResponse, Response};
+use serde::Serialize;
 use tera::Tera;
 
+use crate::models::version::ConflictPage;
+
 // Il faut une fonction root qui ramène à la racine
 // sinon problème. Sauf si on utilise Redirect
 //
-pub async fn root(Extension(ref templates): Extension<Tera>) -> Result<Html<String>, AppError> {
+pub async fn root(
+    Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
+) -> Result<Html<String>, AppError> {
     let title = "Start";
     let mut ctx = tera::Context::new();
     ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
     let body = templates
         .render("start.html", &ctx)
         .map_err(|e| AppError::Tera(e))?;
     Ok(Html(body))
 }
 
-pub async fn about(Extension(ref templates): Extension<Tera>) -> Result<Html<String>, AppError> {
+pub async fn about(
+    Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
+) -> Result<Html<String>, AppError> {
     let title = "A propos de ...";
     let mut ctx = tera::Context::new();
     ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
     let body = templates
         .render("about.html", &ctx)
         .map_err(|e| AppError::Tera(e))?;
@@ -32,12 +44,14 @@ pub async fn about(Extension(ref templates): Extension<Tera>) -> Result<Html<Str
 
 pub async fn handler_404(
     Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
     uri: Uri,
 ) -> Result<Html<String>, (StatusCode, &'static str)> {
     let title = "Erreur de routing";
     let origin = uri.path();
     let mut ctx = tera::Context::new();
     ctx.insert("title", title);
+    ctx.insert("current_user", &current_user.name());
     ctx.insert("uri", origin);
     let body = templates.render("error/404.html", &ctx).map_err(|_| {
         (
@@ -48,6 +62,51 @@ pub async fn handler_404(
     Ok(Html(body))
 }
 
+///
+/// la page de conflit (409) d'une modification refusée parce que la ligne
+/// a été modifiée entre-temps : les deux versions côte à côte
+///
+pub fn conflict_page(
+    templates: &Tera,
+    current_user: &MaybeUser,
+    conflict: &ConflictPage,
+) -> Result<Response, AppError> {
+    let title = "Conflit de modification";
+    let mut ctx = tera::Context::new();
+    ctx.insert("title", title);
+    ctx.insert("current_user", &current_user.name());
+    ctx.insert("conflict", conflict);
+    let body = templates
+        .render("conflict.html", &ctx)
+        .map_err(|e| AppError::Tera(e))?;
+    Ok((StatusCode::CONFLICT, Html(body)).into_response())
+}
+
+///
+/// réaffiche (422) la page d'un formulaire refusé par la validation :
+/// errors, les messages de AppError::UnprocessableEntity par champ,
+/// à côté des champs ; input, les valeurs saisies
+/// ctx : le reste de la page ; les autres erreurs sont renvoyées telles quelles
+///
+pub fn invalid_form_page<T: Serialize>(
+    templates: &Tera,
+    template: &str,
+    mut ctx: tera::Context,
+    error: AppError,
+    input: &T,
+) -> Result<Response, AppError> {
+    let errors = match error {
+        AppError::UnprocessableEntity { errors } => errors,
+        error => return Err(error),
+    };
+    ctx.insert("errors", &errors);
+    ctx.insert("input", input);
+    let body = templates
+        .render(template, &ctx)
+        .map_err(|e| AppError::Tera(e))?;
+    Ok((StatusCode::UNPROCESSABLE_ENTITY, Html(body)).into_response())
+}
+
 pub async fn shutdown_signal() {
     tokio::signal::ctrl_c()
         .await
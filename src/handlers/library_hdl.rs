@@ -0,0 +1,249 @@
+//! src/handlers/library_hdl.rs
+//!
+//! Bibliothèque : exemplaires imprimés des partitions et prêts aux membres
+//! les formulaires des exemplaires et des prêts sont sur la page de la partition
+//! (/partitions/show/:id) ; la liste des retards et "mes emprunts" sous /loans
+
+use axum::extract::{Extension, Form, Path};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::Html;
+use axum_flash::Flash;
+
+use sqlx::PgPool;
+use tera::Tera;
+
+use crate::auth::current_user::CurrentUser;
+use crate::db::library::*;
+use crate::db::partitions::find_partition_by_id;
+use crate::db::users::find_user_by_name;
+use crate::error::AppError;
+use crate::flash::partition_page_response;
+use crate::models::library::{CopyChange, CopyForm, LoanForm};
+
+//***********************************************************************************
+// Exemplaires
+//
+
+///
+/// Adds a batch of copies to the partition
+///
+/// Returns to the page of the partition with a flash message
+///
+pub async fn add_copy_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Path(id): Path<i32>,
+    form: Form<CopyForm>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let copy = form.0;
+    if copy.quantity < 1 {
+        let message = String::from("Erreur : il faut au moins un exemplaire");
+        let level = axum_flash::Level::Error;
+        return Ok(partition_page_response(&mut flash, level, message, id));
+    }
+
+    find_partition_by_id(id, pool).await?;
+    let copy = add_copy(id, copy.quantity, copy.location, copy.condition, pool).await?;
+    let message = format!("{} exemplaire(s) ajouté(s)", copy.quantity);
+    let level = axum_flash::Level::Success;
+    Ok(partition_page_response(&mut flash, level, message, id))
+}
+
+///
+/// Modifies the number, the location or the condition of a batch of copies
+/// the number cannot go below the copies on loan
+///
+pub async fn update_copy_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Path(copy_id): Path<i32>,
+    form: Form<CopyForm>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let form = form.0;
+    let id = find_copy_by_id(copy_id, pool).await?.partition_id;
+
+    if form.quantity < 1 {
+        let message = String::from("Erreur : il faut au moins un exemplaire");
+        let level = axum_flash::Level::Error;
+        return Ok(partition_page_response(&mut flash, level, message, id));
+    }
+
+    // les exemplaires prêtés sont comptés sous verrou par db::library::update_copy
+    let (level, message) =
+        match update_copy(copy_id, form.quantity, form.location, form.condition, pool).await? {
+            CopyChange::Done(_) => (
+                axum_flash::Level::Success,
+                String::from("Exemplaires modifiés"),
+            ),
+            CopyChange::OnLoan(on_loan) => (
+                axum_flash::Level::Error,
+                format!(
+                    "Erreur : le nombre d'exemplaires doit être au moins {} (exemplaires prêtés)",
+                    on_loan
+                ),
+            ),
+        };
+    Ok(partition_page_response(&mut flash, level, message, id))
+}
+
+///
+/// Deletes a batch of copies
+/// refused while some of them are on loan
+///
+pub async fn delete_copy_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Path(copy_id): Path<i32>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let id = find_copy_by_id(copy_id, pool).await?.partition_id;
+
+    let (level, message) = match delete_copy(copy_id, pool).await? {
+        CopyChange::Done(copy) => (
+            axum_flash::Level::Success,
+            format!("{} exemplaire(s) effacé(s)", copy.quantity),
+        ),
+        CopyChange::OnLoan(on_loan) => (
+            axum_flash::Level::Error,
+            format!("Erreur : {} exemplaire(s) de ce lot sont prêtés", on_loan),
+        ),
+    };
+    Ok(partition_page_response(&mut flash, level, message, id))
+}
+
+//***********************************************************************************
+// Prêts
+//
+
+///
+/// Lends copies of a batch to a member (given by the user name)
+///
+/// Returns to the page of the partition with a flash message
+///
+pub async fn add_loan_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Path(copy_id): Path<i32>,
+    form: Form<LoanForm>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let form = form.0;
+    let copy = find_copy_by_id(copy_id, pool).await?;
+    let id = copy.partition_id;
+
+    let user = match find_user_by_name(form.user_name.trim().to_string(), pool).await {
+        Ok(user) => user,
+        Err(sqlx::Error::RowNotFound) => {
+            let message = format!("Erreur : membre inconnu : {}", form.user_name);
+            let level = axum_flash::Level::Error;
+            return Ok(partition_page_response(&mut flash, level, message, id));
+        }
+        Err(e) => return Err(AppError::Sqlx(e)),
+    };
+    if form.quantity < 1 {
+        let message = String::from("Erreur : il faut prêter au moins un exemplaire");
+        let level = axum_flash::Level::Error;
+        return Ok(partition_page_response(&mut flash, level, message, id));
+    }
+
+    let (level, message) =
+        match add_loan(copy_id, user.id, form.quantity, form.due_date, pool).await? {
+            Some(loan) => (
+                axum_flash::Level::Success,
+                format!(
+                    "{} exemplaire(s) prêté(s) à {} jusqu'au {}",
+                    loan.quantity,
+                    loan.user_name,
+                    loan.due_date.format("%d/%m/%Y")
+                ),
+            ),
+            None => (
+                axum_flash::Level::Error,
+                format!(
+                    "Erreur : seulement {} exemplaire(s) disponible(s) dans ce lot",
+                    copy.available
+                ),
+            ),
+        };
+    Ok(partition_page_response(&mut flash, level, message, id))
+}
+
+///
+/// Records the return of a loan
+///
+pub async fn return_loan_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Path(loan_id): Path<i32>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let loan = find_loan_by_id(loan_id, pool).await?;
+    let id = loan.partition_id;
+
+    let (level, message) = match return_loan(loan_id, pool).await? {
+        Some(loan) => (
+            axum_flash::Level::Success,
+            format!(
+                "Retour enregistré : {} exemplaire(s) rendu(s) par {}",
+                loan.quantity, loan.user_name
+            ),
+        ),
+        None => (
+            axum_flash::Level::Warning,
+            String::from("Ce prêt était déjà rendu"),
+        ),
+    };
+    Ok(partition_page_response(&mut flash, level, message, id))
+}
+
+///
+/// Shows the loans not returned after their due date
+///
+/// Returns a HTML Page or AppError
+///
+pub async fn list_overdue_loans_hdl(
+    Extension(ref templates): Extension<Tera>,
+    CurrentUser(user): CurrentUser,
+    Extension(ref pool): Extension<PgPool>,
+) -> Result<Html<String>, AppError> {
+    let loans = list_overdue_loans(pool).await?;
+
+    let title = "Prêts en retard";
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("title", &title);
+    ctx.insert("current_user", &user.name);
+    ctx.insert("loans", &loans);
+    ctx.insert("overdue_list", &true);
+
+    let body = templates
+        .render("loans.html", &ctx)
+        .map_err(|err| AppError::Tera(err))?;
+
+    Ok(Html(body))
+}
+
+///
+/// Shows what the connected member has borrowed
+/// (loans in progress, then the returned ones)
+///
+/// Returns a HTML Page or AppError
+///
+pub async fn list_my_loans_hdl(
+    Extension(ref templates): Extension<Tera>,
+    CurrentUser(user): CurrentUser,
+    Extension(ref pool): Extension<PgPool>,
+) -> Result<Html<String>, AppError> {
+    let loans = list_user_loans(user.id, pool).await?;
+
+    let title = "Mes emprunts";
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("title", &title);
+    ctx.insert("current_user", &user.name);
+    ctx.insert("loans", &loans);
+    ctx.insert("overdue_list", &false);
+
+    let body = templates
+        .render("loans.html", &ctx)
+        .map_err(|err| AppError::Tera(err))?;
+
+    Ok(Html(body))
+}
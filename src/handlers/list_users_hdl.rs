@@ -1,18 +1,42 @@
 //! src/handlers/list_users_hdl.rs
 
-use crate::db::users::list_users;
+use crate::auth::current_user::MaybeUser;
+use crate::auth::roles::USER_ROLES;
+use crate::db::users::{list_users, update_user_role};
+use crate::flash::users_response;
+use crate::models::audit::Actor;
 use crate::AppError;
-use axum::extract::Extension;
+use axum::extract::{Extension, Form, Path};
+use axum::http::{HeaderMap, StatusCode};
 use axum::response::Html;
+use axum_flash::{Flash, IncomingFlashes};
+use serde::Deserialize;
 use sqlx::PgPool;
 use tera::Tera;
 use tower_cookies::Cookies;
+use uuid::Uuid;
+
+///
+/// formulaire de changement de rôle d'un utilisateur
+///
+#[derive(Deserialize, Debug, Clone)]
+pub struct RoleForm {
+    pub role: String,
+}
 
 pub async fn print_list_users_hdl(
     Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
     Extension(ref pool): Extension<PgPool>,
     _cookies: Cookies,
+    flash: IncomingFlashes,
 ) -> Result<Html<String>, AppError> {
+    let flash = flash
+        .into_iter()
+        .map(|(level, text)| format!("{:?}: {}", level, text))
+        .collect::<Vec<_>>()
+        .join(", ");
+
     let users = list_users(pool).await?;
     //.map_err(|e|AppError::Sqlx(e));
 
@@ -20,7 +44,10 @@ pub async fn print_list_users_hdl(
 
     let mut ctx = tera::Context::new();
     ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
     ctx.insert("users", &users);
+    ctx.insert("roles", &USER_ROLES);
+    ctx.insert("flash", &flash);
 
     let body = templates
         .render("list_users.html", &ctx)
@@ -28,3 +55,47 @@ pub async fn print_list_users_hdl(
 
     Ok(Html(body))
 }
+
+///
+/// Gives another role to a user (route reserved to the admins)
+/// an admin cannot change their own role, to always keep one admin
+///
+/// Returns to the list of users with a flash message
+///
+pub async fn update_user_role_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    Path(id): Path<Uuid>,
+    form: Form<RoleForm>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let role = form.0.role.trim().to_lowercase();
+    if !USER_ROLES.contains(&role.as_str()) {
+        let message = format!("Erreur : rôle inconnu : {}", role);
+        return Ok(users_response(
+            &mut flash,
+            axum_flash::Level::Error,
+            message,
+        ));
+    }
+    if actor.id == Some(id) {
+        let message = String::from("Erreur : vous ne pouvez pas changer votre propre rôle");
+        return Ok(users_response(
+            &mut flash,
+            axum_flash::Level::Error,
+            message,
+        ));
+    }
+
+    let user = match update_user_role(id, &role, &actor, pool).await {
+        Ok(user) => user,
+        Err(sqlx::Error::RowNotFound) => return Err(AppError::NotFound),
+        Err(e) => return Err(AppError::from(e)),
+    };
+    let message = format!("Rôle de {} : {}", user.name, user.role);
+    Ok(users_response(
+        &mut flash,
+        axum_flash::Level::Success,
+        message,
+    ))
+}
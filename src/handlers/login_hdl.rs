@@ -16,7 +16,8 @@ use headers::HeaderMap;
 use crate::{AppError, StatusCode};
 use crate::flash::{error_page, login_response};
 use crate::auth::jwt::LoginPayload;
-use crate::auth::session::login_session;
+use crate::auth::current_user::MaybeUser;
+use crate::auth::session::{login_session, logout_session};
 use crate::error::LoginError;
 
 ///
@@ -26,6 +27,7 @@ use crate::error::LoginError;
 #[debug_handler]
 pub async fn login_form_hdl(
     Extension(templates): Extension<Tera>,
+    current_user: MaybeUser,
     flash: IncomingFlashes,
 ) -> Result<Html<String>, AppError> {
     let flash = flash
@@ -40,6 +42,7 @@ pub async fn login_form_hdl(
     let mut ctx = tera::Context::new();
     ctx.insert("flash", &flash);
     ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
 
     let body = templates
         .render("login.html", &ctx)
@@ -90,3 +93,16 @@ pub async fn login_hdl(
         }
     }
 }
+
+///
+/// déconnecte l'utilisateur (POST seulement : un simple lien
+/// ou une image ne doit pas pouvoir déconnecter)
+/// la session est détruite côté serveur
+///
+#[debug_handler]
+pub async fn logout_hdl(session: AxumSession<AxumPgPool>, mut flash: Flash) -> Redirect {
+    logout_session(session).await;
+    let message = "Vous êtes déconnecté".to_string();
+    let level = axum_flash::Level::Info;
+    login_response(&mut flash, level, message)
+}
@@ -1,9 +1,19 @@
 //!src/handlers/mod.rs
 
+pub mod api_hdl;
+pub mod attachments_hdl;
+pub mod audit_hdl;
+pub mod contributors_hdl;
+pub mod csv_hdl;
 pub mod genres_hdl;
 pub mod helpers_hdl;
+pub mod library_hdl;
 pub mod list_users_hdl;
 pub mod login_hdl;
 pub mod musicians_hdl;
 pub mod partitions_hdl;
+pub mod setlists_hdl;
 pub mod sign_up_hdl;
+pub mod tags_hdl;
+pub mod token_hdl;
+pub mod trash_hdl;
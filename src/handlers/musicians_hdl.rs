@@ -1,7 +1,7 @@
 //! src/handlers/musicians_hdl.rs
 
-use axum::extract::{Extension, Form, Path};
-use axum::response::Html;
+use axum::extract::{Extension, Form, Path, Query};
+use axum::response::{Html, IntoResponse, Response};
 use axum_flash::{Flash, IncomingFlashes};
 
 use serde::{Deserialize, Serialize};
@@ -9,16 +9,23 @@ use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use tera::Tera;
 
+use crate::auth::current_user::MaybeUser;
 use crate::error::AppError;
-use crate::flash::person_response;
+use crate::flash::{duplicates_response, person_response};
+use crate::handlers::helpers_hdl::{conflict_page, invalid_form_page};
+use crate::pdf::{pdf_response, PdfTable};
 
 use crate::db::musicians::*;
 
 use axum_macros::debug_handler;
 use headers::HeaderMap;
 
-use crate::globals::{get_static_vec_persons, set_static_vec_persons};
-use crate::models::musician::Person;
+use crate::models::audit::Actor;
+use crate::models::deletion::{DeleteForm, Deletion};
+use crate::models::merge::{group_pairs, DuplicatesQuery, MergeForm};
+use crate::models::musician::{Person, PersonName};
+use crate::models::pagination::ListParams;
+use crate::models::version::{required_version, ConflictField, ConflictPage, Update};
 use crate::StatusCode;
 //use crate::my_askama::askama_structs::{PersonsTemplate, HtmlTemplate,};
 //use askama_axum::*;
@@ -28,6 +35,16 @@ pub struct Demande {
     pub name: String,
 }
 
+///
+/// paramètres de la page d'impression
+/// reprend la recherche affichée par l'utilisateur : /persons/print?name=Ba
+/// sans paramètre, toute la liste est imprimée
+///
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct PrintQuery {
+    pub name: Option<String>,
+}
+
 //***********************************************************************************
 // CRUD Operations
 //
@@ -35,80 +52,216 @@ pub struct Demande {
 ///
 /// Creates a new musician in the persons table
 /// and shows the list of all musicians
+/// an invalid name, or one already used, is shown again next to the field with its error
 ///
-/// Returns PersonResponse or AppError
+/// Returns PersonResponse, the page with the errors or AppError
 ///
 ///
 #[debug_handler]
 pub async fn create_person_hdl(
+    templates: Extension<Tera>,
+    current_user: MaybeUser,
     pool: Extension<PgPool>,
+    actor: Actor,
     form: Form<Person>,
     mut flash: Flash,
-) -> Result<(StatusCode, HeaderMap), AppError> {
+) -> Result<Response, AppError> {
     let pers = form.0;
+    let name = match PersonName::parse(&pers.full_name) {
+        Ok(name) => name,
+        Err(error) => {
+            let ctx = persons_context(&current_user, &pool, &ListParams::default(), "").await?;
+            let error = AppError::unprocessable_entity([("full_name", error)]);
+            return invalid_form_page(&templates, "persons.html", ctx, error, &pers);
+        }
+    };
 
-    if let Ok(person) = add_person(&pool, pers).await {
-        tracing::info!("person added : {:?}", person);
-        let message = format!("Musicien ajouté : {}", person.full_name);
-        let level = axum_flash::Level::Success;
-        Ok(person_response(&mut flash, level, message))
-    } else {
-        tracing::info!("error adding person");
-        let message = format!("Musicien pas ajouté erreur");
-        let level = axum_flash::Level::Error;
-        Ok(person_response(&mut flash, level, message))
+    match add_person(&pool, name, &actor)
+        .await
+        .map_err(AppError::from)
+    {
+        Ok(person) => {
+            tracing::info!("person added : {:?}", person);
+            let message = format!("Musicien ajouté : {}", person.full_name);
+            let level = axum_flash::Level::Success;
+            Ok(person_response(&mut flash, level, message).into_response())
+        }
+        Err(AppError::Duplicate(error)) => {
+            let ctx = persons_context(&current_user, &pool, &ListParams::default(), "").await?;
+            let error = AppError::unprocessable_entity([("full_name", error)]);
+            invalid_form_page(&templates, "persons.html", ctx, error, &pers)
+        }
+        Err(_) => {
+            tracing::info!("error adding person");
+            let message = format!("Musicien pas ajouté erreur");
+            let level = axum_flash::Level::Error;
+            Ok(person_response(&mut flash, level, message).into_response())
+        }
     }
 }
 
 ///
 /// Modifies a musician in the persons table
 /// and shows the list of all musicians
+/// or the conflict page when the musician has been modified meanwhile
+/// an invalid name, or one already used, is shown again in a form
+/// above the list with its error
 ///
-/// Returns PersonResponse or AppError
+/// Returns PersonResponse, the conflict page or AppError
 ///
 ///
 #[debug_handler]
 pub async fn update_person_hdl(
+    templates: Extension<Tera>,
+    current_user: MaybeUser,
     pool: Extension<PgPool>,
+    actor: Actor,
     Path(id): Path<i32>,
     form: Form<Person>,
     mut flash: Flash,
-) -> Result<(StatusCode, HeaderMap), AppError> {
-    let updated_pers = form.0;
-    let person_name = updated_pers.full_name;
+) -> Result<Response, AppError> {
+    let mut updated_pers = form.0;
+    // l'id distingue le formulaire de modification de celui d'ajout dans persons.html
+    updated_pers.id = Some(id);
+    let person_name = match PersonName::parse(&updated_pers.full_name) {
+        Ok(name) => name,
+        Err(error) => {
+            let ctx = persons_context(&current_user, &pool, &ListParams::default(), "").await?;
+            let error = AppError::unprocessable_entity([("full_name", error)]);
+            return invalid_form_page(&templates, "persons.html", ctx, error, &updated_pers);
+        }
+    };
 
-    if let Ok(person) = update_person(id, person_name, &pool).await {
-        let message = format!("Musicien modifié : {}", person.full_name).to_owned();
-        let level = axum_flash::Level::Success;
-        Ok(person_response(&mut flash, level, message))
-    } else {
-        let message = format!("Musicien pas modifié, erreur");
-        let level = axum_flash::Level::Error;
-        Ok(person_response(&mut flash, level, message))
+    let version = required_version(updated_pers.version)?;
+    match update_person(id, person_name, version, &actor, &pool)
+        .await
+        .map_err(AppError::from)
+    {
+        Ok(Update::Saved(person)) => {
+            let message = format!("Musicien modifié : {}", person.full_name).to_owned();
+            let level = axum_flash::Level::Success;
+            Ok(person_response(&mut flash, level, message).into_response())
+        }
+        Ok(Update::Conflict { current }) => {
+            let conflict = ConflictPage {
+                what: String::from("le musicien"),
+                action: format!("/persons/{}", id),
+                back: String::from("/persons"),
+                version: current.version,
+                fields: vec![ConflictField::new(
+                    "full_name",
+                    "Nom",
+                    updated_pers.full_name,
+                    current.full_name,
+                )],
+            };
+            conflict_page(&templates, &current_user, &conflict)
+        }
+        Err(AppError::Duplicate(error)) => {
+            let ctx = persons_context(&current_user, &pool, &ListParams::default(), "").await?;
+            let error = AppError::unprocessable_entity([("full_name", error)]);
+            invalid_form_page(&templates, "persons.html", ctx, error, &updated_pers)
+        }
+        Err(_) => {
+            let message = format!("Musicien pas modifié, erreur");
+            let level = axum_flash::Level::Error;
+            Ok(person_response(&mut flash, level, message).into_response())
+        }
     }
 }
 
 ///
-/// Deletes a musician in the persons table
+/// Shows the confirmation page before deleting a musician :
+/// the partitions which depend on it and the musicians they can be given to
+///
+/// Returns a HTML Page or AppError
+///
+pub async fn confirm_delete_person_hdl(
+    templates: Extension<Tera>,
+    current_user: MaybeUser,
+    pool: Extension<PgPool>,
+    Path(id): Path<i32>,
+) -> Result<Html<String>, AppError> {
+    let person = find_person_by_id(id, &pool).await.map_err(not_found)?;
+    let dependents = list_person_dependents(id, &pool).await?;
+    let candidates: Vec<(i32, String)> = list_persons(&pool)
+        .await?
+        .into_iter()
+        .filter_map(|p| {
+            p.id.filter(|&other| other != id)
+                .map(|other| (other, p.full_name))
+        })
+        .collect();
+
+    let title = "Effacer un musicien";
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
+    ctx.insert("kind", "le musicien");
+    ctx.insert("name", &person.full_name);
+    ctx.insert("action", &format!("/persons/delete/{}", id));
+    ctx.insert("back", "/persons");
+    ctx.insert("dependents", &dependents);
+    ctx.insert("candidates", &candidates);
+
+    let body = templates
+        .render("confirm_delete.html", &ctx)
+        .map_err(|err| AppError::Tera(err))?;
+
+    Ok(Html(body))
+}
+
+///
+/// Moves a musician to the trash (/trash/persons)
+/// after giving its partitions to the musician chosen on the confirmation page ;
+/// refused when partitions still depend on it
 /// and shows the list of all musicians
 ///
 /// Returns PersonResponse or AppError
 ///
-///
 #[debug_handler]
 pub async fn delete_person_hdl(
     pool: Extension<PgPool>,
+    actor: Actor,
     Path(id): Path<i32>,
     mut flash: Flash,
+    Form(form): Form<DeleteForm>,
 ) -> Result<(StatusCode, HeaderMap), AppError> {
-    if let Ok(deleted_name) = delete_person(id, &pool).await {
-        let message = format!("Musicien effacé : {}", deleted_name).to_owned();
-        let level = axum_flash::Level::Success;
-        Ok(person_response(&mut flash, level, message))
-    } else {
-        let message = format!("Erreur Musicien pas effacé");
-        let level = axum_flash::Level::Error;
-        Ok(person_response(&mut flash, level, message))
+    let (level, message) = match delete_person(id, form.reassign_to, &actor, &pool).await {
+        Ok(Deletion::Deleted { name, reassigned }) if reassigned > 0 => (
+            axum_flash::Level::Success,
+            format!(
+                "Musicien mis à la corbeille : {} ; {} partition(s) réaffectée(s)",
+                name, reassigned
+            ),
+        ),
+        Ok(Deletion::Deleted { name, .. }) => (
+            axum_flash::Level::Success,
+            format!("Musicien mis à la corbeille : {}", name),
+        ),
+        Ok(Deletion::Refused { name, dependents }) => (
+            axum_flash::Level::Error,
+            format!(
+                "Musicien pas effacé : {} partition(s) dépendent de {}",
+                dependents, name
+            ),
+        ),
+        Err(e) => {
+            tracing::error!("db : person not deleted : {}", e);
+            (
+                axum_flash::Level::Error,
+                String::from("Erreur Musicien pas effacé"),
+            )
+        }
+    };
+    Ok(person_response(&mut flash, level, message))
+}
+
+fn not_found(err: sqlx::Error) -> AppError {
+    match err {
+        sqlx::Error::RowNotFound => AppError::NotFound,
+        e => AppError::from(e),
     }
 }
 
@@ -118,13 +271,16 @@ pub async fn delete_person_hdl(
 
 ///
 /// Shows the page with the list of musicians
+/// one page at a time : /persons?page=2&per_page=50&sort=name&dir=desc
 ///
 /// Returns a HTML Page or AppError
 ///
 #[debug_handler]
 pub async fn list_persons_hdl(
     templates: Extension<Tera>,
+    current_user: MaybeUser,
     pool: Extension<PgPool>,
+    Query(params): Query<ListParams>,
     flash: IncomingFlashes,
 ) -> Result<Html<String>, AppError> {
     // on va chercher le message dans IncomingFlashes pour l'afficher
@@ -135,16 +291,7 @@ pub async fn list_persons_hdl(
         .join(", ");
     tracing::info!("flash : {}", flash);
 
-    // let persons = list_persons(&pool).await?;
-    set_static_vec_persons(list_persons(&pool).await?);
-    let persons = get_static_vec_persons();
-
-    let title = "Gestion des Musiciens";
-
-    let mut ctx = tera::Context::new();
-    ctx.insert("title", &title);
-    ctx.insert("persons", &persons);
-    ctx.insert("flash", &flash);
+    let ctx = persons_context(&current_user, &pool, &params, &flash).await?;
 
     let body = templates
         .render("persons.html", &ctx)
@@ -153,6 +300,30 @@ pub async fn list_persons_hdl(
     Ok(Html(body))
 }
 
+///
+/// le contexte de la page des musiciens (persons.html) :
+/// une page de la liste avec le message flash
+/// (partagé avec create_person_hdl qui y réaffiche un nom refusé)
+///
+async fn persons_context(
+    current_user: &MaybeUser,
+    pool: &PgPool,
+    params: &ListParams,
+    flash: &str,
+) -> Result<tera::Context, AppError> {
+    let page = list_persons_page(pool, params).await?;
+
+    let title = "Gestion des Musiciens";
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
+    ctx.insert("persons", &page.items);
+    ctx.insert("page", &page);
+    ctx.insert("flash", &flash);
+    Ok(ctx)
+}
+
 /*
 #[debug_handler]
 pub async fn askama_list_persons_hdl(
@@ -182,17 +353,27 @@ pub async fn askama_list_persons_hdl(
 
 ///
 /// Shows a printable list of Musicians
+/// the search shown on the page is passed in the query string
 ///
-/// Returns a HTML Page or AppErro
+/// Returns a HTML Page or AppError
 ///
 #[debug_handler]
-pub async fn print_list_persons_hdl(templates: Extension<Tera>) -> Result<Html<String>, AppError> {
-    // on va chercher la liste statique
-    let persons = get_static_vec_persons();
+pub async fn print_list_persons_hdl(
+    templates: Extension<Tera>,
+    current_user: MaybeUser,
+    pool: Extension<PgPool>,
+    Query(query): Query<PrintQuery>,
+) -> Result<Html<String>, AppError> {
+    // on refait la recherche de l'utilisateur, ou on prend toute la liste
+    let persons = match query.name {
+        Some(name) if !name.is_empty() => find_person_by_name(name, &pool).await?,
+        _ => list_persons(&pool).await?,
+    };
     let title = "Liste des Musiciens";
 
     let mut ctx = tera::Context::new();
     ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
     ctx.insert("persons", &persons);
 
     let body = templates
@@ -202,6 +383,137 @@ pub async fn print_list_persons_hdl(templates: Extension<Tera>) -> Result<Html<S
     Ok(Html(body))
 }
 
+///
+/// The printable list of musicians as a PDF file (A4, grouped by initial letter)
+/// with the same search as the HTML page : /persons/print.pdf?name=Ba
+///
+/// Returns the PDF or AppError
+///
+pub async fn print_pdf_persons_hdl(
+    pool: Extension<PgPool>,
+    Query(query): Query<PrintQuery>,
+) -> Result<Response, AppError> {
+    let persons = match query.name {
+        Some(name) if !name.is_empty() => find_person_by_name(name, &pool).await?,
+        _ => list_persons(&pool).await?,
+    };
+
+    let mut table = PdfTable::new("Liste des Musiciens", &[("Nom", 1.0)]);
+    let mut initial: Option<char> = None;
+    for person in &persons {
+        let first = person
+            .full_name
+            .chars()
+            .next()
+            .and_then(|c| c.to_uppercase().next());
+        if first != initial {
+            initial = first;
+            table.group(&first.map(String::from).unwrap_or_default());
+        }
+        table.row(&[&person.full_name]);
+    }
+    table.line(&format!("{} musicien(s)", persons.len()));
+
+    Ok(pdf_response("musiciens.pdf", table.finish()))
+}
+
+//*************************************************************************************
+// Duplicates
+//
+
+///
+/// Shows the groups of musicians whose names look alike
+/// to choose the one to keep and merge the others into it
+/// /persons/duplicates?threshold=0.6
+///
+/// Returns a HTML Page or AppError
+///
+pub async fn list_duplicate_persons_hdl(
+    templates: Extension<Tera>,
+    current_user: MaybeUser,
+    pool: Extension<PgPool>,
+    Query(query): Query<DuplicatesQuery>,
+    flash: IncomingFlashes,
+) -> Result<Html<String>, AppError> {
+    let flash = flash
+        .into_iter()
+        .map(|(level, text)| format!("{:?}: {}", level, text))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let threshold = query.threshold();
+    let groups = group_pairs(find_similar_persons(threshold, &pool).await?);
+
+    let title = "Doublons des Musiciens";
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
+    ctx.insert("groups", &groups);
+    ctx.insert("threshold", &threshold);
+    ctx.insert("base_url", "/persons");
+    ctx.insert("flash", &flash);
+
+    let body = templates
+        .render("duplicates.html", &ctx)
+        .map_err(|err| AppError::Tera(err))?;
+
+    Ok(Html(body))
+}
+
+///
+/// Merges the checked musicians into the one to keep
+/// survivor=3&duplicates=7&duplicates=12
+///
+/// Returns to the duplicates report with a flash message
+///
+pub async fn merge_persons_hdl(
+    pool: Extension<PgPool>,
+    actor: Actor,
+    mut flash: Flash,
+    Form(pairs): Form<Vec<(String, String)>>,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let form = match MergeForm::from_pairs(&pairs) {
+        Ok(form) => form,
+        Err(message) => {
+            let message = format!("Fusion impossible : {}", message);
+            let level = axum_flash::Level::Error;
+            return Ok(duplicates_response(
+                &mut flash,
+                level,
+                message,
+                "/persons/duplicates",
+            ));
+        }
+    };
+
+    let merged = merge_persons(form.survivor, &form.duplicates, &actor, &pool).await;
+    let (level, message) = match merged {
+        Ok(result) => (
+            axum_flash::Level::Success,
+            format!(
+                "Fusionné(s) dans {} : {} ; {} partition(s) réaffectée(s)",
+                result.survivor,
+                result.merged.join(", "),
+                result.partitions
+            ),
+        ),
+        Err(e) => {
+            tracing::error!("db : persons not merged : {}", e);
+            (
+                axum_flash::Level::Error,
+                String::from("Erreur : musiciens pas fusionnés"),
+            )
+        }
+    };
+    Ok(duplicates_response(
+        &mut flash,
+        level,
+        message,
+        "/persons/duplicates",
+    ))
+}
+
 //*************************************************************************************
 // Functions to find one musician
 //
@@ -214,6 +526,7 @@ pub async fn print_list_persons_hdl(templates: Extension<Tera>) -> Result<Html<S
 #[debug_handler]
 pub async fn find_person_by_name_hdl(
     templates: Extension<Tera>,
+    current_user: MaybeUser,
     pool: Extension<PgPool>,
     form: Form<Demande>,
     flash: IncomingFlashes,
@@ -223,12 +536,11 @@ pub async fn find_person_by_name_hdl(
 
     // on va chercher la liste des musiciens qui correspond à la recherche
     // si le résultat est positif ... autrement ...
-    if let Ok(persons) = find_person_by_name(demande.name, &pool).await {
-        // on insère le résultat de la recherche dans le Vec<Person> global
-        set_static_vec_persons(persons.clone());
-        // on va chercher les valeurs du Vec<Person> global (lazy_static)
-        // que l'on met dans la variable que l'on affiche
-        let found_persons = get_static_vec_persons();
+    if let Ok(found_persons) = find_person_by_name(demande.name.clone(), &pool).await {
+        // la recherche est gardée dans la page pour construire le lien d'impression
+        let search = PrintQuery {
+            name: Some(demande.name),
+        };
 
         let title = "Personne(s) trouvée(s)";
         let flash = flash
@@ -240,8 +552,10 @@ pub async fn find_person_by_name_hdl(
 
         let mut ctx = tera::Context::new();
         ctx.insert("title", &title);
+        ctx.insert("current_user", &current_user.name());
         ctx.insert("persons", &found_persons);
         ctx.insert("flash", &flash);
+        ctx.insert("search", &search);
 
         let body = templates
             .render("persons.html", &ctx)
@@ -1,8 +1,8 @@
 //! src/handlers/partitions_hdl.rs
 
-use axum::extract::{Extension, Form, Path};
+use axum::extract::{Extension, Form, Path, Query};
 use axum::http::{HeaderMap, StatusCode};
-use axum::response::Html;
+use axum::response::{Html, IntoResponse, Response};
 use axum_flash::{Flash, IncomingFlashes};
 
 //use tower_cookies::{Cookies,};
@@ -12,18 +12,46 @@ use tera::Tera;
 
 use serde::{Deserialize, Serialize};
 
-use crate::db::{genres::*, musicians::*, partitions::update_partition, partitions::*};
+use crate::auth::current_user::MaybeUser;
+use crate::db::search::search_partitions;
+use crate::db::tags::tag_cloud;
+use crate::db::{genres::*, musicians::*, partitions::*};
 
 use crate::error::AppError;
-use crate::flash::partition_response;
-use crate::globals::{get_static_vec_partitions, set_static_vec_partitions};
-use crate::models::genre::Genre;
-use crate::models::musician::Person;
-use crate::models::partition::ShowPartition;
-
-#[derive(Deserialize, Serialize, Debug, Clone)]
-pub struct Demande {
-    pub name: String,
+use crate::flash::{partition_page_response, partition_response};
+use crate::handlers::attachments_hdl::partition_context;
+use crate::handlers::helpers_hdl::{conflict_page, invalid_form_page};
+use crate::models::audit::Actor;
+use crate::models::pagination::ListParams;
+use crate::models::partition::{NewPartition, PartitionFilter, ShowPartition};
+use crate::models::version::{required_version, ConflictField, ConflictPage, Update};
+use crate::pdf::{pdf_response, PdfTable};
+use crate::utils::serde_utils::format_duration;
+
+///
+/// regroupement des partitions dans le PDF : /partitions/print.pdf?group=genre
+///
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PdfGroup {
+    #[default]
+    Composer,
+    Genre,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct PdfQuery {
+    pub group: PdfGroup,
+}
+
+///
+/// recherche sur les titres, les musiciens et les genres : /partitions/search?q=faure
+///
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct SearchQuery {
+    pub q: String,
 }
 
 //***********************************************************************************
@@ -33,22 +61,59 @@ pub struct Demande {
 ///
 /// Create a new partition in the partitions table
 /// and shows the list of all partitions
+/// an invalid form, or one with an unknown musician or genre,
+/// is shown again with the errors next to the fields ;
+/// so is a partition with the same title by the same composer as another one,
+/// until "Ajouter quand même" (duplicate_ok) is checked
 ///
-/// Returns PartitionResponse or AppError
+/// Returns PartitionResponse, the page with the errors or AppError
 ///
 pub async fn create_partition_hdl(
-    //Extension(ref pool): Extension<PgPool>,
-    pool: Extension<PgPool>,
+    Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
     form: Form<ShowPartition>,
     mut flash: Flash,
-) -> Result<(StatusCode, HeaderMap), (StatusCode, &'static str)> {
+) -> Result<Response, AppError> {
     let show_partition = form.0;
 
-    let person_name = show_partition.full_name.clone();
-    let genre_name = show_partition.name.clone();
-    let title = show_partition.title.clone();
+    let (partition, person_id, genre_id) = match check_partition_form(&show_partition, pool).await {
+        Ok(checked) => checked,
+        Err(error) => {
+            let filter = PartitionFilter::default();
+            let params = ListParams::default();
+            let ctx = partitions_context(&current_user, pool, &filter, &params, "").await?;
+            return invalid_form_page(templates, "partitions.html", ctx, error, &show_partition);
+        }
+    };
+
+    if !show_partition.duplicate_ok {
+        let duplicates =
+            find_duplicate_partitions(partition.title.as_ref(), partition.full_name.as_ref(), pool)
+                .await?;
+        if !duplicates.is_empty() {
+            let filter = PartitionFilter::default();
+            let params = ListParams::default();
+            let mut ctx = partitions_context(&current_user, pool, &filter, &params, "").await?;
+            ctx.insert("duplicates", &duplicates);
+            let error = AppError::unprocessable_entity([(
+                "title",
+                "Une partition de ce titre existe déjà pour ce compositeur",
+            )]);
+            return invalid_form_page(templates, "partitions.html", ctx, error, &show_partition);
+        }
+    }
 
-    let new_partition = add_partition(title, person_name, genre_name, &pool).await;
+    let new_partition = add_partition_with_ids(
+        partition.title,
+        person_id,
+        genre_id,
+        partition.metadata,
+        &actor,
+        pool,
+    )
+    .await;
 
     match new_partition {
         Ok(new_partition) => {
@@ -56,51 +121,172 @@ pub async fn create_partition_hdl(
 
             let message = format!("Partition ajoutée : {}", new_partition.title);
             let level = axum_flash::Level::Success;
-            Ok(partition_response(&mut flash, level, message))
+            Ok(partition_response(&mut flash, level, message).into_response())
         }
         Err(_) => {
             let message = format!("Erreur : Partition pas ajoutée !");
             let level = axum_flash::Level::Error;
-            Ok(partition_response(&mut flash, level, message))
+            Ok(partition_response(&mut flash, level, message).into_response())
         }
     }
 }
 
+///
+/// Modifies a partition and returns to its page
+/// or shows the conflict page when the partition has been modified meanwhile
+/// an invalid form, or one with an unknown musician or genre,
+/// is shown again on the page of the partition with the errors next to the fields
+///
 pub async fn update_partition_hdl(
+    Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
     Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
     Path(id): Path<i32>,
     form: Form<ShowPartition>,
     mut flash: Flash,
-) -> Result<(StatusCode, HeaderMap), AppError> {
+) -> Result<Response, AppError> {
     let show_partition = form.0;
 
-    let person = find_person_by_name(show_partition.full_name, pool).await?;
-    let person_id = person[0].id.unwrap();
-
-    let genre = find_genre_by_name(show_partition.name, pool).await?;
-    let genre_id = genre[0].id.unwrap();
+    let (partition, person_id, genre_id) = match check_partition_form(&show_partition, pool).await {
+        Ok(checked) => checked,
+        Err(error) => {
+            let ctx = partition_context(&current_user, id, pool, "").await?;
+            return invalid_form_page(templates, "partition.html", ctx, error, &show_partition);
+        }
+    };
+
+    let title = partition.title;
+    let metadata = partition.metadata;
+    let version = required_version(partition.version)?;
+
+    // la modification se fait depuis la page de la partition : on y retourne
+    match update_partition(
+        id, title, person_id, genre_id, metadata, version, &actor, pool,
+    )
+    .await
+    {
+        Ok(Update::Saved(partition_changed)) => {
+            let message = format!("Partition modifiée : {}", partition_changed.title);
+            let level = axum_flash::Level::Success;
+            Ok(partition_page_response(&mut flash, level, message, id).into_response())
+        }
+        Ok(Update::Conflict { current }) => {
+            let current = show_one_partition(current, pool).await?;
+            let conflict = ConflictPage {
+                what: String::from("la partition"),
+                action: format!("/partitions/{}", id),
+                back: format!("/partitions/show/{}", id),
+                version: current.version,
+                fields: conflict_fields(&show_partition, &current),
+            };
+            conflict_page(templates, &current_user, &conflict)
+        }
+        Err(_) => {
+            let message = format!("Erreur : Partition pas modifiée !");
+            let level = axum_flash::Level::Error;
+            Ok(partition_page_response(&mut flash, level, message, id).into_response())
+        }
+    }
+}
 
-    let title = show_partition.title;
+///
+/// le formulaire validé (NewPartition::parse) avec les ids du musicien et du genre choisis,
+/// ou une erreur 422 : celles de NewPartition::parse, ou une sur chaque nom inconnu
+///
+async fn check_partition_form(
+    show_partition: &ShowPartition,
+    pool: &PgPool,
+) -> Result<(NewPartition, i32, i32), AppError> {
+    let partition = NewPartition::parse(show_partition)?;
+    let person_id = find_person_id(partition.full_name.as_ref(), pool).await?;
+    let genre_id = find_genre_id(partition.name.as_ref(), pool).await?;
+
+    match (person_id, genre_id) {
+        (Some(person_id), Some(genre_id)) => Ok((partition, person_id, genre_id)),
+        (person_id, genre_id) => {
+            let mut errors = Vec::new();
+            if person_id.is_none() {
+                let message = format!("Musicien inconnu : {}", partition.full_name.as_ref());
+                errors.push(("full_name", message));
+            }
+            if genre_id.is_none() {
+                let message = format!("Genre inconnu : {}", partition.name.as_ref());
+                errors.push(("name", message));
+            }
+            Err(AppError::unprocessable_entity(errors))
+        }
+    }
+}
 
-    if let Ok(partition_changed) = update_partition(id, title, person_id, genre_id, pool).await {
-        let message =
-            format!("Partition successfully updated : {:?}", partition_changed).to_owned();
-        let level = axum_flash::Level::Success;
-        return Ok(partition_response(&mut flash, level, message));
-    } else {
-        let message = format!("Error in Partition update");
-        let level = axum_flash::Level::Error;
-        Ok(partition_response(&mut flash, level, message))
+///
+/// les champs du formulaire de modification, avec la valeur envoyée
+/// et la valeur enregistrée, pour la page de conflit
+///
+fn conflict_fields(mine: &ShowPartition, current: &ShowPartition) -> Vec<ConflictField> {
+    fn text<T: ToString>(value: &Option<T>) -> String {
+        value.as_ref().map(ToString::to_string).unwrap_or_default()
     }
+    let duration = |seconds: Option<i32>| seconds.map(format_duration).unwrap_or_default();
+    let (m, c) = (&mine.metadata, &current.metadata);
+    vec![
+        ConflictField::new("title", "Titre", mine.title.clone(), current.title.clone()),
+        ConflictField::new(
+            "full_name",
+            "Musicien",
+            mine.full_name.clone(),
+            current.full_name.clone(),
+        ),
+        ConflictField::new("name", "Genre", mine.name.clone(), current.name.clone()),
+        ConflictField::new(
+            "instrumentation",
+            "Effectif",
+            text(&m.instrumentation),
+            text(&c.instrumentation),
+        ),
+        ConflictField::new("key", "Tonalité", text(&m.music_key), text(&c.music_key)),
+        ConflictField::new("tempo", "Tempo", text(&m.tempo), text(&c.tempo)),
+        ConflictField::new(
+            "difficulty",
+            "Difficulté",
+            text(&m.difficulty),
+            text(&c.difficulty),
+        ),
+        ConflictField::new(
+            "duration_seconds",
+            "Durée",
+            duration(m.duration_seconds),
+            duration(c.duration_seconds),
+        ),
+        ConflictField::new("opus", "Opus / catalogue", text(&m.opus), text(&c.opus)),
+        ConflictField::new(
+            "publisher",
+            "Éditeur",
+            text(&m.publisher),
+            text(&c.publisher),
+        ),
+        ConflictField::new(
+            "edition_year",
+            "Année d'édition",
+            text(&m.edition_year),
+            text(&c.edition_year),
+        ),
+        ConflictField::new("notes", "Notes", text(&m.notes), text(&c.notes)),
+    ]
 }
 
+///
+/// Moves a partition to the trash (/trash/partitions) : its files are kept
+/// until it is purged
+///
 pub async fn delete_partition_hdl(
     Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
     Path(id): Path<i32>,
     mut flash: Flash,
 ) -> Result<(StatusCode, HeaderMap), AppError> {
-    let partition_title = delete_partition(id, pool).await?;
-    let message = format!("Partition succcessfully deleted : {}", partition_title).to_owned();
+    let partition_title = delete_partition(id, &actor, pool).await?;
+    let message = format!("Partition mise à la corbeille : {}", partition_title).to_owned();
     let level = axum_flash::Level::Success;
     Ok(partition_response(&mut flash, level, message))
 }
@@ -111,12 +297,18 @@ pub async fn delete_partition_hdl(
 
 ///
 /// Shows the page with the list of partitions via ShowPartition
+/// filtered on any combination of title, author, genre, tag and metadata
+/// one page at a time : /partitions?genre=jazz&author=ellington&title=S&page=2&sort=author
+/// with the tag cloud
 ///
 /// Returns a HTML Page or AppError
 ///
 pub async fn list_partitions_hdl(
     Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
     Extension(ref pool): Extension<PgPool>,
+    Query(filter): Query<PartitionFilter>,
+    Query(params): Query<ListParams>,
     flash: IncomingFlashes,
 ) -> Result<Html<String>, AppError> {
     let flash = flash
@@ -126,20 +318,7 @@ pub async fn list_partitions_hdl(
         .join(", ");
     tracing::info!("flash : {}", flash);
 
-    //let show_partitions = list_show_partitions(pool).await?;
-    set_static_vec_partitions(list_show_partitions(pool).await?);
-    let show_partitions = get_static_vec_partitions();
-
-    let persons = list_persons(pool).await?;
-    let genres = list_genres(pool).await?;
-    let title = "Gestion des Partitions";
-
-    let mut ctx = tera::Context::new();
-    ctx.insert("title", &title);
-    ctx.insert("persons", &persons);
-    ctx.insert("genres", &genres);
-    ctx.insert("partitions", &show_partitions);
-    ctx.insert("flash", &flash);
+    let ctx = partitions_context(&current_user, pool, &filter, &params, &flash).await?;
 
     let body = templates
         .render("partitions.html", &ctx)
@@ -149,155 +328,172 @@ pub async fn list_partitions_hdl(
 }
 
 ///
-/// Shows a printable list of all partitions in the db
-/// under the form of ShowPartitions
-///
-/// Returns a HTML Page or AppError
+/// le contexte de la page des partitions (partitions.html) :
+/// une page de la liste filtrée, les listes des formulaires,
+/// le nuage d'étiquettes et le message flash
+/// (partagé avec create_partition_hdl qui y réaffiche un formulaire refusé)
 ///
-pub async fn print_list_partitions_hdl(
-    Extension(ref templates): Extension<Tera>,
-    //Extension(ref pool): Extension<PgPool>,
-) -> Result<Html<String>, AppError> {
-    //let show_partitions = list_show_partitions(pool).await.unwrap();
-    let show_partitions = get_static_vec_partitions();
+async fn partitions_context(
+    current_user: &MaybeUser,
+    pool: &PgPool,
+    filter: &PartitionFilter,
+    params: &ListParams,
+    flash: &str,
+) -> Result<tera::Context, AppError> {
+    let page = filter_partitions(filter, Some(params), pool).await?;
 
-    let title = "liste des partitions";
+    let persons = list_persons(pool).await?;
+    let genres = list_genres(pool).await?;
+    let tag_cloud = tag_cloud(pool).await?;
+    let title = if filter.is_empty() {
+        "Gestion des Partitions"
+    } else {
+        "Partition(s) trouvée(s)"
+    };
 
     let mut ctx = tera::Context::new();
     ctx.insert("title", &title);
-    ctx.insert("partitions", &show_partitions);
-
-    let body = templates
-        .render("list_partitions.html", &ctx)
-        .map_err(|err| AppError::Tera(err))?;
-
-    Ok(Html(body))
+    ctx.insert("current_user", &current_user.name());
+    ctx.insert("persons", &persons);
+    ctx.insert("genres", &genres);
+    ctx.insert("partitions", &page.items);
+    ctx.insert("page", &page);
+    ctx.insert("filter", filter);
+    ctx.insert("tag_cloud", &tag_cloud);
+    ctx.insert("flash", &flash);
+    Ok(ctx)
 }
 
-//*************************************************************************************
-// Functions to find one or several partitions based on different criteria
-//
-
 ///
-/// find_partition_by_title
+/// Shows the results of the full-text search on titles, musicians and genres
+/// ordered by rank, with the matching words highlighted
+/// /partitions/search?q=bach&page=2
 ///
-/// returns list musicians page with partition(s) found by title
+/// Returns a HTML Page or AppError
 ///
-pub async fn find_partition_title_hdl(
-    Extension(ref templates): Extension<Tera>,
-    Extension(ref pool): Extension<PgPool>,
-    form: Form<Demande>,
-    flash: IncomingFlashes,
-) -> Result<Html<String>, AppError> {
-    let flash = flash
-        .into_iter()
-        .map(|(level, text)| format!("{:?}: {}", level, text))
-        .collect::<Vec<_>>()
-        .join(", ");
-    tracing::info!("flash : {}", flash);
-
-    let demande = form.0;
-    let name = demande.name;
-
-    if let Ok(partitions) = find_partition_by_title(name, pool).await {
-        let title = "Partition(s) trouvée(s)";
-        /*
-                let mut show_partitions: Vec<ShowPartition> = Vec::new();
-                for partition in partitions {
-                    let one_show_partition = show_one_partition(partition, pool).await.unwrap();
-                    show_partitions.push(one_show_partition);
-                }
-        */
-        let sh_partitions = vec_showpartitions_from_vec_partitions(partitions, pool).await;
-        set_static_vec_partitions(sh_partitions);
-        let show_partitions = get_static_vec_partitions();
-
-        let persons = list_persons(pool).await?;
-        let genres = list_genres(pool).await?;
-
-        let mut ctx = tera::Context::new();
-        ctx.insert("title", &title);
-        ctx.insert("partitions", &show_partitions);
-        ctx.insert("genres", &genres);
-        ctx.insert("persons", &persons);
-
-        let body = templates
-            .render("partitions.html", &ctx)
-            .map_err(|err| AppError::Tera(err))?;
-        Ok(Html(body))
-    } else {
-        let mut ctx = tera::Context::new();
-        ctx.insert("data", "partition");
-
-        let body = templates
-            .render("error/void.html.tera", &ctx)
-            .map_err(|err| AppError::Tera(err))?;
-        Ok(Html(body))
-    }
-}
-
-pub async fn find_partition_genre_hdl(
+pub async fn search_partitions_hdl(
     Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
     Extension(ref pool): Extension<PgPool>,
-    form: Form<Genre>,
+    Query(search): Query<SearchQuery>,
+    Query(params): Query<ListParams>,
 ) -> Result<Html<String>, AppError> {
-    let genre = form.0;
-    let name = genre.name;
+    let page = search_partitions(&search.q, &params, pool).await?;
 
-    let title = "Partition(s) trouvée(s)";
-
-    let partitions = find_partition_by_genre(name, pool).await?;
-    let mut show_partitions: Vec<ShowPartition> = Vec::new();
-    for partition in partitions {
-        let show_part = show_one_partition(partition, pool).await?;
-        show_partitions.push(show_part);
-    }
     let persons = list_persons(pool).await?;
     let genres = list_genres(pool).await?;
+    let title = "Recherche de Partitions";
 
     let mut ctx = tera::Context::new();
     ctx.insert("title", &title);
-    ctx.insert("partitions", &show_partitions);
-    ctx.insert("genres", &genres);
+    ctx.insert("current_user", &current_user.name());
     ctx.insert("persons", &persons);
+    ctx.insert("genres", &genres);
+    ctx.insert("hits", &page.items);
+    ctx.insert("page", &page);
+    ctx.insert("search", &search);
 
     let body = templates
         .render("partitions.html", &ctx)
-        .map_err(|err| AppError::Tera(err))?;
+        .map_err(|e| AppError::Tera(e))?;
+
     Ok(Html(body))
 }
 
-pub async fn find_partition_author_hdl(
+///
+/// Shows a printable list of partitions
+/// under the form of ShowPartitions
+/// the filter shown on the page is passed in the query string
+/// /partitions/print?genre=jazz&author=ellington
+///
+/// Returns a HTML Page or AppError
+///
+pub async fn print_list_partitions_hdl(
     Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
     Extension(ref pool): Extension<PgPool>,
-    form: Form<Person>,
+    Query(filter): Query<PartitionFilter>,
 ) -> Result<Html<String>, AppError> {
-    let person = form.0;
-    let name = person.full_name;
-
-    let title = "Partition(s) trouvée(s)";
+    // on reprend le filtre de l'utilisateur, sans pagination
+    let show_partitions = filter_partitions(&filter, None, pool).await?.items;
 
-    let partitions = find_partition_by_author(name, pool).await?;
-    let mut show_partitions: Vec<ShowPartition> = Vec::new();
-    for partition in partitions {
-        let show_part = show_one_partition(partition, pool).await?;
-        show_partitions.push(show_part);
-    }
-    set_static_vec_partitions(show_partitions);
-    let show_partitions = get_static_vec_partitions();
-
-    let persons = list_persons(pool).await?;
-    let genres = list_genres(pool).await?;
+    let title = "liste des partitions";
 
     let mut ctx = tera::Context::new();
     ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
     ctx.insert("partitions", &show_partitions);
-    ctx.insert("genres", &genres);
-    ctx.insert("persons", &persons);
 
     let body = templates
-        .render("partitions.html", &ctx)
+        .render("list_partitions.html", &ctx)
         .map_err(|err| AppError::Tera(err))?;
 
     Ok(Html(body))
 }
+
+///
+/// The printable list of partitions as a PDF file (A4)
+/// with the filter shown on the page, grouped by composer (default) or by genre
+/// /partitions/print.pdf?genre=jazz&group=composer
+///
+/// Returns the PDF or AppError
+///
+pub async fn print_pdf_partitions_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Query(filter): Query<PartitionFilter>,
+    Query(query): Query<PdfQuery>,
+) -> Result<Response, AppError> {
+    let mut partitions = filter_partitions(&filter, None, pool).await?.items;
+
+    let (title, other) = match query.group {
+        PdfGroup::Composer => ("Partitions par compositeur", "Genre"),
+        PdfGroup::Genre => ("Partitions par genre", "Compositeur"),
+    };
+    // (groupe, autre colonne) : le musicien principal et le genre principal
+    let keys = |partition: &ShowPartition| match query.group {
+        PdfGroup::Composer => (partition.full_name.clone(), partition.name.clone()),
+        PdfGroup::Genre => (partition.name.clone(), partition.full_name.clone()),
+    };
+    partitions.sort_by(|a, b| keys(a).0.cmp(&keys(b).0).then(a.title.cmp(&b.title)));
+
+    let mut table = PdfTable::new(
+        title,
+        &[
+            ("Titre", 4.0),
+            (other, 2.2),
+            ("Effectif", 2.2),
+            ("Tonalité", 1.5),
+            ("Durée", 0.9),
+            ("Difficulté", 0.9),
+        ],
+    );
+    let mut current_group: Option<String> = None;
+    for partition in &partitions {
+        let (group, other) = keys(partition);
+        if current_group.as_ref() != Some(&group) {
+            let count = partitions.iter().filter(|p| keys(p).0 == group).count();
+            table.group(&format!("{} ({})", group, count));
+            current_group = Some(group);
+        }
+        let metadata = &partition.metadata;
+        let duration = metadata
+            .duration_seconds
+            .map(format_duration)
+            .unwrap_or_default();
+        let difficulty = metadata
+            .difficulty
+            .map(|level| format!("{} / 5", level))
+            .unwrap_or_default();
+        table.row(&[
+            &partition.title,
+            &other,
+            metadata.instrumentation.as_deref().unwrap_or_default(),
+            metadata.music_key.as_deref().unwrap_or_default(),
+            &duration,
+            &difficulty,
+        ]);
+    }
+    table.line(&format!("{} partition(s)", partitions.len()));
+
+    Ok(pdf_response("partitions.pdf", table.finish()))
+}
@@ -0,0 +1,293 @@
+//! src/handlers/setlists_hdl.rs
+//!
+//! Programmes de concert composés à partir des partitions du catalogue
+
+use axum::extract::{Extension, Form, Path, Query};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::Html;
+use axum_flash::{Flash, IncomingFlashes};
+
+use sqlx::PgPool;
+use tera::Tera;
+
+use crate::auth::current_user::MaybeUser;
+use crate::db::partitions::{filter_partitions, find_partition_by_id};
+use crate::db::setlists::*;
+
+use crate::error::AppError;
+use crate::flash::{setlist_page_response, setlist_response};
+use crate::models::pagination::ListParams;
+use crate::models::partition::PartitionFilter;
+use crate::models::setlist::{MoveForm, Setlist, SetlistItem, SetlistItemForm, SetlistItemNotes};
+
+//***********************************************************************************
+// CRUD Operations
+//
+
+///
+/// Create a new setlist
+/// and shows its page to add the pieces
+///
+pub async fn create_setlist_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    form: Form<Setlist>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let setlist = form.0;
+    if setlist.name.trim().is_empty() {
+        let message = String::from("Erreur : le programme doit avoir un nom");
+        return Ok(setlist_response(
+            &mut flash,
+            axum_flash::Level::Error,
+            message,
+        ));
+    }
+
+    let setlist = add_setlist(setlist, pool).await?;
+    let message = format!("Programme ajouté : {}", setlist.name);
+    let level = axum_flash::Level::Success;
+    Ok(setlist_page_response(
+        &mut flash,
+        level,
+        message,
+        setlist.id.unwrap(),
+    ))
+}
+
+pub async fn update_setlist_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Path(id): Path<i32>,
+    form: Form<Setlist>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let setlist = form.0;
+    if setlist.name.trim().is_empty() {
+        let message = String::from("Erreur : le programme doit avoir un nom");
+        let level = axum_flash::Level::Error;
+        return Ok(setlist_page_response(&mut flash, level, message, id));
+    }
+
+    let setlist = update_setlist(id, setlist, pool).await?;
+    let message = format!("Programme modifié : {}", setlist.name);
+    let level = axum_flash::Level::Success;
+    Ok(setlist_page_response(&mut flash, level, message, id))
+}
+
+pub async fn delete_setlist_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Path(id): Path<i32>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let name = delete_setlist(id, pool).await?;
+    let message = format!("Programme effacé : {}", name);
+    Ok(setlist_response(
+        &mut flash,
+        axum_flash::Level::Success,
+        message,
+    ))
+}
+
+//***********************************************************************************
+// Pièces d'un programme
+//
+
+///
+/// Adds a partition at the end of the setlist
+///
+pub async fn add_setlist_item_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Path(id): Path<i32>,
+    form: Form<SetlistItemForm>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let item = form.0;
+    let partition = find_partition_by_id(item.partition_id, pool).await?;
+    add_setlist_item(id, item.partition_id, item.notes, pool).await?;
+
+    let message = format!("Pièce ajoutée : {}", partition.title);
+    let level = axum_flash::Level::Success;
+    Ok(setlist_page_response(&mut flash, level, message, id))
+}
+
+pub async fn update_setlist_item_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Path(item_id): Path<i32>,
+    form: Form<SetlistItemNotes>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let id = update_setlist_item_notes(item_id, form.0.notes, pool).await?;
+    let message = String::from("Notes modifiées");
+    let level = axum_flash::Level::Success;
+    Ok(setlist_page_response(&mut flash, level, message, id))
+}
+
+pub async fn delete_setlist_item_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Path(item_id): Path<i32>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let id = delete_setlist_item(item_id, pool).await?;
+    let message = String::from("Pièce retirée du programme");
+    let level = axum_flash::Level::Success;
+    Ok(setlist_page_response(&mut flash, level, message, id))
+}
+
+///
+/// Moves a piece one place up or down in the setlist
+///
+pub async fn move_setlist_item_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Path(item_id): Path<i32>,
+    form: Form<MoveForm>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let id = move_setlist_item(item_id, form.0.direction, pool).await?;
+    let message = String::from("Ordre du programme modifié");
+    let level = axum_flash::Level::Success;
+    Ok(setlist_page_response(&mut flash, level, message, id))
+}
+
+//*******************************************************************************
+// Functions to show or print setlists
+//
+
+///
+/// Shows the page with the list of setlists
+/// one page at a time : /setlists?page=2&sort=name
+///
+/// Returns a HTML Page or AppError
+///
+pub async fn list_setlists_hdl(
+    Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
+    Extension(ref pool): Extension<PgPool>,
+    Query(params): Query<ListParams>,
+    flash: IncomingFlashes,
+) -> Result<Html<String>, AppError> {
+    let flash = flash
+        .into_iter()
+        .map(|(level, text)| format!("{:?}: {}", level, text))
+        .collect::<Vec<_>>()
+        .join(", ");
+    tracing::info!("flash : {}", flash);
+
+    let page = list_setlists_page(pool, &params).await?;
+
+    let title = "Programmes de concert";
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
+    ctx.insert("setlists", &page.items);
+    ctx.insert("page", &page);
+    ctx.insert("flash", &flash);
+
+    let body = templates
+        .render("setlists.html", &ctx)
+        .map_err(|err| AppError::Tera(err))?;
+
+    Ok(Html(body))
+}
+
+///
+/// Shows one setlist with its pieces, the forms to modify it
+/// and the total duration
+///
+/// Returns a HTML Page or AppError
+///
+pub async fn show_setlist_hdl(
+    Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
+    Extension(ref pool): Extension<PgPool>,
+    Path(id): Path<i32>,
+    flash: IncomingFlashes,
+) -> Result<Html<String>, AppError> {
+    let flash = flash
+        .into_iter()
+        .map(|(level, text)| format!("{:?}: {}", level, text))
+        .collect::<Vec<_>>()
+        .join(", ");
+    tracing::info!("flash : {}", flash);
+
+    let setlist = find_setlist_by_id(id, pool).await.map_err(not_found)?;
+    let items = list_setlist_items(id, pool).await?;
+    let (total_seconds, unknown_durations) = total_duration(&items);
+    // toutes les partitions pour le choix des pièces à ajouter
+    let partitions = filter_partitions(&PartitionFilter::default(), None, pool)
+        .await?
+        .items;
+
+    let title = format!("Programme : {}", setlist.name);
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
+    ctx.insert("setlist", &setlist);
+    ctx.insert("items", &items);
+    ctx.insert("total_seconds", &total_seconds);
+    ctx.insert("unknown_durations", &unknown_durations);
+    ctx.insert("partitions", &partitions);
+    ctx.insert("flash", &flash);
+
+    let body = templates
+        .render("setlist.html", &ctx)
+        .map_err(|err| AppError::Tera(err))?;
+
+    Ok(Html(body))
+}
+
+///
+/// Shows the printable program of a concert
+///
+/// Returns a HTML Page or AppError
+///
+pub async fn print_setlist_hdl(
+    Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
+    Extension(ref pool): Extension<PgPool>,
+    Path(id): Path<i32>,
+) -> Result<Html<String>, AppError> {
+    let setlist = find_setlist_by_id(id, pool).await.map_err(not_found)?;
+    let items = list_setlist_items(id, pool).await?;
+    let (total_seconds, unknown_durations) = total_duration(&items);
+
+    let title = setlist.name.clone();
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
+    ctx.insert("setlist", &setlist);
+    ctx.insert("items", &items);
+    ctx.insert("total_seconds", &total_seconds);
+    ctx.insert("unknown_durations", &unknown_durations);
+
+    let body = templates
+        .render("print_setlist.html", &ctx)
+        .map_err(|err| AppError::Tera(err))?;
+
+    Ok(Html(body))
+}
+
+///
+/// durée totale des pièces dont la durée est connue
+/// et nombre de pièces sans durée
+///
+fn total_duration(items: &[SetlistItem]) -> (i64, usize) {
+    let total = items
+        .iter()
+        .filter_map(|item| item.duration_seconds)
+        .map(i64::from)
+        .sum();
+    let unknown = items
+        .iter()
+        .filter(|item| item.duration_seconds.is_none())
+        .count();
+    (total, unknown)
+}
+
+fn not_found(err: sqlx::Error) -> AppError {
+    match err {
+        sqlx::Error::RowNotFound => AppError::NotFound,
+        e => AppError::Sqlx(e),
+    }
+}
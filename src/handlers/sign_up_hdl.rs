@@ -11,6 +11,7 @@ use axum_macros::debug_handler;
 use sqlx::PgPool;
 use tera::Tera;
 
+use crate::auth::current_user::MaybeUser;
 use crate::error::{AppError, SignupError};
 use crate::flash::{error_page, login_response, signup_response};
 use crate::auth::session::signup_session;
@@ -22,7 +23,6 @@ pub struct RegisterRequest {
     pub username: String,
     pub password: String,
     pub confirm_pwd: String,
-    pub role: String,
 }
 
 /*
@@ -52,6 +52,7 @@ impl TryFrom<RegisterRequest> for NewUser {
 #[debug_handler]
 pub async fn get_sign_up_hdl(
     templates: Extension<Tera>,
+    current_user: MaybeUser,
     flash: IncomingFlashes,
 ) -> Result<Html<String>, AppError> {
     let flash = flash
@@ -66,6 +67,7 @@ pub async fn get_sign_up_hdl(
 
     let mut ctx = tera::Context::new();
     ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
     ctx.insert("flash", &flash);
 
     let body = templates
@@ -101,11 +103,6 @@ pub async fn sign_up_hdl(
         let level = axum_flash::Level::Error;
         return Ok(signup_response(&mut flash, level, message));
     }
-    if form.role.is_empty() {
-        let message = format!("{}", SignupError::MissingRole);
-        let level = axum_flash::Level::Error;
-        return Ok(signup_response(&mut flash, level, message));
-    }
 
     // on vérifie si le mot de passe est bien confirmé
     if form.password != form.confirm_pwd {
@@ -119,7 +116,6 @@ pub async fn sign_up_hdl(
         session,
         &form.username.clone(),
         &form.password.clone(),
-        &form.role.clone(),
     )
     .await
     {
@@ -0,0 +1,231 @@
+//! src/handlers/tags_hdl.rs
+//!
+//! Étiquettes libres des partitions
+//! page de gestion des étiquettes (/tags) et étiquettes d'une partition
+//! (formulaires sur la page de la partition, /partitions/show/:id)
+
+use axum::extract::{Extension, Form, Path, Query};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::Html;
+use axum_flash::{Flash, IncomingFlashes};
+
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tera::Tera;
+
+use crate::auth::current_user::MaybeUser;
+use crate::db::partitions::find_partition_by_id;
+use crate::db::tags::*;
+
+use crate::error::AppError;
+use crate::flash::{partition_page_response, tag_response};
+use crate::models::audit::Actor;
+use crate::models::pagination::ListParams;
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TagForm {
+    pub name: String,
+}
+
+///
+/// formulaire de retrait d'une étiquette d'une partition
+///
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TagKey {
+    pub tag_id: i32,
+}
+
+//***********************************************************************************
+// CRUD Operations
+//
+
+///
+/// Create a new tag
+/// a tag with the same name (without regard to case) is refused
+///
+/// Returns to the list of tags with a flash message
+///
+pub async fn create_tag_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    form: Form<TagForm>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let name = form.0.name.trim().to_string();
+    if name.is_empty() {
+        let message = String::from("Erreur : l'étiquette est vide");
+        return Ok(tag_response(&mut flash, axum_flash::Level::Error, message));
+    }
+
+    let (level, message) = match add_tag(&name, &actor, pool).await {
+        Ok(tag) => (
+            axum_flash::Level::Success,
+            format!("Étiquette ajoutée : {}", tag.name),
+        ),
+        Err(e) => {
+            tracing::error!("db : tag not added : {}", e);
+            (
+                axum_flash::Level::Error,
+                format!("Erreur : l'étiquette {} existe déjà", name),
+            )
+        }
+    };
+    Ok(tag_response(&mut flash, level, message))
+}
+
+pub async fn update_tag_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    Path(id): Path<i32>,
+    form: Form<TagForm>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let name = form.0.name.trim().to_string();
+    if name.is_empty() {
+        let message = String::from("Erreur : l'étiquette est vide");
+        return Ok(tag_response(&mut flash, axum_flash::Level::Error, message));
+    }
+
+    let (level, message) = match update_tag(id, &name, &actor, pool).await {
+        Ok(tag) => (
+            axum_flash::Level::Success,
+            format!("Étiquette modifiée : {}", tag.name),
+        ),
+        Err(sqlx::Error::RowNotFound) => return Err(AppError::NotFound),
+        Err(e) => {
+            tracing::error!("db : tag not updated : {}", e);
+            (
+                axum_flash::Level::Error,
+                format!("Erreur : l'étiquette {} existe déjà", name),
+            )
+        }
+    };
+    Ok(tag_response(&mut flash, level, message))
+}
+
+///
+/// Deletes a tag, it is removed from all the partitions
+///
+pub async fn delete_tag_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    Path(id): Path<i32>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let name = delete_tag(id, &actor, pool).await?;
+    let message = format!("Étiquette effacée : {}", name);
+    Ok(tag_response(
+        &mut flash,
+        axum_flash::Level::Success,
+        message,
+    ))
+}
+
+//*******************************************************************************
+// Functions to show the list of tags
+//
+
+///
+/// Shows the page with the list of tags and the number of partitions of each one
+/// one page at a time : /tags?page=2&sort=count&dir=desc
+///
+/// Returns a HTML Page or AppError
+///
+pub async fn list_tags_hdl(
+    Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
+    Extension(ref pool): Extension<PgPool>,
+    Query(params): Query<ListParams>,
+    flash: IncomingFlashes,
+) -> Result<Html<String>, AppError> {
+    let flash = flash
+        .into_iter()
+        .map(|(level, text)| format!("{:?}: {}", level, text))
+        .collect::<Vec<_>>()
+        .join(", ");
+    tracing::info!("flash : {}", flash);
+
+    let page = list_tags_page(pool, &params).await?;
+
+    let title = "Gestion des Étiquettes";
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
+    ctx.insert("tags", &page.items);
+    ctx.insert("page", &page);
+    ctx.insert("flash", &flash);
+
+    let body = templates
+        .render("tags.html", &ctx)
+        .map_err(|err| AppError::Tera(err))?;
+
+    Ok(Html(body))
+}
+
+//****************************************************************************************
+// Étiquettes des partitions
+//
+
+///
+/// Puts a tag on the partition
+/// the tag is created when it does not exist yet
+///
+/// Returns to the page of the partition with a flash message
+///
+pub async fn add_partition_tag_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    Path(id): Path<i32>,
+    form: Form<TagForm>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let name = form.0.name.trim().to_string();
+    if name.is_empty() {
+        let message = String::from("Erreur : l'étiquette est vide");
+        let level = axum_flash::Level::Error;
+        return Ok(partition_page_response(&mut flash, level, message, id));
+    }
+
+    find_partition_by_id(id, pool).await?;
+    let tag = find_or_add_tag(&name, &actor, pool).await?;
+
+    let (level, message) = if add_partition_tag(id, tag.id.unwrap(), &actor, pool).await? {
+        (
+            axum_flash::Level::Success,
+            format!("Étiquette ajoutée : {}", tag.name),
+        )
+    } else {
+        (
+            axum_flash::Level::Warning,
+            format!("La partition a déjà l'étiquette {}", tag.name),
+        )
+    };
+    Ok(partition_page_response(&mut flash, level, message, id))
+}
+
+///
+/// Removes a tag from the partition (the tag itself is kept)
+///
+/// Returns to the page of the partition with a flash message
+///
+pub async fn delete_partition_tag_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    Path(id): Path<i32>,
+    form: Form<TagKey>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let (level, message) = if delete_partition_tag(id, form.0.tag_id, &actor, pool).await? {
+        (
+            axum_flash::Level::Success,
+            String::from("Étiquette retirée"),
+        )
+    } else {
+        (
+            axum_flash::Level::Warning,
+            String::from("La partition n'avait pas cette étiquette"),
+        )
+    };
+    Ok(partition_page_response(&mut flash, level, message, id))
+}
@@ -0,0 +1,71 @@
+//! src/handlers/token_hdl.rs
+//!
+//! Authentification par token JWT pour les clients de l'API (sans cookie)
+//! POST /auth/token : identifiants en JSON (LoginPayload) ou en-tête HTTP Basic
+//! POST /auth/token/refresh : échange un token de rafraîchissement contre de nouveaux tokens
+
+use axum::extract::Extension;
+use axum::http::HeaderMap;
+use axum::Json;
+
+use sqlx::PgPool;
+
+use crate::auth::jwt::{
+    basic_authentication, issue_tokens, verify_jwt, AuthBody, LoginPayload, RefreshPayload,
+    TokenKind,
+};
+use crate::auth::session::check_credentials;
+use crate::db::users::find_user_by_id;
+use crate::error::{ApiError, AppError};
+
+///
+/// Returns an AuthBody (access token + refresh token)
+/// if the credentials match the stored hash
+///
+pub async fn token_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    headers: HeaderMap,
+    payload: Option<Json<LoginPayload>>,
+) -> Result<Json<AuthBody>, ApiError> {
+    // le JSON a priorité, sinon on essaie l'en-tête Authorization: Basic
+    let credentials = match payload {
+        Some(Json(payload)) => payload,
+        None => basic_authentication(&headers)
+            .await
+            .map_err(|_| AppError::MissingCredentials)?,
+    };
+    if credentials.username.is_empty() || credentials.password.is_empty() {
+        return Err(AppError::MissingCredentials.into());
+    }
+
+    let user_id = check_credentials(pool, credentials.username.clone(), credentials.password)
+        .await
+        .map_err(|e| AppError::WrongCredentials(e.into()))?;
+
+    tracing::info!("token émis pour {}", credentials.username);
+    let body = issue_tokens(user_id, &credentials.username)?;
+    Ok(Json(body))
+}
+
+///
+/// Returns a new AuthBody from a valid refresh token
+///
+pub async fn refresh_token_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Json(payload): Json<RefreshPayload>,
+) -> Result<Json<AuthBody>, ApiError> {
+    let claims = verify_jwt(&payload.refresh_token).map_err(|_| AppError::InvalidJWTToken)?;
+    if claims.kind != TokenKind::Refresh {
+        return Err(AppError::InvalidJWTToken.into());
+    }
+
+    // l'utilisateur a pu être supprimé depuis l'émission du token
+    let user = match find_user_by_id(claims.sub, pool).await {
+        Ok(user) => user,
+        Err(sqlx::Error::RowNotFound) => return Err(AppError::InvalidJWTToken.into()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let body = issue_tokens(user.id, &user.name)?;
+    Ok(Json(body))
+}
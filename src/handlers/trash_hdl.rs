@@ -0,0 +1,139 @@
+//! src/handlers/trash_hdl.rs
+//!
+//! Pages de la corbeille, réservées aux éditeurs :
+//! /trash/partitions, /trash/persons et /trash/genres
+//! avec la restauration et la purge (effacement définitif) de chaque ligne
+
+use axum::extract::{Extension, Path};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::Html;
+use axum_flash::{Flash, IncomingFlashes};
+
+use sqlx::PgPool;
+use tera::Tera;
+
+use crate::auth::current_user::MaybeUser;
+use crate::db::trash::{list_trash, restore};
+use crate::error::AppError;
+use crate::flash::trash_response;
+use crate::models::audit::Actor;
+use crate::models::deletion::Deletion;
+use crate::models::trash::{Restoration, TrashKind};
+use crate::storage::SharedStorage;
+use crate::trash::{purge_with_files, retention_days};
+
+///
+/// Shows the trashed partitions, musicians or genres
+/// with the date of their automatic purge
+/// /trash/partitions, /trash/persons, /trash/genres
+///
+/// Returns a HTML Page or AppError (404 for an unknown trash)
+///
+pub async fn list_trash_hdl(
+    Extension(ref templates): Extension<Tera>,
+    current_user: MaybeUser,
+    Extension(ref pool): Extension<PgPool>,
+    Path(kind): Path<String>,
+    flash: IncomingFlashes,
+) -> Result<Html<String>, AppError> {
+    let flash = flash
+        .into_iter()
+        .map(|(level, text)| format!("{:?}: {}", level, text))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let kind: TrashKind = kind.parse().map_err(|_| AppError::NotFound)?;
+    let items = list_trash(kind, retention_days(), pool).await?;
+
+    let title = match kind {
+        TrashKind::Partitions => "Corbeille des Partitions",
+        TrashKind::Persons => "Corbeille des Musiciens",
+        TrashKind::Genres => "Corbeille des Genres",
+    };
+
+    let mut ctx = tera::Context::new();
+    ctx.insert("title", &title);
+    ctx.insert("current_user", &current_user.name());
+    ctx.insert("kind", &kind);
+    ctx.insert("items", &items);
+    ctx.insert("retention_days", &retention_days());
+    ctx.insert("flash", &flash);
+
+    let body = templates
+        .render("trash.html", &ctx)
+        .map_err(|err| AppError::Tera(err))?;
+
+    Ok(Html(body))
+}
+
+///
+/// Takes a partition, a musician or a genre out of the trash
+/// refused when its name has been given to another one meanwhile
+///
+/// Returns to the trash with a flash message
+///
+pub async fn restore_trash_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    actor: Actor,
+    Path((kind, id)): Path<(String, i32)>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let kind: TrashKind = kind.parse().map_err(|_| AppError::NotFound)?;
+    let (level, message) = match restore(kind, id, &actor, pool)
+        .await
+        .map_err(AppError::from)
+    {
+        Ok(Restoration { name, with }) if !with.is_empty() => (
+            axum_flash::Level::Success,
+            format!("Restauré : {} ; avec : {}", name, with.join(", ")),
+        ),
+        Ok(Restoration { name, .. }) => {
+            (axum_flash::Level::Success, format!("Restauré : {}", name))
+        }
+        Err(AppError::Duplicate(error)) => (
+            axum_flash::Level::Error,
+            format!(
+                "Pas restauré : {}, modifiez d'abord l'autre",
+                error.to_lowercase()
+            ),
+        ),
+        Err(e) => {
+            tracing::error!("db : {} {} not restored : {}", kind, id, e);
+            (
+                axum_flash::Level::Error,
+                String::from("Erreur : pas restauré"),
+            )
+        }
+    };
+    Ok(trash_response(&mut flash, level, message, kind.as_str()))
+}
+
+///
+/// Deletes a trashed partition, musician or genre for good ;
+/// refused for a musician or a genre still used by trashed partitions
+///
+/// Returns to the trash with a flash message
+///
+pub async fn purge_trash_hdl(
+    Extension(ref pool): Extension<PgPool>,
+    Extension(ref storage): Extension<SharedStorage>,
+    actor: Actor,
+    Path((kind, id)): Path<(String, i32)>,
+    mut flash: Flash,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let kind: TrashKind = kind.parse().map_err(|_| AppError::NotFound)?;
+    let (level, message) = match purge_with_files(kind, id, &actor, storage, pool).await? {
+        Deletion::Deleted { name, .. } => (
+            axum_flash::Level::Success,
+            format!("Effacé définitivement : {}", name),
+        ),
+        Deletion::Refused { name, dependents } => (
+            axum_flash::Level::Error,
+            format!(
+                "Pas effacé : {} partition(s) de la corbeille utilisent encore {}",
+                dependents, name
+            ),
+        ),
+    };
+    Ok(trash_response(&mut flash, level, message, kind.as_str()))
+}
@@ -6,8 +6,10 @@ mod error;
 mod flash;
 mod handlers;
 mod models;
+mod pdf;
 mod router;
-mod globals;
+mod storage;
+mod trash;
 mod utils;
 
 use std::str::FromStr;
@@ -26,10 +28,12 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use tera::Tera;
 
 use crate::auth::session::{new_ascd_creation_sqlx_session, new_axum_sqlx_session};
-use crate::db::connect::create_pg_pool;
+use crate::db::connect::{create_pg_pool, run_migrations};
 use crate::error::AppError;
 use crate::handlers::helpers_hdl::*;
 use crate::router::router;
+use crate::storage::storage_from_env;
+use crate::trash::spawn_purge_task;
 
 
 #[tokio::main]
@@ -50,6 +54,19 @@ async fn main() -> anyhow::Result<()> {
     // ici utilisation de sqlx
     let pool = create_pg_pool(&db_url).await?;
 
+    // les migrations sont appliquées à chaque démarrage
+    // "cargo run -- migrate" applique les migrations et s'arrête
+    run_migrations(&pool).await?;
+    if env::args().nth(1).as_deref() == Some("migrate") {
+        return Ok(());
+    }
+
+    // stockage des fichiers des partitions (STORAGE_BACKEND, STORAGE_DIR)
+    let storage = storage_from_env()?;
+
+    // purge de la corbeille après TRASH_RETENTION_DAYS jours
+    spawn_purge_task(pool.clone(), storage.clone());
+
     // Tera templates
     let templates = match Tera::new("templates/**/*.html") {
         Ok(t) => t,
@@ -89,6 +106,7 @@ async fn main() -> anyhow::Result<()> {
                     .layer(TraceLayer::new_for_http())
                     .layer(CookieManagerLayer::new())
                     .layer(Extension(pool))
+                    .layer(Extension(storage))
                     .layer(Extension(templates)))
                     .layer(axum_flash::layer(key).with_cookie_manager())
                     .layer(AxumSessionLayer::new(session));
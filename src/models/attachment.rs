@@ -0,0 +1,20 @@
+//! src/models/attachment.rs
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+///
+/// un fichier (PDF, MusicXML, image) attaché à une partition
+/// sha3_256 est aussi la clé du contenu dans le Storage
+///
+#[derive(Debug, Clone, Deserialize, Serialize, FromRow)]
+pub struct Attachment {
+    pub id: i32,
+    pub partition_id: i32,
+    pub file_name: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub sha3_256: String,
+    pub uploaded_at: DateTime<Utc>,
+}
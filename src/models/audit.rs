@@ -0,0 +1,221 @@
+//! src/models/audit.rs
+//!
+//! Journal des modifications (table audit_log, migration 0009) :
+//! qui a ajouté, modifié ou effacé un musicien, un genre, une partition ou un utilisateur
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::models::user::User;
+use crate::utils::serde_utils::empty_string_as_none;
+
+///
+/// l'auteur d'une modification, donné aux fonctions de la DB par les handlers
+/// (extracteur dans auth::current_user)
+///
+#[derive(Debug, Clone)]
+pub struct Actor {
+    pub id: Option<Uuid>,
+    pub name: String,
+}
+
+impl From<&User> for Actor {
+    fn from(user: &User) -> Self {
+        Actor {
+            id: Some(user.id),
+            name: user.name.clone(),
+        }
+    }
+}
+
+///
+/// enregistré en anglais dans la DB ('create', ...), affiché en français
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Create => "create",
+            AuditAction::Update => "update",
+            AuditAction::Delete => "delete",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AuditAction::Create => "ajout",
+            AuditAction::Update => "modification",
+            AuditAction::Delete => "effacement",
+        }
+    }
+}
+
+impl fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "create" => Ok(AuditAction::Create),
+            "update" => Ok(AuditAction::Update),
+            "delete" => Ok(AuditAction::Delete),
+            _ => Err(format!("action inconnue : {}", s)),
+        }
+    }
+}
+
+///
+/// la table modifiée ; entity_id est l'id de la ligne (un Uuid pour les utilisateurs)
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditEntity {
+    Person,
+    Genre,
+    Partition,
+    User,
+    Tag,
+}
+
+impl AuditEntity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEntity::Person => "person",
+            AuditEntity::Genre => "genre",
+            AuditEntity::Partition => "partition",
+            AuditEntity::User => "user",
+            AuditEntity::Tag => "tag",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AuditEntity::Person => "musicien",
+            AuditEntity::Genre => "genre",
+            AuditEntity::Partition => "partition",
+            AuditEntity::User => "utilisateur",
+            AuditEntity::Tag => "étiquette",
+        }
+    }
+}
+
+impl fmt::Display for AuditEntity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AuditEntity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "person" => Ok(AuditEntity::Person),
+            "genre" => Ok(AuditEntity::Genre),
+            "partition" => Ok(AuditEntity::Partition),
+            "user" => Ok(AuditEntity::User),
+            "tag" => Ok(AuditEntity::Tag),
+            _ => Err(format!("type inconnu : {}", s)),
+        }
+    }
+}
+
+///
+/// une ligne du journal
+/// changes : les champs qui diffèrent entre before et after, pour l'affichage
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub at: DateTime<Utc>,
+    pub actor_id: Option<Uuid>,
+    pub actor_name: String,
+    pub action: AuditAction,
+    pub action_label: String,
+    pub entity_type: AuditEntity,
+    pub entity_label: String,
+    pub entity_id: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub changes: Vec<FieldChange>,
+}
+
+///
+/// un champ modifié ; les valeurs sont affichées telles quelles ("" pour NULL)
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: String,
+    pub after: String,
+}
+
+///
+/// compare les deux copies de la ligne, champ par champ :
+/// un ajout donne tous les champs de after, un effacement tous ceux de before
+///
+pub fn field_changes(before: Option<&Value>, after: Option<&Value>) -> Vec<FieldChange> {
+    let empty = serde_json::Map::new();
+    let before = before.and_then(Value::as_object).unwrap_or(&empty);
+    let after = after.and_then(Value::as_object).unwrap_or(&empty);
+
+    let mut fields: Vec<&String> = before.keys().chain(after.keys()).collect();
+    fields.sort();
+    fields.dedup();
+
+    fields
+        .into_iter()
+        .filter(|field| before.get(*field) != after.get(*field))
+        .map(|field| FieldChange {
+            field: field.clone(),
+            before: display_value(before.get(field)),
+            after: display_value(after.get(field)),
+        })
+        .collect()
+}
+
+fn display_value(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(text)) => text.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+///
+/// critères de la page du journal : /audit?actor=leon&entity_type=partition&from=2026-10-01
+/// les critères vides sont ignorés ; to est compris (jusqu'à la fin du jour)
+///
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AuditFilter {
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub actor: Option<String>,
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub action: Option<AuditAction>,
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub entity_type: Option<AuditEntity>,
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub entity_id: Option<String>,
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub from: Option<NaiveDate>,
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub to: Option<NaiveDate>,
+}
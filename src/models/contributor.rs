@@ -0,0 +1,92 @@
+//! src/models/contributor.rs
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+///
+/// le rôle d'un musicien dans une partition
+/// enregistré en anglais dans la DB ('composer', ...), affiché en français
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContributorRole {
+    Composer,
+    Arranger,
+    Lyricist,
+}
+
+impl ContributorRole {
+    pub const ALL: [ContributorRole; 3] = [
+        ContributorRole::Composer,
+        ContributorRole::Arranger,
+        ContributorRole::Lyricist,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContributorRole::Composer => "composer",
+            ContributorRole::Arranger => "arranger",
+            ContributorRole::Lyricist => "lyricist",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ContributorRole::Composer => "compositeur",
+            ContributorRole::Arranger => "arrangeur",
+            ContributorRole::Lyricist => "parolier",
+        }
+    }
+}
+
+impl fmt::Display for ContributorRole {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ContributorRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "composer" => Ok(ContributorRole::Composer),
+            "arranger" => Ok(ContributorRole::Arranger),
+            "lyricist" => Ok(ContributorRole::Lyricist),
+            _ => Err(format!("rôle inconnu : {}", s)),
+        }
+    }
+}
+
+///
+/// un musicien et son rôle dans une partition
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct Contributor {
+    pub partition_id: i32,
+    pub person_id: i32,
+    pub full_name: String,
+    pub role: ContributorRole,
+    /// le rôle en français, pour les templates
+    pub role_label: String,
+}
+
+///
+/// formulaire d'ajout d'un musicien à une partition (page de la partition)
+///
+#[derive(Debug, Clone, Deserialize)]
+pub struct ContributorForm {
+    pub full_name: String,
+    pub role: ContributorRole,
+}
+
+///
+/// formulaire de retrait d'un musicien d'une partition
+///
+#[derive(Debug, Clone, Deserialize)]
+pub struct ContributorKey {
+    pub person_id: i32,
+    pub role: ContributorRole,
+}
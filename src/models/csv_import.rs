@@ -0,0 +1,80 @@
+//! src/models/csv_import.rs
+//!
+//! Les lignes d'un fichier CSV de partitions, vérifiées avant l'import
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::partition::PartitionMetadata;
+
+///
+/// les colonnes du CSV des partitions, dans l'ordre de l'export :
+/// (nom du champ, en-tête écrit par l'export)
+/// l'import accepte l'un ou l'autre dans l'en-tête, sans tenir compte des majuscules
+///
+pub const PARTITION_COLUMNS: [(&str, &str); 13] = [
+    ("title", "titre"),
+    ("composer", "compositeur"),
+    ("genre", "genre"),
+    ("instrumentation", "effectif"),
+    ("key", "tonalité"),
+    ("tempo", "tempo"),
+    ("difficulty", "difficulté"),
+    ("duration", "durée"),
+    ("opus", "opus"),
+    ("publisher", "éditeur"),
+    ("edition_year", "année"),
+    ("notes", "notes"),
+    ("tags", "étiquettes"),
+];
+
+///
+/// une ligne du fichier, avec ce qu'il faut pour l'enregistrer
+/// person_id et genre_id sont None quand le musicien ou le genre est à créer
+/// une ligne avec des erreurs bloque tout l'import
+///
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportRow {
+    /// numéro de la ligne dans le fichier (l'en-tête est la ligne 1)
+    pub line: u64,
+    pub title: String,
+    pub composer: String,
+    pub genre: String,
+    pub person_id: Option<i32>,
+    pub genre_id: Option<i32>,
+    #[serde(flatten)]
+    pub metadata: PartitionMetadata,
+    pub tags: Vec<String>,
+    pub errors: Vec<String>,
+    /// remarques qui n'empêchent pas l'import ("musicien créé : ...")
+    pub warnings: Vec<String>,
+}
+
+///
+/// le résultat de la vérification d'un fichier, montré avant l'import
+/// csv : le texte du fichier, renvoyé tel quel pour confirmer l'import
+///
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    pub rows: Vec<ImportRow>,
+    /// erreur qui concerne tout le fichier (en-tête, encodage, ...)
+    pub file_error: Option<String>,
+    pub error_count: usize,
+    pub create_missing: bool,
+    pub csv: String,
+}
+
+impl ImportReport {
+    pub fn is_valid(&self) -> bool {
+        self.file_error.is_none() && self.error_count == 0 && !self.rows.is_empty()
+    }
+}
+
+///
+/// le formulaire de confirmation de l'import, envoyé depuis l'aperçu
+///
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportForm {
+    pub csv: String,
+    #[serde(default)]
+    pub create_missing: Option<String>,
+}
@@ -0,0 +1,42 @@
+//! src/models/deletion.rs
+//!
+//! Effacement d'un musicien ou d'un genre dont dépendent des partitions
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::serde_utils::empty_string_as_none;
+
+///
+/// une partition qui dépend du musicien ou du genre à effacer
+/// main : c'est le musicien (ou le genre) principal de la partition
+/// roles : les rôles du musicien dans la partition, en français (vide pour un genre)
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct Dependent {
+    pub partition_id: i32,
+    pub title: String,
+    pub main: bool,
+    pub roles: Vec<String>,
+}
+
+///
+/// formulaire de la page de confirmation (ou query string de l'API) :
+/// sans reassign_to, l'effacement est refusé s'il reste des partitions
+///
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DeleteForm {
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub reassign_to: Option<i32>,
+}
+
+///
+/// le résultat d'une demande d'effacement
+///
+#[derive(Debug, Clone)]
+pub enum Deletion {
+    /// effacé ; reassigned : nombre de partitions passées à l'autre musicien ou genre
+    Deleted { name: String, reassigned: i64 },
+    /// rien n'a été modifié : des partitions en dépendent encore
+    Refused { name: String, dependents: i64 },
+}
@@ -3,9 +3,36 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
+use crate::utils::serde_utils::empty_string_as_none;
+use crate::utils::validation_utils::parse_text;
+
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, FromRow)]
 pub struct Genre {
     #[serde(skip_deserializing)]
     pub id: Option<i32>,
     pub name: String,
+    /// la version affichée par le formulaire (champ caché), voir models::version
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub version: Option<i32>,
+}
+
+///
+/// struct to protect the integrity of the genres' names :
+/// no useless spaces, neither empty nor longer than 100 characters
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenreName(String);
+
+impl AsRef<str> for GenreName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl GenreName {
+    /// Returns a `GenreName` if the input satisfies the rules of validation_utils::parse_text
+    /// or the message to show next to the field
+    pub fn parse(s: &str) -> Result<GenreName, String> {
+        parse_text(s, 100, "Le nom du genre").map(Self)
+    }
 }
@@ -0,0 +1,136 @@
+//! src/models/library.rs
+//!
+//! Bibliothèque : exemplaires imprimés des partitions et prêts aux membres
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::utils::serde_utils::empty_string_as_none;
+
+///
+/// état d'un lot d'exemplaires
+/// enregistré en anglais dans la DB ('good', ...), affiché en français
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CopyCondition {
+    New,
+    Good,
+    Worn,
+    Damaged,
+}
+
+impl CopyCondition {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CopyCondition::New => "new",
+            CopyCondition::Good => "good",
+            CopyCondition::Worn => "worn",
+            CopyCondition::Damaged => "damaged",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CopyCondition::New => "neuf",
+            CopyCondition::Good => "bon état",
+            CopyCondition::Worn => "usé",
+            CopyCondition::Damaged => "abîmé",
+        }
+    }
+}
+
+impl fmt::Display for CopyCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for CopyCondition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "new" => Ok(CopyCondition::New),
+            "good" => Ok(CopyCondition::Good),
+            "worn" => Ok(CopyCondition::Worn),
+            "damaged" => Ok(CopyCondition::Damaged),
+            _ => Err(format!("état inconnu : {}", s)),
+        }
+    }
+}
+
+///
+/// un lot d'exemplaires identiques d'une partition, rangés au même endroit
+/// on_loan : exemplaires prêtés en ce moment ; available = quantity - on_loan
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct PartitionCopy {
+    pub id: i32,
+    pub partition_id: i32,
+    pub quantity: i32,
+    /// étagère, boîte, armoire, ...
+    pub location: Option<String>,
+    pub condition: CopyCondition,
+    pub condition_label: String,
+    pub added_at: DateTime<Utc>,
+    pub on_loan: i64,
+    pub available: i64,
+}
+
+///
+/// résultat de la modification ou de l'effacement d'un lot d'exemplaires :
+/// refusé (OnLoan, avec le nombre d'exemplaires prêtés) quand il en resterait moins
+/// que d'exemplaires prêtés
+///
+#[derive(Debug, Clone)]
+pub enum CopyChange {
+    Done(PartitionCopy),
+    OnLoan(i64),
+}
+
+///
+/// un prêt d'exemplaires à un membre
+/// overdue : pas encore rendu et la date de retour est dépassée
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct Loan {
+    pub id: i32,
+    pub copy_id: i32,
+    pub partition_id: i32,
+    pub title: String,
+    pub location: Option<String>,
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub quantity: i32,
+    pub loaned_at: DateTime<Utc>,
+    pub due_date: NaiveDate,
+    pub returned_at: Option<DateTime<Utc>>,
+    pub overdue: bool,
+}
+
+///
+/// formulaire d'ajout ou de modification d'un lot d'exemplaires
+///
+#[derive(Debug, Clone, Deserialize)]
+pub struct CopyForm {
+    pub quantity: i32,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub location: Option<String>,
+    pub condition: CopyCondition,
+}
+
+///
+/// formulaire de prêt : le membre est donné par son nom d'utilisateur
+/// due_date au format du champ <input type="date"> : 2026-11-30
+///
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoanForm {
+    pub user_name: String,
+    pub quantity: i32,
+    pub due_date: NaiveDate,
+}
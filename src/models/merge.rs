@@ -0,0 +1,257 @@
+//! src/models/merge.rs
+//!
+//! Fusion des doublons de musiciens ou de genres
+//! ("J.S. Bach", "Bach, Johann Sebastian" et "Bach")
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+///
+/// un musicien ou un genre proposé comme doublon
+/// partitions : nombre de partitions qui en dépendent
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct Candidate {
+    pub id: i32,
+    pub name: String,
+    pub partitions: i64,
+}
+
+///
+/// deux noms proches ; score de 0 à 1 (similarité des trigrammes, sans accents ni majuscules)
+///
+#[derive(Debug, Clone)]
+pub struct SimilarPair {
+    pub first: Candidate,
+    pub second: Candidate,
+    pub score: f32,
+}
+
+///
+/// des noms reliés deux à deux par leur similarité, à fusionner en un seul
+/// les membres sont classés du plus utilisé au moins utilisé :
+/// le premier est proposé comme survivant
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateGroup {
+    pub members: Vec<Candidate>,
+    /// le meilleur score entre deux membres du groupe
+    pub score: f32,
+}
+
+///
+/// regroupe les paires qui ont un nom en commun :
+/// (A, B) et (B, C) donnent le groupe A, B, C
+///
+pub fn group_pairs(pairs: Vec<SimilarPair>) -> Vec<DuplicateGroup> {
+    // numéro de groupe de chaque id ; deux groupes reliés par une paire sont réunis
+    let mut group_of: HashMap<i32, usize> = HashMap::new();
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+
+    for pair in pairs {
+        let first = group_of.get(&pair.first.id).copied();
+        let second = group_of.get(&pair.second.id).copied();
+        let index = match (first, second) {
+            (Some(a), Some(b)) if a != b => {
+                let moved = std::mem::take(&mut groups[b].members);
+                for member in &moved {
+                    group_of.insert(member.id, a);
+                }
+                groups[a].members.extend(moved);
+                groups[a].score = groups[a].score.max(groups[b].score);
+                a
+            }
+            (Some(a), _) => a,
+            (None, Some(b)) => b,
+            (None, None) => {
+                groups.push(DuplicateGroup {
+                    members: Vec::new(),
+                    score: 0.0,
+                });
+                groups.len() - 1
+            }
+        };
+        for candidate in [pair.first, pair.second] {
+            if let Entry::Vacant(entry) = group_of.entry(candidate.id) {
+                entry.insert(index);
+                groups[index].members.push(candidate);
+            }
+        }
+        groups[index].score = groups[index].score.max(pair.score);
+    }
+
+    let mut groups: Vec<DuplicateGroup> = groups
+        .into_iter()
+        .filter(|group| group.members.len() > 1)
+        .collect();
+    for group in &mut groups {
+        group
+            .members
+            .sort_by(|a, b| b.partitions.cmp(&a.partitions).then(a.name.cmp(&b.name)));
+    }
+    groups.sort_by(|a, b| b.score.total_cmp(&a.score));
+    groups
+}
+
+///
+/// le formulaire de fusion : survivor=3&duplicates=7&duplicates=12
+/// (les cases cochées répètent le champ "duplicates",
+/// le formulaire est donc lu comme une liste de paires)
+///
+#[derive(Debug, Clone)]
+pub struct MergeForm {
+    pub survivor: i32,
+    pub duplicates: Vec<i32>,
+}
+
+impl MergeForm {
+    pub fn from_pairs(pairs: &[(String, String)]) -> Result<Self, String> {
+        let mut survivor = None;
+        let mut duplicates = Vec::new();
+        for (key, value) in pairs {
+            let id = value
+                .parse::<i32>()
+                .map_err(|_| format!("identifiant incorrect : {}", value))?;
+            match key.as_str() {
+                "survivor" => survivor = Some(id),
+                "duplicates" => duplicates.push(id),
+                _ => (),
+            }
+        }
+        let survivor = survivor.ok_or_else(|| "aucun nom à garder n'est choisi".to_string())?;
+        duplicates.retain(|&id| id != survivor);
+        duplicates.sort_unstable();
+        duplicates.dedup();
+        if duplicates.is_empty() {
+            return Err("aucun doublon à fusionner n'est choisi".to_string());
+        }
+        Ok(MergeForm {
+            survivor,
+            duplicates,
+        })
+    }
+}
+
+///
+/// seuil de similarité du rapport : /persons/duplicates?threshold=0.6
+///
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DuplicatesQuery {
+    pub threshold: f32,
+}
+
+impl Default for DuplicatesQuery {
+    fn default() -> Self {
+        DuplicatesQuery { threshold: 0.5 }
+    }
+}
+
+impl DuplicatesQuery {
+    /// un seuil trop bas rendrait le rapport illisible
+    pub fn threshold(&self) -> f32 {
+        self.threshold.clamp(0.2, 1.0)
+    }
+}
+
+///
+/// le résultat d'une fusion
+///
+#[derive(Debug, Clone)]
+pub struct MergeResult {
+    pub survivor: String,
+    pub merged: Vec<String>,
+    /// nombre de partitions passées au survivant
+    pub partitions: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(id: i32, partitions: i64) -> Candidate {
+        Candidate {
+            id,
+            name: format!("nom {}", id),
+            partitions,
+        }
+    }
+
+    fn pair(first: Candidate, second: Candidate, score: f32) -> SimilarPair {
+        SimilarPair {
+            first,
+            second,
+            score,
+        }
+    }
+
+    fn ids(group: &DuplicateGroup) -> Vec<i32> {
+        group.members.iter().map(|member| member.id).collect()
+    }
+
+    fn form(pairs: &[(&str, &str)]) -> Result<MergeForm, String> {
+        let pairs: Vec<(String, String)> = pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        MergeForm::from_pairs(&pairs)
+    }
+
+    #[test]
+    fn pairs_sharing_a_name_make_one_group() {
+        let groups = group_pairs(vec![
+            pair(candidate(1, 2), candidate(2, 5), 0.6),
+            pair(candidate(2, 5), candidate(3, 0), 0.8),
+        ]);
+        assert_eq!(groups.len(), 1);
+        // le plus utilisé d'abord : c'est le survivant proposé
+        assert_eq!(ids(&groups[0]), vec![2, 1, 3]);
+        assert_eq!(groups[0].score, 0.8);
+    }
+
+    #[test]
+    fn a_pair_joins_two_groups() {
+        let groups = group_pairs(vec![
+            pair(candidate(1, 1), candidate(2, 1), 0.9),
+            pair(candidate(3, 4), candidate(4, 1), 0.5),
+            pair(candidate(2, 1), candidate(3, 4), 0.6),
+        ]);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(ids(&groups[0]), vec![3, 1, 2, 4]);
+        assert_eq!(groups[0].score, 0.9);
+    }
+
+    #[test]
+    fn groups_are_sorted_by_best_score() {
+        let groups = group_pairs(vec![
+            pair(candidate(1, 0), candidate(2, 0), 0.5),
+            pair(candidate(3, 0), candidate(4, 0), 0.7),
+        ]);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(ids(&groups[0]), vec![3, 4]);
+        assert_eq!(ids(&groups[1]), vec![1, 2]);
+    }
+
+    #[test]
+    fn merge_form_drops_the_survivor_and_repeated_duplicates() {
+        let merge = form(&[
+            ("survivor", "3"),
+            ("duplicates", "12"),
+            ("duplicates", "3"),
+            ("duplicates", "7"),
+            ("duplicates", "12"),
+        ])
+        .unwrap();
+        assert_eq!(merge.survivor, 3);
+        assert_eq!(merge.duplicates, vec![7, 12]);
+    }
+
+    #[test]
+    fn merge_form_needs_a_survivor_and_a_duplicate() {
+        assert!(form(&[("duplicates", "7")]).is_err());
+        assert!(form(&[("survivor", "3"), ("duplicates", "3")]).is_err());
+        assert!(form(&[("survivor", "3"), ("duplicates", "x")]).is_err());
+    }
+}
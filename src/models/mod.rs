@@ -1,6 +1,18 @@
 //! src/models/mod.rs
 
+pub mod attachment;
+pub mod audit;
+pub mod contributor;
+pub mod csv_import;
+pub mod deletion;
 pub mod genre;
+pub mod library;
+pub mod merge;
 pub mod musician;
+pub mod pagination;
 pub mod partition;
+pub mod setlist;
+pub mod tag;
+pub mod trash;
 pub mod user;
+pub mod version;
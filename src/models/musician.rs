@@ -2,10 +2,37 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
+use crate::utils::serde_utils::empty_string_as_none;
+use crate::utils::validation_utils::parse_text;
+
 // this struct will be used to represent database record
 #[derive(Clone, Serialize, Deserialize, FromRow, Debug, Eq, PartialEq)]
 pub struct Person {
     #[serde(skip_deserializing)]
     pub id: Option<i32>,
     pub full_name: String,
+    /// la version affichée par le formulaire (champ caché), voir models::version
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub version: Option<i32>,
+}
+
+///
+/// struct to protect the integrity of the musicians' names :
+/// no useless spaces, neither empty nor longer than 200 characters
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PersonName(String);
+
+impl AsRef<str> for PersonName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PersonName {
+    /// Returns a `PersonName` if the input satisfies the rules of validation_utils::parse_text
+    /// or the message to show next to the field
+    pub fn parse(s: &str) -> Result<PersonName, String> {
+        parse_text(s, 200, "Le nom du musicien").map(Self)
+    }
 }
@@ -0,0 +1,149 @@
+//! src/models/pagination.rs
+//!
+//! Pagination et tri des listes
+//! ListParams vient de la query string : ?page=2&per_page=50&sort=name&dir=desc
+//! Page<T> est renvoyée par les fonctions list_*_page de la DB
+
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PER_PAGE: i64 = 50;
+pub const MAX_PER_PAGE: i64 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDir {
+    Asc,
+    Desc,
+}
+
+impl Default for SortDir {
+    fn default() -> Self {
+        SortDir::Asc
+    }
+}
+
+impl SortDir {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            SortDir::Asc => "ASC",
+            SortDir::Desc => "DESC",
+        }
+    }
+}
+
+///
+/// paramètres de pagination et de tri
+/// la colonne de tri est vérifiée par chaque fonction de la DB
+/// (liste blanche) avant d'être utilisée dans la requête
+///
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ListParams {
+    pub page: i64,
+    pub per_page: i64,
+    pub sort: Option<String>,
+    pub dir: SortDir,
+}
+
+impl Default for ListParams {
+    fn default() -> Self {
+        Self {
+            page: 1,
+            per_page: DEFAULT_PER_PAGE,
+            sort: None,
+            dir: SortDir::Asc,
+        }
+    }
+}
+
+impl ListParams {
+    pub fn limit(&self) -> i64 {
+        self.per_page.clamp(1, MAX_PER_PAGE)
+    }
+
+    ///
+    /// saturée plutôt que de déborder pour un numéro de page énorme
+    /// (?page=9223372036854775807) : la page est simplement vide
+    ///
+    pub fn offset(&self) -> i64 {
+        (self.page.max(1) - 1).saturating_mul(self.limit())
+    }
+}
+
+///
+/// une page de résultats avec le nombre total d'enregistrements
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+    pub total_pages: i64,
+    pub sort: String,
+    pub dir: SortDir,
+}
+
+impl<T> Page<T> {
+    ///
+    /// sort : le nom de la colonne de tri réellement utilisée
+    ///
+    pub fn new(items: Vec<T>, total: i64, params: &ListParams, sort: &str) -> Self {
+        let per_page = params.limit();
+        Self {
+            items,
+            total,
+            page: params.page.max(1),
+            per_page,
+            total_pages: ((total + per_page - 1) / per_page).max(1),
+            sort: sort.to_string(),
+            dir: params.dir,
+        }
+    }
+
+    ///
+    /// toutes les lignes en une seule page (impression)
+    ///
+    pub fn all(items: Vec<T>, sort: &str, dir: SortDir) -> Self {
+        let total = items.len() as i64;
+        Self {
+            items,
+            total,
+            page: 1,
+            per_page: total.max(1),
+            total_pages: 1,
+            sort: sort.to_string(),
+            dir,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offset_of_the_first_pages() {
+        let params = ListParams {
+            page: 3,
+            per_page: 20,
+            ..Default::default()
+        };
+        assert_eq!(params.offset(), 40);
+        let params = ListParams {
+            page: 0,
+            ..Default::default()
+        };
+        assert_eq!(params.offset(), 0);
+    }
+
+    #[test]
+    fn offset_of_a_huge_page_does_not_overflow() {
+        let params = ListParams {
+            page: i64::MAX,
+            per_page: 100,
+            ..Default::default()
+        };
+        assert_eq!(params.offset(), i64::MAX);
+    }
+}
@@ -3,6 +3,14 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
+use crate::error::AppError;
+use crate::models::contributor::{Contributor, ContributorRole};
+use crate::models::genre::{Genre, GenreName};
+use crate::models::musician::PersonName;
+use crate::models::tag::Tag;
+use crate::utils::serde_utils::{duration_as_seconds, empty_string_as_none};
+use crate::utils::validation_utils::parse_text;
+
 #[derive(Debug, Clone, Deserialize, Serialize, FromRow)]
 pub struct Partition {
     #[serde(skip_deserializing)]
@@ -10,6 +18,46 @@ pub struct Partition {
     pub title: String,
     pub person_id: i32,
     pub genre_id: i32,
+    /// la version lue, à renvoyer avec une modification (voir models::version)
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub version: Option<i32>,
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub metadata: PartitionMetadata,
+}
+
+///
+/// les renseignements facultatifs d'une partition
+/// partagés par Partition (API) et ShowPartition (pages HTML)
+/// un champ vide d'un formulaire est enregistré comme NULL
+///
+#[derive(Debug, Clone, Default, Deserialize, Serialize, FromRow)]
+#[serde(default)]
+pub struct PartitionMetadata {
+    /// effectif : "piano", "SATB", "quatuor à cordes", ...
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub instrumentation: Option<String>,
+    /// tonalité : "ré mineur", "Bb", ...
+    #[serde(rename = "key", deserialize_with = "empty_string_as_none")]
+    pub music_key: Option<String>,
+    /// indication de tempo : "Allegro", "♩ = 120", ...
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub tempo: Option<String>,
+    /// niveau de difficulté de 1 (facile) à 5 (virtuose)
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub difficulty: Option<i16>,
+    /// durée en secondes ; le formulaire accepte aussi "4:05"
+    #[serde(deserialize_with = "duration_as_seconds")]
+    pub duration_seconds: Option<i32>,
+    /// numéro d'opus ou de catalogue : "op. 27 n° 2", "BWV 1007", ...
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub opus: Option<String>,
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub publisher: Option<String>,
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub edition_year: Option<i32>,
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub notes: Option<String>,
 }
 
 ///
@@ -23,4 +71,181 @@ pub struct ShowPartition {
     pub title: String,
     pub full_name: String,
     pub name: String,
+    /// la version affichée par le formulaire (champ caché), voir models::version
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub version: Option<i32>,
+    /// coché dans le formulaire d'ajout pour ajouter la partition
+    /// malgré une partition du même titre et du même compositeur
+    #[serde(default, skip_serializing)]
+    pub duplicate_ok: bool,
+    #[serde(flatten)]
+    pub metadata: PartitionMetadata,
+    /// tous les musiciens de la partition avec leur rôle
+    /// (remplis par la DB, pas par les formulaires)
+    #[serde(default, skip_deserializing)]
+    pub contributors: Vec<Contributor>,
+    /// tous les genres de la partition, le genre principal compris
+    #[serde(default, skip_deserializing)]
+    pub genres: Vec<Genre>,
+    #[serde(default, skip_deserializing)]
+    pub tags: Vec<Tag>,
+}
+
+///
+/// struct to protect the integrity of the partitions' titles :
+/// no useless spaces, neither empty nor longer than 300 characters
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionTitle(String);
+
+impl AsRef<str> for PartitionTitle {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartitionTitle {
+    /// Returns a `PartitionTitle` if the input satisfies the rules of validation_utils::parse_text
+    /// or the message to show next to the field
+    pub fn parse(s: &str) -> Result<PartitionTitle, String> {
+        parse_text(s, 300, "Le titre").map(Self)
+    }
+}
+
+///
+/// NewPartition
+///
+/// le formulaire d'ajout ou de modification d'une partition (ShowPartition)
+/// une fois validé : titre, musicien et genre principaux
+///
+#[derive(Debug, Clone)]
+pub struct NewPartition {
+    pub title: PartitionTitle,
+    pub full_name: PersonName,
+    pub name: GenreName,
+    pub metadata: PartitionMetadata,
+    pub version: Option<i32>,
+}
+
+impl NewPartition {
+    /// Returns a `NewPartition`, or AppError::UnprocessableEntity
+    /// with the errors of all the fields at once (keys : the names of the fields)
+    pub fn parse(form: &ShowPartition) -> Result<NewPartition, AppError> {
+        let title = PartitionTitle::parse(&form.title);
+        let full_name = PersonName::parse(&form.full_name);
+        let name = GenreName::parse(&form.name);
+        match (title, full_name, name) {
+            (Ok(title), Ok(full_name), Ok(name)) => Ok(NewPartition {
+                title,
+                full_name,
+                name,
+                metadata: form.metadata.clone(),
+                version: form.version,
+            }),
+            (title, full_name, name) => Err(AppError::unprocessable_entity(
+                [
+                    ("title", title.err()),
+                    ("full_name", full_name.err()),
+                    ("name", name.err()),
+                ]
+                .into_iter()
+                .filter_map(|(field, error)| error.map(|error| (field, error))),
+            )),
+        }
+    }
+}
+
+///
+/// une partition déjà au catalogue avec le même titre et le même compositeur
+/// (sans majuscules, accents ni espaces autour) qu'une partition à ajouter
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicatePartition {
+    pub id: i32,
+    pub title: String,
+    pub full_name: String,
+}
+
+///
+/// un résultat de la recherche plein texte
+/// les champs *_hl contiennent le texte échappé pour le HTML
+/// avec les mots trouvés entourés de <mark></mark>
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchPartition {
+    pub id: i32,
+    pub title: String,
+    pub full_name: String,
+    pub name: String,
+    pub rank: f32,
+    pub title_hl: String,
+    pub full_name_hl: String,
+    pub name_hl: String,
+}
+
+///
+/// critères de filtre des partitions, tous facultatifs et combinés par ET :
+/// /partitions?title=S&author=Ellington&role=arranger&genre=jazz&tag=noël&difficulty_max=3
+/// titre : commence par ; tonalité : égale ; textes : contient
+/// (sans tenir compte des accents ni des majuscules)
+/// difficulté, durée et année : bornes incluses
+///
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PartitionFilter {
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub title: Option<String>,
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub author: Option<String>,
+    /// limite le musicien (ou tous les musiciens) à un rôle
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub role: Option<ContributorRole>,
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub genre: Option<String>,
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub tag: Option<String>,
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub instrumentation: Option<String>,
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub key: Option<String>,
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub tempo: Option<String>,
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub difficulty_min: Option<i16>,
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub difficulty_max: Option<i16>,
+    /// durée maximale, "4:00" ou en secondes
+    #[serde(deserialize_with = "duration_as_seconds")]
+    pub duration_max: Option<i32>,
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub opus: Option<String>,
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub publisher: Option<String>,
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub year_from: Option<i32>,
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub year_to: Option<i32>,
+    #[serde(deserialize_with = "empty_string_as_none")]
+    pub notes: Option<String>,
+}
+
+impl PartitionFilter {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.author.is_none()
+            && self.role.is_none()
+            && self.genre.is_none()
+            && self.tag.is_none()
+            && self.instrumentation.is_none()
+            && self.key.is_none()
+            && self.tempo.is_none()
+            && self.difficulty_min.is_none()
+            && self.difficulty_max.is_none()
+            && self.duration_max.is_none()
+            && self.opus.is_none()
+            && self.publisher.is_none()
+            && self.year_from.is_none()
+            && self.year_to.is_none()
+            && self.notes.is_none()
+    }
 }
@@ -0,0 +1,91 @@
+//! src/models/setlist.rs
+//!
+//! Programmes de concert composés à partir des partitions du catalogue
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::serde_utils::empty_string_as_none;
+
+///
+/// un programme : nom, date et lieu du concert
+/// un champ vide d'un formulaire est enregistré comme NULL
+///
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Setlist {
+    #[serde(skip_deserializing)]
+    pub id: Option<i32>,
+    pub name: String,
+    /// au format du champ <input type="date"> : 2026-12-20
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub concert_date: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub venue: Option<String>,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub notes: Option<String>,
+}
+
+///
+/// un programme dans la liste des programmes
+/// total_seconds : somme des durées connues des pièces
+/// unknown_durations : nombre de pièces sans durée
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct SetlistSummary {
+    #[serde(flatten)]
+    pub setlist: Setlist,
+    pub item_count: i64,
+    pub total_seconds: i64,
+    pub unknown_durations: i64,
+}
+
+///
+/// une pièce d'un programme, avec ce qu'il faut de la partition pour l'afficher
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct SetlistItem {
+    pub id: i32,
+    pub setlist_id: i32,
+    pub position: i32,
+    pub partition_id: i32,
+    pub title: String,
+    pub full_name: String,
+    pub instrumentation: Option<String>,
+    pub music_key: Option<String>,
+    pub duration_seconds: Option<i32>,
+    pub notes: Option<String>,
+}
+
+///
+/// formulaire d'ajout d'une pièce à un programme
+///
+#[derive(Debug, Clone, Deserialize)]
+pub struct SetlistItemForm {
+    pub partition_id: i32,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub notes: Option<String>,
+}
+
+///
+/// formulaire de modification des notes d'une pièce
+///
+#[derive(Debug, Clone, Deserialize)]
+pub struct SetlistItemNotes {
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub notes: Option<String>,
+}
+
+///
+/// déplacement d'une pièce d'une place vers le haut ou vers le bas
+///
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MoveDirection {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MoveForm {
+    pub direction: MoveDirection,
+}
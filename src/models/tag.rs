@@ -0,0 +1,26 @@
+//! src/models/tag.rs
+
+use serde::{Deserialize, Serialize};
+
+///
+/// une étiquette libre ("Noël", "mariage", "débutants", ...)
+/// attachée à autant de partitions que l'on veut
+///
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct Tag {
+    #[serde(skip_deserializing)]
+    pub id: Option<i32>,
+    pub name: String,
+}
+
+///
+/// une étiquette avec le nombre de partitions qui la portent
+/// weight (de 1 à 5) donne la taille du mot dans le nuage d'étiquettes
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct TagCount {
+    pub id: i32,
+    pub name: String,
+    pub count: i64,
+    pub weight: u8,
+}
@@ -0,0 +1,107 @@
+//! src/models/trash.rs
+//!
+//! Corbeille des partitions, des musiciens et des genres (colonne deleted_at, migration 0010)
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::audit::AuditEntity;
+
+///
+/// ce qui peut aller à la corbeille ; le texte est celui des adresses :
+/// /trash/partitions, /trash/persons, /trash/genres
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrashKind {
+    Partitions,
+    Persons,
+    Genres,
+}
+
+impl TrashKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrashKind::Partitions => "partitions",
+            TrashKind::Persons => "persons",
+            TrashKind::Genres => "genres",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TrashKind::Partitions => "partition",
+            TrashKind::Persons => "musicien",
+            TrashKind::Genres => "genre",
+        }
+    }
+
+    /// la table, qui porte aussi le nom de l'adresse
+    pub fn table(&self) -> &'static str {
+        self.as_str()
+    }
+
+    /// la colonne affichée dans la corbeille
+    pub fn name_column(&self) -> &'static str {
+        match self {
+            TrashKind::Partitions => "title",
+            TrashKind::Persons => "full_name",
+            TrashKind::Genres => "name",
+        }
+    }
+
+    pub fn entity(&self) -> AuditEntity {
+        match self {
+            TrashKind::Partitions => AuditEntity::Partition,
+            TrashKind::Persons => AuditEntity::Person,
+            TrashKind::Genres => AuditEntity::Genre,
+        }
+    }
+}
+
+impl fmt::Display for TrashKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TrashKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "partitions" => Ok(TrashKind::Partitions),
+            "persons" => Ok(TrashKind::Persons),
+            "genres" => Ok(TrashKind::Genres),
+            _ => Err(format!("corbeille inconnue : {}", s)),
+        }
+    }
+}
+
+///
+/// une ligne de la corbeille
+/// purge_at : la date de la purge automatique (None si elle est désactivée)
+/// references : les partitions (à la corbeille) qui utilisent encore
+/// le musicien ou le genre ; il ne peut pas être purgé avant elles
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct TrashedItem {
+    pub id: i32,
+    pub name: String,
+    pub deleted_at: DateTime<Utc>,
+    pub purge_at: Option<DateTime<Utc>>,
+    pub references: i64,
+}
+
+///
+/// le résultat d'une restauration
+/// with : les musiciens et les genres de la partition restaurés avec elle
+///
+#[derive(Debug, Clone)]
+pub struct Restoration {
+    pub name: String,
+    pub with: Vec<String>,
+}
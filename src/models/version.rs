@@ -0,0 +1,79 @@
+//! src/models/version.rs
+//!
+//! Verrouillage optimiste : persons, genres et partitions ont une colonne version
+//! (migration 0011) incrémentée à chaque modification
+
+use serde::Serialize;
+
+use crate::error::AppError;
+
+///
+/// le résultat d'une modification qui vérifie la version de la ligne
+/// (la version est obligatoire, voir required_version)
+///
+#[derive(Debug, Clone)]
+pub enum Update<T> {
+    /// enregistré : la ligne avec sa nouvelle version
+    Saved(T),
+    /// rien n'a été modifié : la ligne a changé depuis la version envoyée ;
+    /// current : son état actuel
+    Conflict { current: T },
+}
+
+///
+/// la version renvoyée avec une modification (champ caché du formulaire, "version" du JSON)
+/// est obligatoire : sans elle, la modification écraserait celles faites entre-temps ;
+/// "enregistrer quand même" (conflict.html) renvoie la version enregistrée
+/// Renvoie la version, ou AppError::UnprocessableEntity sur le champ "version"
+///
+pub fn required_version(version: Option<i32>) -> Result<i32, AppError> {
+    version.ok_or_else(|| {
+        AppError::unprocessable_entity([(
+            "version",
+            "la version de la ligne modifiée est obligatoire",
+        )])
+    })
+}
+
+///
+/// la page de conflit : la version envoyée par l'utilisateur
+/// à côté de la version enregistrée entre-temps
+/// action : l'adresse du formulaire, pour enregistrer quand même sa version
+/// back : la page à rouvrir pour repartir de la version enregistrée
+/// version : la version enregistrée, renvoyée avec "enregistrer quand même"
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct ConflictPage {
+    pub what: String,
+    pub action: String,
+    pub back: String,
+    pub version: Option<i32>,
+    pub fields: Vec<ConflictField>,
+}
+
+///
+/// un champ du formulaire : name est le nom du champ,
+/// mine la valeur envoyée, current la valeur enregistrée
+/// changed : les deux valeurs diffèrent (mises en évidence sur la page)
+///
+#[derive(Debug, Clone, Serialize)]
+pub struct ConflictField {
+    pub name: &'static str,
+    pub label: &'static str,
+    pub mine: String,
+    pub current: String,
+    pub changed: bool,
+}
+
+impl ConflictField {
+    pub fn new(name: &'static str, label: &'static str, mine: String, current: String) -> Self {
+        let changed = mine != current;
+        ConflictField {
+            name,
+            label,
+            mine,
+            current,
+            changed,
+        }
+    }
+}
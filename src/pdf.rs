@@ -0,0 +1,497 @@
+//! src/pdf.rs
+//!
+//! Génération des listes imprimables en PDF, sans bibliothèque ni service externe
+//!
+//! Le document est un tableau sur des pages A4 :
+//! - en-tête de chaque page : titre de la liste, date d'impression et en-têtes des colonnes
+//! - pied de page : "Page n / N"
+//! - les lignes peuvent être regroupées sous des intertitres (par compositeur, par genre, ...)
+//!
+//! Seules les polices standard Helvetica et Helvetica-Bold sont utilisées :
+//! tout lecteur PDF les connaît, il n'y a donc aucun fichier de police à embarquer.
+//! Le texte est encodé en WinAnsi, qui contient les lettres accentuées du français ;
+//! un caractère absent de cet encodage est remplacé par "?".
+//! Le texte trop long pour sa colonne est coupé et terminé par "…".
+
+use std::fmt::Write;
+
+use axum::http::{header, HeaderValue};
+use axum::response::{IntoResponse, Response};
+
+// format A4 en points (1/72 de pouce)
+const PAGE_WIDTH: f32 = 595.28;
+const PAGE_HEIGHT: f32 = 841.89;
+const MARGIN: f32 = 42.0;
+
+const TITLE_SIZE: f32 = 14.0;
+const TEXT_SIZE: f32 = 9.0;
+const SMALL_SIZE: f32 = 8.0;
+const LINE_HEIGHT: f32 = 13.0;
+const GROUP_HEIGHT: f32 = 18.0;
+const CELL_PADDING: f32 = 4.0;
+
+// première ligne du corps de la page, sous l'en-tête, et limite basse avant le pied de page
+const BODY_TOP: f32 = PAGE_HEIGHT - MARGIN - 52.0;
+const BODY_BOTTOM: f32 = MARGIN + 24.0;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn name(self) -> &'static str {
+        match self {
+            Font::Regular => "/F1",
+            Font::Bold => "/F2",
+        }
+    }
+}
+
+struct Column {
+    header: String,
+    x: f32,
+    width: f32,
+}
+
+///
+/// une liste en tableau, remplie ligne par ligne puis écrite par finish()
+///
+pub struct PdfTable {
+    title: String,
+    date: String,
+    columns: Vec<Column>,
+    /// le contenu de chaque page terminée
+    pages: Vec<String>,
+    current: String,
+    y: f32,
+}
+
+impl PdfTable {
+    ///
+    /// title : le titre répété en haut de chaque page
+    /// columns : (en-tête, largeur relative) ; les largeurs sont réparties
+    /// sur toute la largeur utile de la page
+    ///
+    pub fn new(title: &str, columns: &[(&str, f32)]) -> Self {
+        let total: f32 = columns.iter().map(|(_, width)| width).sum();
+        let usable = PAGE_WIDTH - 2.0 * MARGIN;
+        let mut x = MARGIN;
+        let columns = columns
+            .iter()
+            .map(|(header, width)| {
+                let width = usable * width / total;
+                let column = Column {
+                    header: header.to_string(),
+                    x,
+                    width,
+                };
+                x += width;
+                column
+            })
+            .collect();
+
+        let mut table = PdfTable {
+            title: title.to_string(),
+            date: chrono::Local::now().format("%d/%m/%Y").to_string(),
+            columns,
+            pages: Vec::new(),
+            current: String::new(),
+            y: 0.0,
+        };
+        table.start_page();
+        table
+    }
+
+    ///
+    /// un intertitre sur fond gris ; il n'est jamais laissé seul en bas de page
+    ///
+    pub fn group(&mut self, heading: &str) {
+        if self.y - GROUP_HEIGHT - LINE_HEIGHT < BODY_BOTTOM {
+            self.new_page();
+        }
+        self.y -= GROUP_HEIGHT;
+        let _ = writeln!(
+            self.current,
+            "0.88 g {} {} {} {} re f 0 g",
+            MARGIN,
+            self.y - 4.0,
+            PAGE_WIDTH - 2.0 * MARGIN,
+            GROUP_HEIGHT - 2.0
+        );
+        let heading = fit(heading, Font::Bold, 10.0, PAGE_WIDTH - 2.0 * MARGIN);
+        text(
+            &mut self.current,
+            Font::Bold,
+            10.0,
+            MARGIN + CELL_PADDING,
+            self.y + 1.0,
+            &heading,
+        );
+    }
+
+    ///
+    /// une ligne du tableau : une cellule par colonne, dans l'ordre de new()
+    ///
+    pub fn row(&mut self, cells: &[&str]) {
+        if self.y - LINE_HEIGHT < BODY_BOTTOM {
+            self.new_page();
+        }
+        self.y -= LINE_HEIGHT;
+        for (column, cell) in self.columns.iter().zip(cells) {
+            let cell = fit(
+                cell,
+                Font::Regular,
+                TEXT_SIZE,
+                column.width - 2.0 * CELL_PADDING,
+            );
+            text(
+                &mut self.current,
+                Font::Regular,
+                TEXT_SIZE,
+                column.x + CELL_PADDING,
+                self.y,
+                &cell,
+            );
+        }
+    }
+
+    ///
+    /// une ligne de texte sur toute la largeur, en dehors du tableau (total, remarque)
+    ///
+    pub fn line(&mut self, content: &str) {
+        if self.y - 2.0 * LINE_HEIGHT < BODY_BOTTOM {
+            self.new_page();
+        }
+        self.y -= 2.0 * LINE_HEIGHT;
+        let content = fit(content, Font::Bold, TEXT_SIZE, PAGE_WIDTH - 2.0 * MARGIN);
+        text(
+            &mut self.current,
+            Font::Bold,
+            TEXT_SIZE,
+            MARGIN,
+            self.y,
+            &content,
+        );
+    }
+
+    ///
+    /// ajoute l'en-tête et le pied de chaque page (le nombre de pages est alors connu)
+    /// et renvoie le fichier PDF
+    ///
+    pub fn finish(mut self) -> Vec<u8> {
+        self.pages.push(std::mem::take(&mut self.current));
+        let count = self.pages.len();
+
+        let pages: Vec<String> = self
+            .pages
+            .iter()
+            .enumerate()
+            .map(|(index, body)| {
+                let mut page = String::new();
+                let top = PAGE_HEIGHT - MARGIN - TITLE_SIZE;
+                let title = fit(
+                    &self.title,
+                    Font::Bold,
+                    TITLE_SIZE,
+                    PAGE_WIDTH - 2.0 * MARGIN - 80.0,
+                );
+                text(&mut page, Font::Bold, TITLE_SIZE, MARGIN, top, &title);
+                let date = format!("imprimé le {}", self.date);
+                let x = PAGE_WIDTH - MARGIN - text_width(&date, Font::Regular, SMALL_SIZE);
+                text(&mut page, Font::Regular, SMALL_SIZE, x, top, &date);
+
+                let footer = format!("Page {} / {}", index + 1, count);
+                let x = (PAGE_WIDTH - text_width(&footer, Font::Regular, SMALL_SIZE)) / 2.0;
+                text(&mut page, Font::Regular, SMALL_SIZE, x, MARGIN, &footer);
+                hline(&mut page, MARGIN + 12.0);
+
+                page.push_str(body);
+                page
+            })
+            .collect();
+
+        write_document(&self.title, &pages)
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(std::mem::take(&mut self.current));
+        self.start_page();
+    }
+
+    // les en-têtes des colonnes, répétés sur chaque page
+    fn start_page(&mut self) {
+        let y = BODY_TOP + 14.0;
+        hline(&mut self.current, y + 16.0);
+        for column in &self.columns {
+            let header = fit(
+                &column.header,
+                Font::Bold,
+                TEXT_SIZE,
+                column.width - 2.0 * CELL_PADDING,
+            );
+            text(
+                &mut self.current,
+                Font::Bold,
+                TEXT_SIZE,
+                column.x + CELL_PADDING,
+                y,
+                &header,
+            );
+        }
+        hline(&mut self.current, y - 5.0);
+        self.y = BODY_TOP;
+    }
+}
+
+///
+/// réponse HTTP avec le PDF, affiché par le navigateur (ou téléchargé sous file_name)
+///
+pub fn pdf_response(file_name: &str, content: Vec<u8>) -> Response {
+    let mut response = content.into_response();
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/pdf"),
+    );
+    if let Ok(value) = HeaderValue::from_str(&format!("inline; filename=\"{}\"", file_name)) {
+        headers.insert(header::CONTENT_DISPOSITION, value);
+    }
+    response
+}
+
+//*******************************************************************************
+// Écriture du contenu des pages
+//
+
+fn text(out: &mut String, font: Font, size: f32, x: f32, y: f32, content: &str) {
+    let _ = writeln!(
+        out,
+        "BT {} {} Tf {:.2} {:.2} Td ({}) Tj ET",
+        font.name(),
+        size,
+        x,
+        y,
+        escape(content)
+    );
+}
+
+// trait horizontal sur toute la largeur utile
+fn hline(out: &mut String, y: f32) {
+    let _ = writeln!(
+        out,
+        "0.5 w {:.2} {:.2} m {:.2} {:.2} l S",
+        MARGIN,
+        y,
+        PAGE_WIDTH - MARGIN,
+        y
+    );
+}
+
+///
+/// coupe le texte pour qu'il tienne dans la largeur donnée
+///
+fn fit(content: &str, font: Font, size: f32, width: f32) -> String {
+    if text_width(content, font, size) <= width {
+        return content.to_string();
+    }
+    let ellipsis = text_width("…", font, size);
+    let mut fitted = String::new();
+    let mut used = 0.0;
+    for c in content.chars() {
+        let w = char_width(c, font) * size / 1000.0;
+        if used + w + ellipsis > width {
+            break;
+        }
+        used += w;
+        fitted.push(c);
+    }
+    fitted.truncate(fitted.trim_end().len());
+    fitted.push('…');
+    fitted
+}
+
+fn text_width(content: &str, font: Font, size: f32) -> f32 {
+    content.chars().map(|c| char_width(c, font)).sum::<f32>() * size / 1000.0
+}
+
+///
+/// les chaînes PDF : ( ) et \ sont échappés,
+/// les octets WinAnsi hors ASCII sont écrits en octal
+///
+fn escape(content: &str) -> String {
+    let mut escaped = String::with_capacity(content.len());
+    for c in content.chars() {
+        match win_ansi(c) {
+            b'(' => escaped.push_str("\\("),
+            b')' => escaped.push_str("\\)"),
+            b'\\' => escaped.push_str("\\\\"),
+            byte @ 32..=126 => escaped.push(byte as char),
+            byte => {
+                let _ = write!(escaped, "\\{:03o}", byte);
+            }
+        }
+    }
+    escaped
+}
+
+///
+/// le code WinAnsi (Windows-1252) d'un caractère : Latin-1 et quelques signes
+/// typographiques ; les autres deviennent "?"
+///
+fn win_ansi(c: char) -> u8 {
+    match c {
+        ' '..='~' => c as u8,
+        '\u{a0}'..='\u{ff}' => c as u32 as u8,
+        '€' => 0x80,
+        '‚' => 0x82,
+        '„' => 0x84,
+        '…' => 0x85,
+        'Š' => 0x8a,
+        'Œ' => 0x8c,
+        'Ž' => 0x8e,
+        '‘' => 0x91,
+        '’' => 0x92,
+        '“' => 0x93,
+        '”' => 0x94,
+        '•' => 0x95,
+        '–' => 0x96,
+        '—' => 0x97,
+        '™' => 0x99,
+        'š' => 0x9a,
+        'œ' => 0x9c,
+        'ž' => 0x9e,
+        'Ÿ' => 0x9f,
+        '\t' | '\n' | '\r' => b' ',
+        _ => b'?',
+    }
+}
+
+// largeurs Helvetica et Helvetica-Bold des caractères ASCII 32 à 126,
+// en millièmes de la taille de la police (fichiers AFM d'Adobe)
+#[rustfmt::skip]
+const REGULAR_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+#[rustfmt::skip]
+const BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611,
+    975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584, 556,
+    333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611, 611,
+    611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+
+///
+/// largeur d'un caractère ; une lettre accentuée a la largeur de sa lettre de base
+///
+fn char_width(c: char, font: Font) -> f32 {
+    let base = match c {
+        'À'..='Å' => 'A',
+        'Ç' => 'C',
+        'È'..='Ë' => 'E',
+        'Ì'..='Ï' => 'I',
+        'Ñ' => 'N',
+        'Ò'..='Ö' | 'Ø' => 'O',
+        'Ù'..='Ü' => 'U',
+        'Ý' | 'Ÿ' => 'Y',
+        'à'..='å' => 'a',
+        'ç' => 'c',
+        'è'..='ë' => 'e',
+        'ì'..='ï' => 'i',
+        'ñ' => 'n',
+        'ò'..='ö' | 'ø' => 'o',
+        'ù'..='ü' => 'u',
+        'ý' | 'ÿ' => 'y',
+        'Œ' | 'Æ' | '…' | '—' | '‰' => return 1000.0,
+        'œ' | 'æ' => return if font == Font::Bold { 944.0 } else { 889.0 },
+        c => c,
+    };
+    let widths = match font {
+        Font::Regular => &REGULAR_WIDTHS,
+        Font::Bold => &BOLD_WIDTHS,
+    };
+    match base {
+        ' '..='~' => widths[base as usize - 32] as f32,
+        _ => 556.0,
+    }
+}
+
+//*******************************************************************************
+// Écriture du fichier : objets, table des références et trailer
+//
+
+///
+/// objets 1 : catalogue, 2 : arbre des pages, 3 et 4 : polices, 5 : informations,
+/// puis pour chaque page son objet Page et son contenu
+///
+fn write_document(title: &str, pages: &[String]) -> Vec<u8> {
+    let mut objects: Vec<String> = Vec::new();
+    let first_page = 6;
+    let kids = (0..pages.len())
+        .map(|index| format!("{} 0 R", first_page + 2 * index))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    objects.push("<< /Type /Catalog /Pages 2 0 R >>".to_string());
+    objects.push(format!(
+        "<< /Type /Pages /Kids [{}] /Count {} >>",
+        kids,
+        pages.len()
+    ));
+    for base_font in ["Helvetica", "Helvetica-Bold"] {
+        objects.push(format!(
+            "<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+            base_font
+        ));
+    }
+    objects.push(format!(
+        "<< /Title ({}) /Producer (partitions) /CreationDate (D:{}) >>",
+        escape(title),
+        chrono::Local::now().format("%Y%m%d%H%M%S")
+    ));
+    for (index, content) in pages.iter().enumerate() {
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+             /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+            PAGE_WIDTH,
+            PAGE_HEIGHT,
+            first_page + 2 * index + 1
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            content.len(),
+            content
+        ));
+    }
+
+    // le contenu est en ASCII (escape) : la longueur en octets est celle de la String
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        let _ = writeln!(pdf, "{} 0 obj\n{}\nendobj", index + 1, object);
+    }
+
+    let xref = pdf.len();
+    let _ = writeln!(pdf, "xref\n0 {}\n0000000000 65535 f ", objects.len() + 1);
+    for offset in offsets {
+        let _ = writeln!(pdf, "{:010} 00000 n ", offset);
+    }
+    let _ = writeln!(
+        pdf,
+        "trailer\n<< /Size {} /Root 1 0 R /Info 5 0 R >>\nstartxref\n{}\n%%EOF",
+        objects.len() + 1,
+        xref
+    );
+
+    pdf.into_bytes()
+}
@@ -9,13 +9,18 @@
 //***********************************************************************************
 
 use axum::{
+    handler::Handler,
+    middleware::from_fn,
     routing::{get, post},
     Router,
 };
 
+use crate::auth::roles::{require_admin, require_editor};
+
 use crate::handlers::{
-    genres_hdl::*, helpers_hdl::*, list_users_hdl::*, login_hdl::*, musicians_hdl::*,
-    partitions_hdl::*, sign_up_hdl::*,
+    api_hdl::*, attachments_hdl::*, audit_hdl::*, contributors_hdl::*, csv_hdl::*, genres_hdl::*,
+    helpers_hdl::*, library_hdl::*, list_users_hdl::*, login_hdl::*, musicians_hdl::*,
+    partitions_hdl::*, setlists_hdl::*, sign_up_hdl::*, tags_hdl::*, token_hdl::*, trash_hdl::*,
 };
 
 ///
@@ -29,7 +34,13 @@ pub fn router() -> Router {
         .nest("/persons", musicians_routes())
         .nest("/genres", genres_routes())
         .nest("/partitions", partitions_routes())
+        .nest("/tags", tags_routes())
+        .nest("/loans", loans_routes())
+        .nest("/setlists", setlists_routes())
         .nest("/auth", authentication_routes())
+        .nest("/trash", trash_routes())
+        .nest("/audit", audit_routes())
+        .nest("/api/v1", api_routes())
         .nest("/about", get(about))
         .route("/favicon.png", get(favicon))
 }
@@ -40,12 +51,21 @@ pub fn router() -> Router {
 ///
 pub fn musicians_routes() -> Router {
     Router::new()
-        //.route("/", get(askama_list_persons_hdl))
-        .route("/", get(list_persons_hdl))
+        // routes qui modifient la DB : réservées aux éditeurs
         .route("/add", post(create_person_hdl))
         .route("/:id", post(update_person_hdl))
-        .route("/delete/:id", post(delete_person_hdl))
+        .route(
+            "/delete/:id",
+            get(confirm_delete_person_hdl).post(delete_person_hdl),
+        )
+        .route("/duplicates", get(list_duplicate_persons_hdl))
+        .route("/merge", post(merge_persons_hdl))
+        .route_layer(from_fn(require_editor))
+        //.route("/", get(askama_list_persons_hdl))
+        .route("/", get(list_persons_hdl))
         .route("/print", get(print_list_persons_hdl))
+        .route("/print.pdf", get(print_pdf_persons_hdl))
+        .route("/export.csv", get(export_persons_hdl))
         .route("/find", post(find_person_by_name_hdl))
 }
 
@@ -55,28 +75,103 @@ pub fn musicians_routes() -> Router {
 ///
 pub fn genres_routes() -> Router {
     Router::new()
-        .route("/", get(list_genres_hdl))
+        // routes qui modifient la DB : réservées aux éditeurs
         .route("/add", post(create_genre_hdl))
         .route("/:id", post(update_genre_hdl))
-        .route("/delete/:id", post(delete_genre_hdl))
+        .route(
+            "/delete/:id",
+            get(confirm_delete_genre_hdl).post(delete_genre_hdl),
+        )
+        .route("/duplicates", get(list_duplicate_genres_hdl))
+        .route("/merge", post(merge_genres_hdl))
+        .route_layer(from_fn(require_editor))
+        .route("/", get(list_genres_hdl))
         .route("/print", get(print_list_genres_hdl))
+        .route("/print.pdf", get(print_pdf_genres_hdl))
+        .route("/export.csv", get(export_genres_hdl))
         .route("/find", post(find_genre_by_name_hdl))
 }
 
+///
+/// gère les routes vers la page de gestion des étiquettes
+/// la route "/" correspond à "/tags"
+///
+pub fn tags_routes() -> Router {
+    Router::new()
+        // routes qui modifient la DB : réservées aux éditeurs
+        .route("/add", post(create_tag_hdl))
+        .route("/:id", post(update_tag_hdl))
+        .route("/delete/:id", post(delete_tag_hdl))
+        .route_layer(from_fn(require_editor))
+        .route("/", get(list_tags_hdl))
+}
+
+///
+/// gère les routes vers les pages des prêts
+/// "/overdue" (retards) est réservée aux éditeurs,
+/// "/mine" montre les emprunts de l'utilisateur connecté
+///
+pub fn loans_routes() -> Router {
+    Router::new()
+        .route("/overdue", get(list_overdue_loans_hdl))
+        .route_layer(from_fn(require_editor))
+        .route("/mine", get(list_my_loans_hdl))
+}
+
 ///
 /// gère les routes vers les pages de gestion des partitions
 /// la route "/" correspond à "/partitions"
 ///
 pub fn partitions_routes() -> Router {
     Router::new()
-        .route("/", get(list_partitions_hdl))
+        // routes qui modifient la DB : réservées aux éditeurs
         .route("/add", post(create_partition_hdl))
         .route("/:id", post(update_partition_hdl))
         .route("/delete/:id", post(delete_partition_hdl))
+        .route("/attachments/:id", post(upload_attachments_hdl))
+        .route("/attachments/delete/:id", post(delete_attachment_hdl))
+        .route("/contributors/:id", post(add_contributor_hdl))
+        .route("/contributors/delete/:id", post(delete_contributor_hdl))
+        .route("/genres/:id", post(add_partition_genre_hdl))
+        .route("/genres/delete/:id", post(delete_partition_genre_hdl))
+        .route("/tags/:id", post(add_partition_tag_hdl))
+        .route("/tags/delete/:id", post(delete_partition_tag_hdl))
+        .route("/copies/:id", post(add_copy_hdl))
+        .route("/copies/update/:id", post(update_copy_hdl))
+        .route("/copies/delete/:id", post(delete_copy_hdl))
+        .route("/loans/:id", post(add_loan_hdl))
+        .route("/loans/return/:id", post(return_loan_hdl))
+        .route("/import", get(import_form_hdl).post(import_partitions_hdl))
+        .route("/import/preview", post(preview_import_hdl))
+        .route_layer(from_fn(require_editor))
+        .route("/", get(list_partitions_hdl))
+        .route("/show/:id", get(show_partition_hdl))
+        .route("/attachments/view/:id", get(view_attachment_hdl))
+        .route("/attachments/download/:id", get(download_attachment_hdl))
         .route("/print", get(print_list_partitions_hdl))
-        .route("/find/title", post(find_partition_title_hdl))
-        .route("/find/genre", post(find_partition_genre_hdl))
-        .route("/find/author", post(find_partition_author_hdl))
+        .route("/print.pdf", get(print_pdf_partitions_hdl))
+        .route("/export.csv", get(export_partitions_hdl))
+        .route("/search", get(search_partitions_hdl))
+}
+
+///
+/// gère les routes vers les pages des programmes de concert
+/// la route "/" correspond à "/setlists"
+///
+pub fn setlists_routes() -> Router {
+    Router::new()
+        // routes qui modifient la DB : réservées aux éditeurs
+        .route("/add", post(create_setlist_hdl))
+        .route("/:id", post(update_setlist_hdl))
+        .route("/delete/:id", post(delete_setlist_hdl))
+        .route("/items/:id", post(add_setlist_item_hdl))
+        .route("/items/update/:id", post(update_setlist_item_hdl))
+        .route("/items/delete/:id", post(delete_setlist_item_hdl))
+        .route("/items/move/:id", post(move_setlist_item_hdl))
+        .route_layer(from_fn(require_editor))
+        .route("/", get(list_setlists_hdl))
+        .route("/show/:id", get(show_setlist_hdl))
+        .route("/print/:id", get(print_setlist_hdl))
 }
 
 ///
@@ -85,7 +180,77 @@ pub fn partitions_routes() -> Router {
 ///
 pub fn authentication_routes() -> Router {
     Router::new()
+        // la liste des utilisateurs et leurs rôles sont réservés aux administrateurs
+        .route("/users", get(print_list_users_hdl))
+        .route("/users/role/:id", post(update_user_role_hdl))
+        .route_layer(from_fn(require_admin))
         .route("/signup", get(get_sign_up_hdl).post(sign_up_hdl))
         .route("/login", get(login_form_hdl).post(login_hdl))
-        .route("/users", get(print_list_users_hdl))
+        .route("/logout", post(logout_hdl))
+        .route("/token", post(token_hdl))
+        .route("/token/refresh", post(refresh_token_hdl))
+}
+
+///
+/// gère les routes vers les corbeilles
+/// "/partitions" correspond à "/trash/partitions" ; de même pour persons et genres
+/// réservé aux éditeurs
+///
+pub fn trash_routes() -> Router {
+    Router::new()
+        .route("/:kind", get(list_trash_hdl))
+        .route("/:kind/restore/:id", post(restore_trash_hdl))
+        .route("/:kind/purge/:id", post(purge_trash_hdl))
+        .route_layer(from_fn(require_editor))
+}
+
+///
+/// gère les routes vers le journal des modifications
+/// réservé aux administrateurs
+///
+pub fn audit_routes() -> Router {
+    Router::new()
+        .route("/", get(list_audit_hdl))
+        .route("/:entity_type/:id", get(entity_history_hdl))
+        .route_layer(from_fn(require_admin))
+}
+
+///
+/// gère les routes de l'API JSON
+/// la route "/persons" correspond à "/api/v1/persons"
+/// les méthodes POST, PUT et DELETE sont réservées aux éditeurs
+///
+pub fn api_routes() -> Router {
+    Router::new()
+        .route(
+            "/persons",
+            get(api_list_persons_hdl).post(api_create_person_hdl.layer(from_fn(require_editor))),
+        )
+        .route(
+            "/persons/:id",
+            get(api_get_person_hdl)
+                .put(api_update_person_hdl.layer(from_fn(require_editor)))
+                .delete(api_delete_person_hdl.layer(from_fn(require_editor))),
+        )
+        .route(
+            "/genres",
+            get(api_list_genres_hdl).post(api_create_genre_hdl.layer(from_fn(require_editor))),
+        )
+        .route(
+            "/genres/:id",
+            get(api_get_genre_hdl)
+                .put(api_update_genre_hdl.layer(from_fn(require_editor)))
+                .delete(api_delete_genre_hdl.layer(from_fn(require_editor))),
+        )
+        .route(
+            "/partitions",
+            get(api_list_partitions_hdl)
+                .post(api_create_partition_hdl.layer(from_fn(require_editor))),
+        )
+        .route(
+            "/partitions/:id",
+            get(api_get_partition_hdl)
+                .put(api_update_partition_hdl.layer(from_fn(require_editor)))
+                .delete(api_delete_partition_hdl.layer(from_fn(require_editor))),
+        )
 }
@@ -0,0 +1,74 @@
+//! src/storage/local.rs
+//!
+//! Storage sur le système de fichiers local
+//! le fichier de clé "abcdef..." est rangé dans STORAGE_DIR/ab/abcdef...
+
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use axum::async_trait;
+use axum::body::Bytes;
+
+use crate::storage::Storage;
+
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> anyhow::Result<PathBuf> {
+        // la clé est une empreinte hexadécimale : rien d'autre n'est accepté
+        // pour ne jamais sortir du répertoire de stockage
+        if key.len() < 3 || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+            anyhow::bail!("clé de stockage invalide : {}", key);
+        }
+        Ok(self.root.join(&key[..2]).join(key))
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, content: Bytes) -> anyhow::Result<()> {
+        let path = self.path(key)?;
+        if tokio::fs::metadata(&path).await.is_ok() {
+            return Ok(());
+        }
+        let dir = path.parent().expect("le chemin a toujours un parent");
+        tokio::fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("création du répertoire {:?}", dir))?;
+
+        // écriture dans un fichier temporaire puis renommage :
+        // un fichier interrompu n'est jamais visible sous sa clé
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, &content)
+            .await
+            .with_context(|| format!("écriture de {:?}", tmp))?;
+        tokio::fs::rename(&tmp, &path)
+            .await
+            .with_context(|| format!("renommage de {:?}", tmp))?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Bytes> {
+        let path = self.path(key)?;
+        let content = tokio::fs::read(&path)
+            .await
+            .with_context(|| format!("lecture de {:?}", path))?;
+        Ok(Bytes::from(content))
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        let path = self.path(key)?;
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e).with_context(|| format!("suppression de {:?}", path)),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! src/storage/mod.rs
+//!
+//! Stockage du contenu des fichiers attachés aux partitions
+//!
+//! Le trait Storage cache l'emplacement réel des fichiers :
+//! les handlers ne voient qu'une clé (l'empreinte SHA3-256 du contenu).
+//! Un autre backend (S3, ...) n'a qu'à implémenter Storage
+//! et à être ajouté dans storage_from_env.
+//!
+//! Dans le fichier .env :
+//! STORAGE_BACKEND (défaut "local"), STORAGE_DIR (défaut "./storage")
+
+pub mod local;
+
+use std::sync::Arc;
+
+use axum::async_trait;
+use axum::body::Bytes;
+use sha3::{Digest, Sha3_256};
+
+use crate::storage::local::LocalStorage;
+
+#[async_trait]
+pub trait Storage: Send + Sync {
+    ///
+    /// range le contenu sous la clé ; une clé déjà présente n'est pas réécrite
+    ///
+    async fn put(&self, key: &str, content: Bytes) -> anyhow::Result<()>;
+
+    async fn get(&self, key: &str) -> anyhow::Result<Bytes>;
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()>;
+}
+
+///
+/// le Storage partagé entre les handlers via Extension
+///
+pub type SharedStorage = Arc<dyn Storage>;
+
+///
+/// construit le Storage choisi dans le fichier .env
+///
+pub fn storage_from_env() -> anyhow::Result<SharedStorage> {
+    let backend = std::env::var("STORAGE_BACKEND").unwrap_or_else(|_| "local".to_string());
+    match backend.as_str() {
+        "local" => {
+            let dir = std::env::var("STORAGE_DIR").unwrap_or_else(|_| "./storage".to_string());
+            tracing::info!("stockage des fichiers dans : {}", dir);
+            Ok(Arc::new(LocalStorage::new(dir)))
+        }
+        other => anyhow::bail!("STORAGE_BACKEND inconnu : {}", other),
+    }
+}
+
+///
+/// empreinte SHA3-256 en hexadécimal, utilisée comme clé du contenu
+///
+pub fn content_hash(content: &[u8]) -> String {
+    format!("{:x}", Sha3_256::digest(content))
+}
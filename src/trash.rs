@@ -0,0 +1,101 @@
+//! src/trash.rs
+//!
+//! Purge de la corbeille (db::trash)
+//!
+//! Une ligne effacée reste à la corbeille TRASH_RETENTION_DAYS jours (30 par défaut,
+//! 0 pour la garder jusqu'à sa purge à la main), puis une tâche de fond,
+//! lancée au démarrage et répétée toutes les heures, l'efface pour de bon :
+//! les partitions d'abord, puis les musiciens et les genres qu'elles ne retiennent plus.
+//! La purge d'une partition retire aussi du stockage ses fichiers devenus inutiles.
+
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use sqlx::PgPool;
+
+use crate::db::trash::{list_expired, purge};
+use crate::error::AppError;
+use crate::handlers::attachments_hdl::{attachment_hashes, purge_unused_contents};
+use crate::models::audit::Actor;
+use crate::models::deletion::Deletion;
+use crate::models::trash::TrashKind;
+use crate::storage::SharedStorage;
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+static RETENTION_DAYS: Lazy<i32> = Lazy::new(|| {
+    std::env::var("TRASH_RETENTION_DAYS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(30)
+});
+
+///
+/// le délai avant la purge automatique, None si elle est désactivée
+///
+pub(crate) fn retention_days() -> Option<i32> {
+    Some(*RETENTION_DAYS).filter(|&days| days > 0)
+}
+
+///
+/// efface une ligne de la corbeille pour de bon,
+/// avec les fichiers d'une partition qu'aucune autre n'utilise
+///
+pub(crate) async fn purge_with_files(
+    kind: TrashKind,
+    id: i32,
+    actor: &Actor,
+    storage: &SharedStorage,
+    pool: &PgPool,
+) -> Result<Deletion, AppError> {
+    let hashes = match kind {
+        TrashKind::Partitions => attachment_hashes(id, pool).await?,
+        _ => Vec::new(),
+    };
+    let deletion = purge(kind, id, actor, pool).await?;
+    if let Deletion::Deleted { .. } = deletion {
+        purge_unused_contents(&hashes, storage, pool).await?;
+    }
+    Ok(deletion)
+}
+
+///
+/// lance la purge automatique en tâche de fond (rien si elle est désactivée)
+///
+pub fn spawn_purge_task(pool: PgPool, storage: SharedStorage) {
+    let days = match retention_days() {
+        Some(days) => days,
+        None => {
+            tracing::info!("corbeille : purge automatique désactivée");
+            return;
+        }
+    };
+    tracing::info!("corbeille : purge après {} jours", days);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = purge_expired(days, &storage, &pool).await {
+                tracing::error!("corbeille : erreur de purge : {:?}", err);
+            }
+        }
+    });
+}
+
+async fn purge_expired(days: i32, storage: &SharedStorage, pool: &PgPool) -> Result<(), AppError> {
+    let actor = Actor {
+        id: None,
+        name: String::from("purge automatique"),
+    };
+    for kind in [TrashKind::Partitions, TrashKind::Persons, TrashKind::Genres] {
+        for id in list_expired(kind, days, pool).await? {
+            // un musicien ou un genre encore utilisé attend la purge de ses partitions
+            if let Deletion::Deleted { name, .. } =
+                purge_with_files(kind, id, &actor, storage, pool).await?
+            {
+                tracing::info!("corbeille : {} purgé : {}", kind.label(), name);
+            }
+        }
+    }
+    Ok(())
+}
@@ -2,3 +2,5 @@
 
 pub mod auth_utils;
 pub mod print_request_response;
+pub mod serde_utils;
+pub mod validation_utils;
@@ -0,0 +1,118 @@
+//! src/utils/serde_utils.rs
+//!
+//! Aides pour la désérialisation des formulaires et des query strings
+
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use serde::{de, Deserializer};
+
+///
+/// un champ vide d'un formulaire (?title=&genre=Jazz) devient None
+/// au lieu de Some("") ou d'une erreur pour les nombres
+/// les nombres du JSON (API) sont aussi acceptés
+///
+pub fn empty_string_as_none<'de, D, T>(de: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let text = de.deserialize_any(OptionalTextVisitor)?;
+    match text.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(s) => FromStr::from_str(s).map_err(de::Error::custom).map(Some),
+    }
+}
+
+///
+/// durée en secondes, entrée sous la forme "245", "4:05" ou "1:02:30"
+/// un champ vide devient None
+///
+pub fn duration_as_seconds<'de, D>(de: D) -> Result<Option<i32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let text = de.deserialize_any(OptionalTextVisitor)?;
+    let text = match text.as_deref().map(str::trim) {
+        None | Some("") => return Ok(None),
+        Some(s) => s.to_string(),
+    };
+
+    parse_duration(&text)
+        .map(Some)
+        .ok_or_else(|| de::Error::custom(format!("durée incorrecte : {}", text)))
+}
+
+///
+/// "245", "4:05" ou "1:02:30" en secondes, None si le texte n'est pas une durée
+/// (utilisée aussi par l'import CSV)
+///
+pub fn parse_duration(text: &str) -> Option<i32> {
+    let mut seconds: i32 = 0;
+    for (i, part) in text.trim().split(':').enumerate() {
+        let value: i32 = part.trim().parse().ok()?;
+        if i > 2 || value < 0 || (i > 0 && value >= 60) {
+            return None;
+        }
+        seconds = seconds.checked_mul(60)?.checked_add(value)?;
+    }
+    Some(seconds)
+}
+
+///
+/// l'inverse de parse_duration : 245 -> "4:05", 4530 -> "1:15:30"
+///
+pub fn format_duration(seconds: i32) -> String {
+    let (hours, minutes, rest) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, rest)
+    } else {
+        format!("{}:{:02}", minutes, rest)
+    }
+}
+
+///
+/// lit une valeur texte ou numérique (formulaire ou JSON) sous forme de texte
+///
+struct OptionalTextVisitor;
+
+impl<'de> de::Visitor<'de> for OptionalTextVisitor {
+    type Value = Option<String>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("un texte, un nombre ou rien")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(Some(v.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+        Ok(Some(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(Some(v.to_string()))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(Some(v.to_string()))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(Some(v.to_string()))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, de: D) -> Result<Self::Value, D::Error> {
+        de.deserialize_any(self)
+    }
+}
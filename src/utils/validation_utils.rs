@@ -0,0 +1,30 @@
+//! src/utils/validation_utils.rs
+//!
+//! Règles communes des noms et des titres saisis dans les formulaires,
+//! l'API et l'import CSV (PersonName, GenreName, PartitionTitle)
+
+use unicode_segmentation::UnicodeSegmentation;
+
+///
+/// nettoie un nom ou un titre : les espaces du début et de la fin sont retirés
+/// et ceux du milieu (tabulations, retours à la ligne, ...) réduits à un seul
+/// what : le nom du champ dans le message d'erreur ("le titre", ...)
+/// Returns the cleaned text, or the error message when it is empty,
+/// longer than max_length characters or has control characters
+///
+pub fn parse_text(s: &str, max_length: usize, what: &str) -> Result<String, String> {
+    if s.chars().any(|c| c.is_control() && !c.is_whitespace()) {
+        return Err(format!("{} contient des caractères interdits", what));
+    }
+    let text = s.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        return Err(format!("{} est obligatoire", what));
+    }
+    if text.graphemes(true).count() > max_length {
+        return Err(format!(
+            "{} ne peut pas dépasser {} caractères",
+            what, max_length
+        ));
+    }
+    Ok(text)
+}